@@ -1,18 +1,32 @@
 use std::{
+  collections::HashMap,
   io::ErrorKind,
   sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+    atomic::{AtomicBool, AtomicU16, Ordering},
+    Arc, Mutex,
   },
+  time::{Duration, Instant},
 };
 
 use car_utils::{
-  buffer::RingBuffer, command::Command, Response as Statistics, ResponseType, REQUEST_HEADER_LEN, RESPONSE_HEADER_LEN,
+  buffer::RingBuffer, command::Command, mission::Measurement, Ack, Alarm, RequestError, Response as Statistics,
+  ResponseType, Sample, Scan, RESPONSE_HEADER_LEN,
 };
 use num_traits::FromPrimitive;
+use serde::Serialize;
 use tauri::{Emitter, Error, Listener};
 use tokio::{net::TcpStream, sync::Notify};
 
+const ACK_TIMEOUT: Duration = Duration::from_secs(2); // 超过该时间没有收到处理结果视为没有回复
+
+/// 命令的处理结果, 发送给前端
+#[derive(Serialize, Clone)]
+struct CommandResult {
+  seq: u16,
+  command: Command,
+  error: Option<RequestError>, // 成功为 None
+}
+
 /// TODO: 字节序
 /// TODO: 客户端卡死问题 ？
 /// 连接
@@ -30,7 +44,9 @@ pub async fn connect(window: tauri::Window, addr: &str) -> Result<(), Error> {
     .map_err(Error::Io)?;
   let stream = Arc::new(stream);
   let should_shutdown = Arc::new(AtomicBool::new(false));
-  let (tx_notify, tx_buffer) = (Arc::new(Notify::new()), Arc::new(std::sync::Mutex::new(RingBuffer::<1024>::new())));
+  let (tx_notify, tx_buffer) = (Arc::new(Notify::new()), Arc::new(Mutex::new(RingBuffer::<1024>::new())));
+  // 已发送但还没有收到处理结果的命令
+  let (seq, pending) = (Arc::new(AtomicU16::new(0)), Arc::new(Mutex::new(HashMap::<u16, (Command, Instant)>::new())));
 
   let mut listen_ids = Vec::new();
   listen_ids.push(window.listen("close-server", {
@@ -42,22 +58,26 @@ pub async fn connect(window: tauri::Window, addr: &str) -> Result<(), Error> {
   }));
   listen_ids.push(window.listen("command-server", {
     let (tx_notify, tx_buffer) = (Arc::clone(&tx_notify), Arc::clone(&tx_buffer));
+    let (seq, pending) = (Arc::clone(&seq), Arc::clone(&pending));
 
     move |event| match serde_json::from_str::<Command>(event.payload()) {
       Ok(command) => {
+        let seq = seq.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        let Some(request) = command.to_request(seq) else {
+          println!("命令过长 {:?}", command);
+          return;
+        };
         let mut tx_buffer = tx_buffer.lock().unwrap();
-        if tx_buffer.free_len() < command.buf_len() + REQUEST_HEADER_LEN {
+        if tx_buffer.free_len() < request.len() {
           println!("丢弃 {:?}", command);
           return;
         }
-        tx_buffer.enqueue_one(command.buf_len() as u8).unwrap();
         // TODO: refactor
-        let mut buf = vec![0; command.buf_len()];
-        command.write(&mut buf);
-        buf.into_iter().for_each(|x| {
+        request.into_iter().for_each(|x| {
           tx_buffer.enqueue_one(x).unwrap();
         });
 
+        pending.lock().unwrap().insert(seq, (command, Instant::now()));
         tx_notify.notify_one();
       }
       Err(_) => {
@@ -95,8 +115,21 @@ pub async fn connect(window: tauri::Window, addr: &str) -> Result<(), Error> {
 
   // 接受数据并发送给前端
   let mut rx_buffer = RingBuffer::<1024>::new();
+  // 推送的统计信息只包含订阅的主题中的字段, 合并后发送给前端
+  let mut statistics = serde_json::to_value(Statistics::default()).unwrap();
   while !should_shutdown.load(Ordering::Acquire) {
-    stream.readable().await.ok();
+    // 定时醒来检查超时的命令
+    let _ = tokio::time::timeout(ACK_TIMEOUT / 4, stream.readable()).await;
+    pending.lock().unwrap().retain(|&seq, (command, sent)| {
+      if sent.elapsed() < ACK_TIMEOUT {
+        return true;
+      }
+      let result = CommandResult { seq, command: command.clone(), error: Some(RequestError::NoReply) };
+      let _ = window.emit("command-result", result).inspect_err(|e| {
+        println!("{:?}", e);
+      });
+      false
+    });
 
     let _ = rx_buffer.enqueue_with(|buf| {
       match stream.try_read(buf) {
@@ -119,8 +152,15 @@ pub async fn connect(window: tauri::Window, addr: &str) -> Result<(), Error> {
       }
     });
 
-    while rx_buffer.peek_one().map_or(false, |len| rx_buffer.len() >= len as usize + RESPONSE_HEADER_LEN) {
-      let len = rx_buffer.dequeue_one().unwrap() as usize; // 负载长度
+    // 负载长度为包头的前两个字节 (大端)
+    while let Some(len) = rx_buffer
+      .peek(0)
+      .zip(rx_buffer.peek(1))
+      .map(|(high, low)| u16::from_be_bytes([high, low]) as usize)
+      .filter(|len| rx_buffer.len() >= len + RESPONSE_HEADER_LEN)
+    {
+      rx_buffer.dequeue_one();
+      rx_buffer.dequeue_one();
       let response_type = rx_buffer.dequeue_one().unwrap();
 
       rx_buffer.congestion_alloced(len);
@@ -129,7 +169,9 @@ pub async fn connect(window: tauri::Window, addr: &str) -> Result<(), Error> {
         match ResponseType::from_u8(response_type) {
           Some(resp) => match resp {
             ResponseType::Statistics => {
-              let statistics = serde_json::from_slice::<Statistics>(payload).unwrap();
+              let fields = serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(payload).unwrap();
+              statistics.as_object_mut().unwrap().extend(fields);
+              let statistics = serde_json::from_value::<Statistics>(statistics.clone()).unwrap();
               println!("statistics: {:?}", statistics);
               let _ = window.emit("statistics", statistics).inspect_err(|e| {
                 println!("{:?}", e);
@@ -139,6 +181,51 @@ pub async fn connect(window: tauri::Window, addr: &str) -> Result<(), Error> {
               //   println!("{:?}", e);
               // });
             }
+            ResponseType::Scan => {
+              let scan = serde_json::from_slice::<Scan>(payload).unwrap();
+              let _ = window.emit("scan", scan).inspect_err(|e| {
+                println!("{:?}", e);
+              });
+            }
+            ResponseType::Log => {
+              let log = serde_json::from_slice::<String>(payload).unwrap();
+              let _ = window.emit("script-log", log).inspect_err(|e| {
+                println!("{:?}", e);
+              });
+            }
+            ResponseType::Measurement => {
+              let measurement = serde_json::from_slice::<Measurement>(payload).unwrap();
+              let _ = window.emit("measurement", measurement).inspect_err(|e| {
+                println!("{:?}", e);
+              });
+            }
+            ResponseType::Macros => {
+              let macros = serde_json::from_slice::<Vec<String>>(payload).unwrap();
+              let _ = window.emit("macros", macros).inspect_err(|e| {
+                println!("{:?}", e);
+              });
+            }
+            ResponseType::Alarm => {
+              let alarm = serde_json::from_slice::<Alarm>(payload).unwrap();
+              let _ = window.emit("alarm", alarm).inspect_err(|e| {
+                println!("{:?}", e);
+              });
+            }
+            ResponseType::Sample => {
+              let sample = serde_json::from_slice::<Sample>(payload).unwrap();
+              let _ = window.emit("sample", sample).inspect_err(|e| {
+                println!("{:?}", e);
+              });
+            }
+            ResponseType::Ack => {
+              let Ack { seq, error } = serde_json::from_slice::<Ack>(payload).unwrap();
+              let Some((command, _)) = pending.lock().unwrap().remove(&seq) else {
+                return;
+              };
+              let _ = window.emit("command-result", CommandResult { seq, command, error }).inspect_err(|e| {
+                println!("{:?}", e);
+              });
+            }
           },
           None => {
             println!("unknown response");
@@ -1,11 +1,14 @@
 mod command;
+mod stream;
 
 use command::connect;
+use stream::stream;
 use tauri::Manager;
 
 pub fn entry() {
-  let mut builder =
-    tauri::Builder::default().plugin(tauri_plugin_shell::init()).invoke_handler(tauri::generate_handler![connect]);
+  let mut builder = tauri::Builder::default()
+    .plugin(tauri_plugin_shell::init())
+    .invoke_handler(tauri::generate_handler![connect, stream]);
 
   #[cfg(debug_assertions)]
   {
@@ -0,0 +1,71 @@
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tauri::{Emitter, Error, Listener};
+use tokio::net::TcpStream;
+
+/// 接收小车的 MJPEG 推流, 每一帧以 base64 编码的 JPEG 触发 "frame" 事件
+/// addr: 推流地址, 如 192.168.1.2:5001
+#[tauri::command]
+pub async fn stream(window: tauri::Window, addr: &str) -> Result<(), Error> {
+  println!("stream from {}", addr);
+  let stream = TcpStream::connect(addr).await.map_err(Error::Io)?;
+  let should_shutdown = Arc::new(AtomicBool::new(false));
+
+  let listen_ids = ["close-stream", "close-server"].map(|event| {
+    let shutdown = Arc::clone(&should_shutdown);
+    window.listen(event, move |_event| shutdown.store(true, Ordering::Release))
+  });
+
+  stream.writable().await.map_err(Error::Io)?;
+  stream.try_write(b"GET / HTTP/1.0\r\n\r\n").map_err(Error::Io)?;
+
+  let mut buf = Vec::new();
+  let mut chunk = [0; 4096];
+  while !should_shutdown.load(Ordering::Acquire) {
+    stream.readable().await.ok();
+    match stream.try_read(&mut chunk) {
+      Ok(0) => break,
+      Ok(len) => buf.extend_from_slice(&chunk[..len]),
+      Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => continue,
+      Err(err) => {
+        println!("stream error: {}", err);
+        break;
+      }
+    }
+
+    while let Some(jpeg) = take_frame(&mut buf) {
+      let _ = window.emit("frame", STANDARD.encode(jpeg)).inspect_err(|e| println!("{:?}", e));
+    }
+  }
+
+  listen_ids.into_iter().for_each(|id| window.unlisten(id));
+  println!("推流关闭: {}", addr);
+  Ok(())
+}
+
+/// 从缓冲区取出一帧完整的 JPEG, 跳过 HTTP 头与没有内容的部分
+fn take_frame(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+  loop {
+    let header_end = buf.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let content_length = String::from_utf8_lossy(&buf[..header_end])
+      .lines()
+      .find_map(|line| line.strip_prefix("Content-Length:").and_then(|len| len.trim().parse::<usize>().ok()));
+
+    let body = header_end + 4;
+    match content_length {
+      Some(len) if buf.len() >= body + len => {
+        let jpeg = buf[body..body + len].to_vec();
+        buf.drain(..body + len);
+        return Some(jpeg);
+      }
+      Some(_) => return None, // 等待剩余的数据
+      None => {
+        buf.drain(..body);
+      }
+    }
+  }
+}
@@ -19,11 +19,22 @@ import { useNavigate } from "react-router-dom";
 import { NextUIProvider } from "@nextui-org/react";
 import DashBoard from "./pages/DashBoard";
 import Login from "./pages/Login";
-import { Statistics } from "car-utils";
+import { Command, RequestError, Statistics } from "car-utils";
 import { StatisticsContext } from "./context";
 
 ChartJS.register(CategoryScale, LinearScale, PointElement, LineElement, Title, Tooltip, Filler, Legend);
 
+// 命令失败的原因
+const requestErrors: Record<RequestError, string> = {
+  UnknownCommand: "小车不支持该命令",
+  BadLength: "命令格式错误",
+  InvalidAngle: "舵机角度超出 0-180",
+  InvalidArgument: "参数无效",
+  Busy: "其它功能运行中, 已忽略",
+  HardwareFailure: "硬件故障",
+  NoReply: "小车没有回复",
+};
+
 function App() {
   const navigate = useNavigate();
   const [isConnected, setIsConnected] = useState(false);
@@ -32,12 +43,29 @@ function App() {
 
   const [statistics, setStatistics] = useState<Statistics>({
     time_brightness: null,
+    nixie_mode: "Clock",
     speed_percent: 20,
     distance: null,
+    distance_status: { age: null, stale: false, error: null, samples: 0, failures: 0 },
     servos: 90,
-    led: false,
+    led: null,
+    led_color: [255, 255, 255],
+    led_brightness: 100,
     th: null,
-    trace: false,
+    th_status: { age: null, stale: false, error: null, samples: 0, failures: 0 },
+    trace: null,
+    trace_pid: [0.6, 0, 0.05],
+    marker: null,
+    stream: null,
+    camera: false,
+    scan: false,
+    autopilot: null,
+    mission: null,
+    script: false,
+    macro_state: null,
+    alarm: false,
+    survey: false,
+    sound: null,
   });
 
   // 监听事件
@@ -50,6 +78,16 @@ function App() {
       })
     );
 
+    // 命令的处理结果, 失败时提示
+    listenIds.push(
+      event.listen<{ seq: number; command: Command; error: RequestError | null }>("command-result", (event) => {
+        const { command, error } = event.payload;
+        if (error) {
+          toast.error(`${command.kind}: ${requestErrors[error]}`);
+        }
+      })
+    );
+
     // 注册通知事件
     listenIds.push(
       event.listen<string>("notify", (event) => {
@@ -74,6 +112,18 @@ function App() {
         if (payload.status) {
           setAddr(payload.addr);
           setIsConnected(true);
+          // 树莓派没有 RTC, 连接后以本机时间校时
+          event.emit("command-server", { kind: "synctime", timestamp: Date.now() } as Command);
+          // 距离用于避障显示需要及时更新, 温湿度变化缓慢
+          event.emit("command-server", {
+            kind: "subscribe",
+            subscriptions: [
+              { topic: "Distance", interval_ms: 50 },
+              { topic: "TH", interval_ms: 2000 },
+              { topic: "Motion", interval_ms: 100 },
+              { topic: "Status", interval_ms: 100 },
+            ],
+          } as Command);
 
           toast(`连接成功：${payload.addr}`);
         } else {
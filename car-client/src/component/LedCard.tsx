@@ -0,0 +1,81 @@
+import { Card, CardBody, CardHeader, Select, SelectItem, Slider, Switch } from "@nextui-org/react";
+import { event } from "@tauri-apps/api";
+import { FC, useContext } from "react";
+import { StatisticsContext } from "../context";
+import { Command, LedEffect } from "car-utils";
+
+const effects: { key: LedEffect; label: string }[] = [
+  { key: "Solid", label: "常亮" },
+  { key: "Blink", label: "闪烁" },
+  { key: "Breathe", label: "呼吸" },
+  { key: "Rainbow", label: "彩虹" },
+  { key: "Status", label: "状态指示" },
+];
+
+const toHex = ([r, g, b]: [number, number, number]) =>
+  "#" + [r, g, b].map((x) => x.toString(16).padStart(2, "0")).join("");
+
+const fromHex = (hex: string): [number, number, number] => [
+  parseInt(hex.slice(1, 3), 16),
+  parseInt(hex.slice(3, 5), 16),
+  parseInt(hex.slice(5, 7), 16),
+];
+
+/// LED 灯
+const LedCard: FC = () => {
+  const { statistics } = useContext(StatisticsContext);
+
+  const emit = (led: Partial<Extract<Command, { kind: "led" }>>) => {
+    event.emit("command-server", {
+      kind: "led",
+      enabled: statistics.led != null,
+      effect: statistics.led ?? "Solid",
+      color: statistics.led_color,
+      brightness: statistics.led_brightness,
+      ...led,
+    } as Command);
+  };
+
+  return (
+    <Card>
+      <CardHeader>
+        <Switch isSelected={statistics.led != null} onValueChange={(enabled) => emit({ enabled })}>
+          LED 灯
+        </Switch>
+      </CardHeader>
+      <CardBody className="space-y-2">
+        <Select
+          size="sm"
+          label="效果"
+          isDisabled={statistics.led == null}
+          selectedKeys={[statistics.led ?? "Solid"]}
+          onChange={(e) => e.target.value && emit({ effect: e.target.value as LedEffect })}
+        >
+          {effects.map((effect) => (
+            <SelectItem key={effect.key}>{effect.label}</SelectItem>
+          ))}
+        </Select>
+        <div className="flex items-center gap-2">
+          颜色
+          <input
+            type="color"
+            disabled={statistics.led == null}
+            value={toHex(statistics.led_color)}
+            onChange={(e) => emit({ color: fromHex(e.target.value) })}
+          />
+        </div>
+        <Slider
+          isDisabled={statistics.led == null}
+          size="sm"
+          label="亮度"
+          minValue={0}
+          maxValue={100}
+          value={statistics.led_brightness}
+          onChangeEnd={(brightness) => emit({ brightness: brightness as number })}
+        />
+      </CardBody>
+    </Card>
+  );
+};
+
+export default LedCard;
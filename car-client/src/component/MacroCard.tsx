@@ -0,0 +1,89 @@
+import { Button, Card, CardBody, CardHeader, Chip, Input, Slider } from "@nextui-org/react";
+import { event } from "@tauri-apps/api";
+import { FC, useContext, useEffect, useState } from "react";
+import { StatisticsContext } from "../context";
+import { Command, MacroAction } from "car-utils";
+
+/// 录制与回放: 录制手动驾驶并按倍速回放
+const MacroCard: FC = () => {
+  const { statistics } = useContext(StatisticsContext);
+  const [macros, setMacros] = useState<string[]>([]);
+  const [name, setName] = useState("");
+  const [scale, setScale] = useState(100);
+
+  useEffect(() => {
+    const unlisten = event.listen<string[]>("macros", (event) => {
+      setMacros(event.payload);
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const emit = (action: MacroAction, name = "") =>
+    event.emit("command-server", { kind: "macro", action, scale, name } as Command);
+
+  const recording = statistics.macro_state == "Recording";
+  const playing = statistics.macro_state == "Playing";
+
+  return (
+    <Card>
+      <CardHeader className="gap-2">
+        录制与回放
+        {statistics.macro_state && (
+          <Chip size="sm" color={recording ? "danger" : "success"}>
+            {recording ? "录制中" : "回放中"}
+          </Chip>
+        )}
+      </CardHeader>
+      <CardBody className="space-y-2">
+        <div className="flex gap-2 items-center">
+          <Input size="sm" label="名称" value={name} onValueChange={setName} isDisabled={statistics.macro_state != null} />
+          {statistics.macro_state ? (
+            <Button size="sm" color="danger" onPress={() => emit("Stop")}>
+              停止
+            </Button>
+          ) : (
+            <Button
+              size="sm"
+              color="primary"
+              isDisabled={!/^[\w-]{1,32}$/.test(name)}
+              onPress={() => emit("Record", name)}
+            >
+              录制
+            </Button>
+          )}
+        </div>
+        <Slider
+          size="sm"
+          label="回放速度 (%)"
+          minValue={25}
+          maxValue={200}
+          step={25}
+          value={scale}
+          onChange={(value) => setScale(value as number)}
+        />
+        {macros.map((macro) => (
+          <div key={macro} className="flex gap-2 items-center">
+            <span className="flex-1 text-sm font-mono">{macro}</span>
+            <Button size="sm" variant="bordered" isDisabled={recording || playing} onPress={() => emit("Play", macro)}>
+              回放
+            </Button>
+            <Button
+              size="sm"
+              variant="bordered"
+              color="danger"
+              isDisabled={recording}
+              onPress={() => emit("Delete", macro)}
+            >
+              删除
+            </Button>
+          </div>
+        ))}
+      </CardBody>
+    </Card>
+  );
+};
+
+export default MacroCard;
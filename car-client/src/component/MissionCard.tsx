@@ -0,0 +1,93 @@
+import { Button, Card, CardBody, CardHeader, Progress, Textarea } from "@nextui-org/react";
+import { event } from "@tauri-apps/api";
+import { FC, useContext, useEffect, useState } from "react";
+import { StatisticsContext } from "../context";
+import { Command, Measurement, MissionAction, MissionStep } from "car-utils";
+
+const EXAMPLE: MissionStep[] = [
+  { step: "move", forward: true, speed: 50, cm: 60 },
+  { step: "turn", degrees: 90, speed: 50 },
+  { step: "servo", angle: 90 },
+  { step: "measure" },
+  { step: "beep" },
+];
+
+/// 任务: 以 JSON 编写步骤并上传到小车执行
+const MissionCard: FC = () => {
+  const { statistics } = useContext(StatisticsContext);
+  const [script, setScript] = useState(JSON.stringify(EXAMPLE, null, 2));
+  const [error, setError] = useState<string | null>(null);
+  const [measurements, setMeasurements] = useState<Measurement[]>([]);
+
+  useEffect(() => {
+    const unlisten = event.listen<Measurement>("measurement", (event) => {
+      setMeasurements((measurements) => [...measurements, event.payload]);
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const upload = () => {
+    try {
+      const steps = JSON.parse(script) as MissionStep[];
+      if (!Array.isArray(steps)) {
+        throw new Error("任务应为步骤数组");
+      }
+      setError(null);
+      setMeasurements([]);
+      event.emit("command-server", { kind: "mission", steps } as Command);
+    } catch (err) {
+      setError(String(err));
+    }
+  };
+
+  const control = (action: MissionAction) => {
+    event.emit("command-server", { kind: "missioncontrol", action } as Command);
+  };
+
+  const mission = statistics.mission;
+  const active = mission?.state == "Running" || mission?.state == "Paused";
+
+  return (
+    <Card>
+      <CardHeader>任务 {mission && `(${mission.state})`}</CardHeader>
+      <CardBody className="space-y-2">
+        <Textarea
+          size="sm"
+          minRows={6}
+          value={script}
+          onValueChange={setScript}
+          isInvalid={error != null}
+          errorMessage={error}
+        />
+        {mission && <Progress size="sm" value={mission.step} maxValue={Math.max(mission.total, 1)} />}
+        <div className="space-x-2">
+          <Button size="sm" color="primary" onPress={upload}>
+            上传并执行
+          </Button>
+          <Button
+            size="sm"
+            variant="bordered"
+            isDisabled={!active}
+            onPress={() => control(mission?.state == "Paused" ? "Resume" : "Pause")}
+          >
+            {mission?.state == "Paused" ? "继续" : "暂停"}
+          </Button>
+          <Button size="sm" variant="bordered" color="danger" isDisabled={!active} onPress={() => control("Abort")}>
+            中止
+          </Button>
+        </div>
+        {measurements.map((measurement, i) => (
+          <div key={i} className="text-sm">
+            第 {measurement.step + 1} 步: 距离 {measurement.distance ?? "-"} m, 温度 {measurement.th?.[0] ?? "-"} °c, 湿度{" "}
+            {measurement.th?.[1] ?? "-"} %
+          </div>
+        ))}
+      </CardBody>
+    </Card>
+  );
+};
+
+export default MissionCard;
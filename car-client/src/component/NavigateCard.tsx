@@ -1,18 +1,31 @@
-import { Button, Card, CardBody, CardHeader, Slider, Switch } from "@nextui-org/react";
+import { Button, Card, CardBody, CardHeader, Chip, Input, Select, SelectItem, Slider, Switch } from "@nextui-org/react";
 import { event } from "@tauri-apps/api";
 import { FC, useContext, useState } from "react";
 import { StatisticsContext } from "../context";
 import { useHotkeys } from "react-hotkeys-hook";
 
-import { Command, Navigate } from "car-utils";
+import { Command, Marker, Navigate, TraceAlgorithm } from "car-utils";
 import { FaArrowDown, FaArrowLeft, FaArrowRight, FaArrowUp } from "react-icons/fa";
 import { IoHandLeft } from "react-icons/io5";
 
+const algorithms: { key: TraceAlgorithm; label: string }[] = [
+  { key: "Contour", label: "轮廓质心" },
+  { key: "SlidingWindow", label: "滑动窗口" },
+  { key: "ColorTape", label: "彩色胶带" },
+];
+
+const markers: Record<Marker, { label: string; color: "danger" | "success" | "warning" }> = {
+  Stop: { label: "停车", color: "danger" },
+  Go: { label: "通行", color: "success" },
+  Slow: { label: "减速", color: "warning" },
+};
+
 /// 导航卡片
 /// TODO: 解决同时按两个导航按钮的问题
 const NavigateCard: FC = () => {
   const { statistics } = useContext(StatisticsContext);
   const [navigate, setNavigate] = useState<Navigate>("Brake");
+  const [algorithm, setAlgorithm] = useState<TraceAlgorithm>("Contour"); // 未开启寻迹时选择的算法
 
   const onPress = (navigate: Navigate) => {
     return () => {
@@ -63,16 +76,57 @@ const NavigateCard: FC = () => {
           </div>
 
           <Switch
-            isSelected={statistics.trace}
+            isSelected={statistics.trace != null}
             onValueChange={(value) => {
               event.emit("command-server", {
                 kind: "trace",
                 enabled: value,
+                algorithm: statistics.trace ?? algorithm,
               } as Command);
             }}
           >
-            自动寻迹
+            自动寻迹{statistics.camera ? "" : " (摄像头不可用)"}
           </Switch>
+          {statistics.marker && (
+            <Chip size="sm" color={markers[statistics.marker].color}>
+              标志: {markers[statistics.marker].label}
+            </Chip>
+          )}
+          <Select
+            size="sm"
+            label="寻迹算法"
+            selectedKeys={[statistics.trace ?? algorithm]}
+            onChange={(e) => {
+              if (!e.target.value) return;
+              const value = e.target.value as TraceAlgorithm;
+              setAlgorithm(value);
+              if (statistics.trace != null) {
+                event.emit("command-server", { kind: "trace", enabled: true, algorithm: value } as Command);
+              }
+            }}
+          >
+            {algorithms.map((algorithm) => (
+              <SelectItem key={algorithm.key}>{algorithm.label}</SelectItem>
+            ))}
+          </Select>
+          <div className="flex gap-1">
+            {(["kp", "ki", "kd"] as const).map((key, i) => (
+              <Input
+                key={key}
+                size="sm"
+                type="number"
+                step="0.01"
+                label={key}
+                defaultValue={statistics.trace_pid[i].toString()}
+                onBlur={(e) => {
+                  const value = parseFloat((e.target as HTMLInputElement).value);
+                  if (Number.isNaN(value)) return;
+                  const [kp, ki, kd] = statistics.trace_pid.map((x, j) => (j == i ? value : x));
+                  event.emit("command-server", { kind: "tracepid", kp, ki, kd } as Command);
+                }}
+              />
+            ))}
+          </div>
           </div>
 
 
@@ -0,0 +1,107 @@
+import { Button, Card, CardBody, CardHeader, Input, Select, SelectItem, Slider, Switch } from "@nextui-org/react";
+import { event } from "@tauri-apps/api";
+import { FC, useContext, useState } from "react";
+import { MdOutlineBrightnessHigh, MdOutlineBrightnessLow } from "react-icons/md";
+import { StatisticsContext } from "../context";
+import { Command, NixieMode } from "car-utils";
+
+const modes: { key: NixieMode; label: string }[] = [
+  { key: "Clock", label: "时钟" },
+  { key: "Temperature", label: "温度" },
+  { key: "Humidity", label: "湿度" },
+  { key: "Distance", label: "距离" },
+  { key: "Speed", label: "速度" },
+  { key: "Text", label: "滚动文字" },
+  { key: "Segments", label: "自定义段码" },
+  { key: "Cycle", label: "轮换" },
+];
+
+/// 解析 4 个十六进制段码, 如 "76 79 38 38"
+const parseSegments = (value: string): number[] | null => {
+  const segments = value.trim().split(/\s+/).map((x) => parseInt(x, 16));
+  return segments.length == 4 && segments.every((x) => x >= 0 && x <= 0xff) ? segments : null;
+};
+
+/// 数码管
+const NixieCard: FC = () => {
+  const { statistics } = useContext(StatisticsContext);
+  const [text, setText] = useState("");
+  const [segments, setSegments] = useState("");
+
+  const enabled = statistics.time_brightness != null;
+  const emit = (nixie: Partial<Extract<Command, { kind: "nixie" }>>) => {
+    event.emit("command-server", {
+      kind: "nixie",
+      enabled,
+      brightness: statistics.time_brightness ?? 1,
+      mode: statistics.nixie_mode,
+      ...nixie,
+    } as Command);
+  };
+
+  return (
+    <Card>
+      <CardHeader>
+        <Switch isSelected={enabled} onValueChange={(enabled) => emit({ enabled })}>
+          数码管
+        </Switch>
+      </CardHeader>
+      <CardBody className="space-y-2">
+        <Select
+          size="sm"
+          label="显示"
+          isDisabled={!enabled}
+          selectedKeys={[statistics.nixie_mode]}
+          onChange={(e) => e.target.value && emit({ mode: e.target.value as NixieMode })}
+        >
+          {modes.map((mode) => (
+            <SelectItem key={mode.key}>{mode.label}</SelectItem>
+          ))}
+        </Select>
+        <div className="flex items-center gap-2">
+          <Input size="sm" label="文字" isDisabled={!enabled} value={text} onValueChange={setText} />
+          <Button
+            isDisabled={!enabled || text.length == 0}
+            onPress={() => event.emit("command-server", { kind: "nixietext", text } as Command)}
+          >
+            显示
+          </Button>
+        </div>
+        <div className="flex items-center gap-2">
+          <Input
+            size="sm"
+            label="段码"
+            placeholder="76 79 38 38"
+            isDisabled={!enabled}
+            value={segments}
+            onValueChange={setSegments}
+          />
+          <Button
+            isDisabled={!enabled || parseSegments(segments) == null}
+            onPress={() =>
+              event.emit("command-server", { kind: "nixiesegments", segments: parseSegments(segments) } as Command)
+            }
+          >
+            显示
+          </Button>
+        </div>
+        <Slider
+          isDisabled={!enabled}
+          size="md"
+          step={1}
+          color="foreground"
+          label="亮度"
+          showSteps={true}
+          maxValue={7}
+          minValue={1}
+          value={statistics.time_brightness ?? 0}
+          startContent={<MdOutlineBrightnessLow />}
+          endContent={<MdOutlineBrightnessHigh />}
+          onChangeEnd={(brightness) => emit({ brightness: brightness as number })}
+        />
+      </CardBody>
+    </Card>
+  );
+};
+
+export default NixieCard;
@@ -0,0 +1,77 @@
+import { Card, CardBody, CardHeader, Switch } from "@nextui-org/react";
+import { event } from "@tauri-apps/api";
+import { FC, useContext, useEffect, useState } from "react";
+import { StatisticsContext } from "../context";
+import { Command, Scan } from "car-utils";
+
+const MAX_DISTANCE = 200; // 雷达图显示的最大距离 cm
+const RADIUS = 100;
+
+/// 雷达扫描
+const RadarCard: FC = () => {
+  const { statistics } = useContext(StatisticsContext);
+  const [scan, setScan] = useState<Scan | null>(null);
+
+  useEffect(() => {
+    const unlisten = event.listen<Scan>("scan", (event) => {
+      setScan(event.payload);
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  // 0° 在右侧, 90° 在正前方, 180° 在左侧
+  const points = (scan?.distances ?? []).flatMap((distance, i) => {
+    if (distance === null) {
+      return [];
+    }
+    const angle = ((scan!.from + i * scan!.step) * Math.PI) / 180;
+    const r = (Math.min(distance, MAX_DISTANCE) / MAX_DISTANCE) * RADIUS;
+    return [{ x: RADIUS + r * Math.cos(angle), y: RADIUS - r * Math.sin(angle), far: distance >= MAX_DISTANCE }];
+  });
+
+  return (
+    <Card>
+      <CardHeader>
+        <Switch
+          isSelected={statistics.scan}
+          onValueChange={(enabled) => {
+            event.emit("command-server", { kind: "scan", enabled, from: 0, to: 180, step: 10 } as Command);
+          }}
+        >
+          雷达扫描
+        </Switch>
+      </CardHeader>
+      <CardBody>
+        <svg viewBox={`0 0 ${RADIUS * 2} ${RADIUS + 5}`} className="w-full">
+          {[0.25, 0.5, 0.75, 1].map((ratio) => (
+            <path
+              key={ratio}
+              d={`M ${RADIUS - RADIUS * ratio} ${RADIUS} A ${RADIUS * ratio} ${RADIUS * ratio} 0 0 1 ${
+                RADIUS + RADIUS * ratio
+              } ${RADIUS}`}
+              fill="none"
+              stroke="gray"
+              strokeWidth={0.5}
+            />
+          ))}
+          {points.length > 1 && (
+            <polyline
+              points={points.map((p) => `${p.x},${p.y}`).join(" ")}
+              fill="none"
+              stroke="rgb(53, 162, 235)"
+              strokeWidth={1}
+            />
+          )}
+          {points.map((p, i) => (
+            <circle key={i} cx={p.x} cy={p.y} r={2} fill={p.far ? "gray" : "red"} />
+          ))}
+        </svg>
+      </CardBody>
+    </Card>
+  );
+};
+
+export default RadarCard;
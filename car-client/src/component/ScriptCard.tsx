@@ -0,0 +1,101 @@
+import { Button, Card, CardBody, CardHeader, Chip, Textarea } from "@nextui-org/react";
+import { event } from "@tauri-apps/api";
+import { FC, useContext, useEffect, useState } from "react";
+import { StatisticsContext } from "../context";
+import { Command, ScriptAction } from "car-utils";
+
+const CHUNK_BYTES = 200; // 每条命令携带的源码字节数, 命令负载最长 255 字节
+const MAX_LOGS = 50;
+
+const EXAMPLE = `// 距离障碍物较近时亮红灯并停车
+every(200, || {
+  let s = statistics();
+  if s.distance != () && s.distance < 0.3 {
+    led(255, 0, 0);
+    stop();
+  } else {
+    led(0, 255, 0);
+  }
+});
+`;
+
+/// 按 UTF-8 字节数切分源码, 不拆开字符
+const chunks = (source: string) => {
+  const encoder = new TextEncoder();
+  const result: string[] = [];
+  let chunk = "";
+  for (const char of source) {
+    if (encoder.encode(chunk + char).length > CHUNK_BYTES) {
+      result.push(chunk);
+      chunk = "";
+    }
+    chunk += char;
+  }
+  if (chunk) result.push(chunk);
+  return result;
+};
+
+/// 脚本: 上传 Rhai 脚本到小车运行
+const ScriptCard: FC = () => {
+  const { statistics } = useContext(StatisticsContext);
+  const [source, setSource] = useState(EXAMPLE);
+  const [logs, setLogs] = useState<string[]>([]);
+
+  useEffect(() => {
+    const unlisten = event.listen<string>("script-log", (event) => {
+      setLogs((logs) => [...logs, event.payload].slice(-MAX_LOGS));
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const script = (action: ScriptAction) => event.emit("command-server", { kind: "script", action } as Command);
+
+  const run = async () => {
+    setLogs([]);
+    await script("Clear");
+    for (const text of chunks(source)) {
+      await event.emit("command-server", { kind: "scriptsource", text } as Command);
+    }
+    await script("Start");
+  };
+
+  return (
+    <Card>
+      <CardHeader className="gap-2">
+        脚本
+        {statistics.script && (
+          <Chip size="sm" color="success">
+            运行中
+          </Chip>
+        )}
+      </CardHeader>
+      <CardBody className="space-y-2">
+        <Textarea size="sm" minRows={8} value={source} onValueChange={setSource} className="font-mono" />
+        <div className="space-x-2">
+          <Button size="sm" color="primary" onPress={run}>
+            上传并运行
+          </Button>
+          <Button
+            size="sm"
+            variant="bordered"
+            color="danger"
+            isDisabled={!statistics.script}
+            onPress={() => script("Stop")}
+          >
+            停止
+          </Button>
+        </div>
+        <div className="text-sm font-mono max-h-40 overflow-auto">
+          {logs.map((log, i) => (
+            <div key={i}>{log}</div>
+          ))}
+        </div>
+      </CardBody>
+    </Card>
+  );
+};
+
+export default ScriptCard;
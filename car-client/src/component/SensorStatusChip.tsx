@@ -0,0 +1,26 @@
+import { Chip, Tooltip } from "@nextui-org/react";
+import { FC } from "react";
+import { SensorError, SensorStatus } from "car-utils";
+
+const errors: Record<SensorError, string> = {
+  NoResponse: "无响应",
+  Timeout: "超时",
+  Checksum: "校验错误",
+  OutOfRange: "超出量程",
+};
+
+/// 传感器读数过期或读取失败时显示
+const SensorStatusChip: FC<{ status: SensorStatus }> = ({ status }) => {
+  if (!status.stale && status.error == null) return null;
+
+  const age = status.age == null ? "从未读取" : `${(status.age / 1000).toFixed(1)} 秒前更新`;
+  return (
+    <Tooltip content={`${age}, 成功 ${status.samples} 次, 失败 ${status.failures} 次`}>
+      <Chip size="sm" color={status.stale ? "danger" : "warning"}>
+        {status.stale ? "数据过期" : errors[status.error!]}
+      </Chip>
+    </Tooltip>
+  );
+};
+
+export default SensorStatusChip;
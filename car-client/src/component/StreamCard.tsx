@@ -0,0 +1,72 @@
+import { Card, CardBody, CardHeader, Checkbox, Image, Switch } from "@nextui-org/react";
+import { event } from "@tauri-apps/api";
+import { invoke } from "@tauri-apps/api/core";
+import { FC, useContext, useEffect, useState } from "react";
+import { StatisticsContext } from "../context";
+import { Command, StreamOverlay } from "car-utils";
+
+const STREAM_PORT = 5001;
+
+const overlays: { key: keyof StreamOverlay; label: string }[] = [
+  { key: "roi", label: "感兴趣区域" },
+  { key: "mask", label: "二值化" },
+  { key: "centroid", label: "识别结果" },
+];
+
+/// 摄像头推流
+const StreamCard: FC<{ addr: string }> = ({ addr }) => {
+  const { statistics } = useContext(StatisticsContext);
+  const [frame, setFrame] = useState<string | null>(null);
+  const enabled = statistics.stream != null;
+
+  useEffect(() => {
+    const unlisten = event.listen<string>("frame", (event) => setFrame(event.payload));
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  // 开启推流后开始接收画面
+  useEffect(() => {
+    if (!enabled || !addr) return;
+    const host = addr.slice(0, addr.lastIndexOf(":"));
+    invoke("stream", { addr: `${host}:${STREAM_PORT}` });
+    return () => {
+      event.emit("close-stream");
+      setFrame(null);
+    };
+  }, [enabled, addr]);
+
+  const emit = (enabled: boolean, overlay: StreamOverlay) => {
+    event.emit("command-server", { kind: "stream", enabled, overlay } as Command);
+  };
+  const overlay = statistics.stream ?? { roi: false, mask: false, centroid: true };
+
+  return (
+    <Card>
+      <CardHeader>
+        <Switch isSelected={enabled} onValueChange={(enabled) => emit(enabled, overlay)}>
+          摄像头{statistics.camera ? "" : " (不可用)"}
+        </Switch>
+      </CardHeader>
+      <CardBody className="space-y-2">
+        <div className="flex gap-2">
+          {overlays.map(({ key, label }) => (
+            <Checkbox
+              key={key}
+              size="sm"
+              isDisabled={!enabled}
+              isSelected={overlay[key]}
+              onValueChange={(value) => emit(true, { ...overlay, [key]: value })}
+            >
+              {label}
+            </Checkbox>
+          ))}
+        </div>
+        {frame && <Image className="w-72" alt="推流" src={`data:image/jpeg;base64,${frame}`} />}
+      </CardBody>
+    </Card>
+  );
+};
+
+export default StreamCard;
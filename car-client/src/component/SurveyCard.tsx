@@ -0,0 +1,101 @@
+import { Button, Card, CardBody, CardHeader, Switch } from "@nextui-org/react";
+import { ChartOptions } from "chart.js";
+import { event } from "@tauri-apps/api";
+import { FC, useContext, useEffect, useState } from "react";
+import { Chart } from "react-chartjs-2";
+import { StatisticsContext } from "../context";
+import { Command, Sample } from "car-utils";
+
+const options: ChartOptions = {
+  responsive: true,
+  maintainAspectRatio: true,
+  animation: false,
+  plugins: { legend: { labels: { boxWidth: 20 } } },
+  scales: {
+    x: { type: "linear", title: { display: true, text: "距离 (cm)" } },
+    "y-axis-temp": { type: "linear", position: "left" },
+    "y-axis-humidity": { type: "linear", position: "right", min: 0, max: 100 },
+  },
+};
+
+/// 导出为 CSV 文件
+const exportCsv = (samples: Sample[]) => {
+  const rows = samples.map((s) => [s.ms, s.distance.toFixed(1), s.temperature, s.humidity].join(","));
+  const blob = new Blob([["ms,distance_cm,temperature,humidity", ...rows].join("\n")], { type: "text/csv" });
+  const a = document.createElement("a");
+  a.href = URL.createObjectURL(blob);
+  a.download = `survey-${new Date().toISOString().replace(/[:.]/g, "-")}.csv`;
+  a.click();
+  URL.revokeObjectURL(a.href);
+};
+
+/// 巡测: 沿赛道记录温湿度与位置
+const SurveyCard: FC = () => {
+  const { statistics } = useContext(StatisticsContext);
+  const [samples, setSamples] = useState<Sample[]>([]);
+
+  useEffect(() => {
+    const unlisten = event.listen<Sample>("sample", (event) => {
+      // 新的巡测从 0 ms 开始
+      setSamples((samples) => (event.payload.ms == 0 ? [event.payload] : [...samples, event.payload]));
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  return (
+    <Card>
+      <CardHeader>
+        <Switch
+          isSelected={statistics.survey}
+          onValueChange={(enabled) => {
+            event.emit("command-server", { kind: "survey", enabled } as Command);
+          }}
+        >
+          巡测
+        </Switch>
+      </CardHeader>
+      <CardBody className="space-y-2">
+        <Chart
+          type="line"
+          options={options}
+          data={{
+            datasets: [
+              {
+                label: "温度 (°C)",
+                data: samples.map((s) => ({ x: s.distance, y: s.temperature })),
+                borderColor: "rgb(53, 162, 235)",
+                borderWidth: 1,
+                pointRadius: 1,
+                yAxisID: "y-axis-temp",
+              },
+              {
+                label: "湿度 (%)",
+                data: samples.map((s) => ({ x: s.distance, y: s.humidity })),
+                borderColor: "red",
+                borderWidth: 1,
+                pointRadius: 1,
+                yAxisID: "y-axis-humidity",
+              },
+            ],
+          }}
+          width={400}
+          height={150}
+        />
+        <div className="flex items-center justify-between text-sm">
+          <span>
+            {samples.length} 个样本
+            {samples.length > 0 && `, ${samples[samples.length - 1].distance.toFixed(0)} cm`}
+          </span>
+          <Button size="sm" variant="bordered" isDisabled={samples.length == 0} onPress={() => exportCsv(samples)}>
+            导出 CSV
+          </Button>
+        </div>
+      </CardBody>
+    </Card>
+  );
+};
+
+export default SurveyCard;
@@ -1,10 +1,11 @@
-import { Card, CardHeader, CardBody, CardFooter, Switch } from "@nextui-org/react";
+import { Card, CardHeader, CardBody, CardFooter, Chip, Switch } from "@nextui-org/react";
 import { ChartOptions } from "chart.js";
 import { useState, useEffect, useContext } from "react";
 import { Chart } from "react-chartjs-2";
 import { StatisticsContext } from "../context";
 import { event } from "@tauri-apps/api";
-import { Command } from "car-utils";
+import { Alarm, Command } from "car-utils";
+import SensorStatusChip from "./SensorStatusChip";
 
 const options: ChartOptions = {
   responsive: true,
@@ -40,12 +41,24 @@ const options: ChartOptions = {
 };
 
 const labels = new Array<string>(100).fill("");
+const MAX_ALARMS = 5;
 
 /// 温湿度
 export function THCard() {
   const { statistics } = useContext(StatisticsContext);
 
   const [datasets, setDatasets] = useState<[number[], number[]]>([[], []]);
+  const [alarms, setAlarms] = useState<Alarm[]>([]);
+
+  useEffect(() => {
+    const unlisten = event.listen<Alarm>("alarm", (event) => {
+      setAlarms((alarms) => [event.payload, ...alarms].slice(0, MAX_ALARMS));
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
 
   useEffect(() => {
     setDatasets((datasets) => {
@@ -65,7 +78,7 @@ export function THCard() {
 
   return (
     <Card>
-      <CardHeader>
+      <CardHeader className="gap-2">
         <Switch
           isSelected={statistics.th != null}
           onValueChange={(enabled) => {
@@ -74,6 +87,12 @@ export function THCard() {
         >
           温湿度传感器
         </Switch>
+        {statistics.th != null && <SensorStatusChip status={statistics.th_status} />}
+        {statistics.alarm && (
+          <Chip size="sm" color="danger">
+            报警
+          </Chip>
+        )}
       </CardHeader>
 
       <CardBody>
@@ -123,6 +142,12 @@ export function THCard() {
           {/* <div>平均湿度: {statistics.avg.toFixed(2)}</div> */}
           <div>最高湿度: {Math.max(...datasets[1]).toFixed(2)}</div>
         </div>
+        {alarms.map((alarm, i) => (
+          <div key={i} className={`text-sm ${alarm.active ? "text-danger" : "text-default-500"}`}>
+            {new Date(alarm.time).toLocaleTimeString()} {alarm.active ? "报警" : "解除"}: {alarm.name} (
+            {alarm.value.toFixed(1)})
+          </div>
+        ))}
       </CardFooter>
     </Card>
   );
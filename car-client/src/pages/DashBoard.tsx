@@ -11,11 +11,18 @@ import {
   ModalContent,
   ModalFooter,
   ModalHeader,
-  Slider,
   Switch,
 } from "@nextui-org/react";
-import { MdOutlineBrightnessHigh, MdOutlineBrightnessLow } from "react-icons/md";
 import NavigateCard from "../component/NavigateCard";
+import RadarCard from "../component/RadarCard";
+import LedCard from "../component/LedCard";
+import NixieCard from "../component/NixieCard";
+import StreamCard from "../component/StreamCard";
+import MissionCard from "../component/MissionCard";
+import ScriptCard from "../component/ScriptCard";
+import MacroCard from "../component/MacroCard";
+import SurveyCard from "../component/SurveyCard";
+import SensorStatusChip from "../component/SensorStatusChip";
 import { StatisticsContext } from "../context";
 import { useHotkeys } from "react-hotkeys-hook";
 import { Command } from "car-utils";
@@ -63,55 +70,52 @@ const DashBoard: FC<{ addr: string }> = ({ addr }) => {
 
         <Card>
           <CardBody>
-            <div>距离: {statistics.distance} cm</div>
-            <div>温度: {statistics.th?.[0]} °c</div>
+            <div className="flex gap-2">
+              距离: {statistics.distance} cm
+              {statistics.distance != null && <SensorStatusChip status={statistics.distance_status} />}
+            </div>
+            <div className="flex gap-2">
+              温度: {statistics.th?.[0]} °c
+              {statistics.th != null && <SensorStatusChip status={statistics.th_status} />}
+            </div>
             <div>湿度: {statistics.th?.[1]} %</div>
 
             <Switch
-              isSelected={statistics.led}
+              isSelected={statistics.autopilot != null}
               onValueChange={(enabled) => {
-                event.emit("command-server", {
-                  kind: "led",
-                  enabled,
-                } as Command);
+                event.emit("command-server", { kind: "autopilot", enabled } as Command);
               }}
             >
-              LED 灯
+              自动避障 {statistics.autopilot && `(${statistics.autopilot})`}
             </Switch>
+
+            <div className="space-x-2">
+              <Button
+                size="sm"
+                variant="bordered"
+                onPress={() => {
+                  event.emit("command-server", { kind: "buzzer", sound: "Beep" } as Command);
+                }}
+              >
+                鸣笛
+              </Button>
+              <Button
+                size="sm"
+                variant="bordered"
+                color="danger"
+                isDisabled={statistics.sound == null}
+                onPress={() => {
+                  event.emit("command-server", { kind: "buzzer", sound: "Stop" } as Command);
+                }}
+              >
+                停止播放
+              </Button>
+            </div>
           </CardBody>
         </Card>
 
         <Card>
           <CardBody>
-            <Switch
-              isSelected={statistics.time_brightness != null}
-              onValueChange={(enabled) => {
-                event.emit("command-server", {
-                  kind: "nixie",
-                  enabled,
-                  brightness: statistics.time_brightness ?? 0,
-                } as Command);
-              }}
-            >
-              显示时间
-            </Switch>
-            <Slider
-              isDisabled={statistics.time_brightness == null}
-              size="md"
-              step={1}
-              color="foreground"
-              label="数码管亮度"
-              showSteps={true}
-              maxValue={7}
-              minValue={1}
-              value={statistics?.time_brightness ?? 0}
-              startContent={<MdOutlineBrightnessLow />}
-              endContent={<MdOutlineBrightnessHigh />}
-              onChangeEnd={(x) => {
-                event.emit("command-server", { kind: "nixie", enabled: true, brightness: x } as Command);
-              }}
-            />
-
             <Switch
               isSelected={statistics.distance != null}
               onValueChange={(value) => {
@@ -159,14 +163,15 @@ const DashBoard: FC<{ addr: string }> = ({ addr }) => {
         </Card>
 
         <THCard />
+        <SurveyCard />
         <NavigateCard />
-
-        {/* TODO: 推流 */}
-        {/* Card>
-        <CardBody>
-          <Image className="w-72" alt="推流" src={`http://${addr}/?action=stream`} />
-        </CardBody>
-      </Card> */}
+        <RadarCard />
+        <LedCard />
+        <NixieCard />
+        <StreamCard addr={addr} />
+        <MissionCard />
+        <ScriptCard />
+        <MacroCard />
       </div>
 
       <div className="fixed w-full bottom-0">
@@ -0,0 +1,195 @@
+//! 环境报警: 温湿度超过阈值或变化过快时报警, 读数恢复后解除
+
+use std::{
+  collections::VecDeque,
+  time::{Duration, Instant},
+};
+
+use car_utils::{Metric, Sound};
+use serde::Deserialize;
+
+use crate::config;
+
+/// 报警条件
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "when", rename_all = "snake_case")]
+pub enum Condition {
+  Above { value: f32 },        // 读数高于 value
+  Below { value: f32 },        // 读数低于 value
+  Rising { per_minute: f32 },  // 每分钟上升超过 per_minute
+  Falling { per_minute: f32 }, // 每分钟下降超过 per_minute
+}
+
+/// 报警规则
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AlarmRule {
+  pub name: String,
+  pub metric: Metric,
+  #[serde(flatten)]
+  pub condition: Condition,
+  #[serde(default = "default_sound")]
+  pub sound: Sound, // 开始报警时播放的声音
+}
+
+fn default_sound() -> Sound {
+  Sound::Alarm
+}
+
+impl AlarmRule {
+  /// 默认规则
+  pub fn defaults() -> Vec<AlarmRule> {
+    vec![AlarmRule {
+      name: "温度过高".to_string(),
+      metric: Metric::Temperature,
+      condition: Condition::Above { value: config::OVER_TEMPERATURE },
+      sound: Sound::OverTemperature,
+    }]
+  }
+}
+
+/// 规则报警状态的变化
+#[derive(Debug, PartialEq)]
+pub struct Change<'a> {
+  pub rule: &'a AlarmRule,
+  pub value: f32, // 读数, 变化率规则为每分钟的变化量
+  pub active: bool,
+}
+
+pub struct Alarms {
+  rules: Vec<AlarmRule>,
+  active: Vec<bool>,
+  samples: VecDeque<(Instant, f32, f32)>, // 最近的温度, 湿度读数
+}
+
+impl Alarms {
+  pub fn new(rules: Vec<AlarmRule>) -> Self {
+    Self { active: vec![false; rules.len()], rules, samples: VecDeque::new() }
+  }
+
+  /// 输入一次读数, 返回报警状态有变化的规则
+  pub fn update(&mut self, now: Instant, temperature: f32, humidity: f32) -> Vec<Change<'_>> {
+    let window = Duration::from_secs(config::ALARM_RATE_WINDOW_S);
+    while self.samples.front().is_some_and(|(at, ..)| now.duration_since(*at) > window) {
+      self.samples.pop_front();
+    }
+    self.samples.push_back((now, temperature, humidity));
+
+    // 每分钟的变化量, 读数跨越的时间太短时为 None
+    let (oldest, t0, h0) = self.samples[0];
+    let elapsed = now.duration_since(oldest);
+    let rate = |metric: Metric| {
+      (elapsed >= Duration::from_secs(config::ALARM_RATE_MIN_S)).then(|| {
+        let delta = match metric {
+          Metric::Temperature => temperature - t0,
+          Metric::Humidity => humidity - h0,
+        };
+        delta * 60_f32 / elapsed.as_secs_f32()
+      })
+    };
+
+    let mut changes = Vec::new();
+    for (rule, active) in self.rules.iter().zip(self.active.iter_mut()) {
+      let reading = match rule.metric {
+        Metric::Temperature => temperature,
+        Metric::Humidity => humidity,
+      };
+      let (value, next) = match rule.condition {
+        Condition::Above { value } => {
+          (reading, if *active { reading > value - config::ALARM_HYSTERESIS } else { reading > value })
+        }
+        Condition::Below { value } => {
+          (reading, if *active { reading < value + config::ALARM_HYSTERESIS } else { reading < value })
+        }
+        Condition::Rising { per_minute } => match rate(rule.metric) {
+          Some(rate) => (rate, rate > per_minute),
+          None => continue,
+        },
+        Condition::Falling { per_minute } => match rate(rule.metric) {
+          Some(rate) => (rate, -rate > per_minute),
+          None => continue,
+        },
+      };
+
+      if next != *active {
+        *active = next;
+        changes.push(Change { rule, value, active: next });
+      }
+    }
+    changes
+  }
+
+  /// 是否有正在报警的规则
+  pub fn active(&self) -> bool {
+    self.active.iter().any(|active| *active)
+  }
+
+  /// 传感器关闭时清除状态与读数, 不产生解除事件
+  pub fn reset(&mut self) {
+    self.active.fill(false);
+    self.samples.clear();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::{Duration, Instant};
+
+  use car_utils::{Metric, Sound};
+
+  use super::{AlarmRule, Alarms, Condition};
+
+  fn rule(name: &str, metric: Metric, condition: Condition) -> AlarmRule {
+    AlarmRule { name: name.to_string(), metric, condition, sound: Sound::Alarm }
+  }
+
+  /// 返回状态有变化的规则名称与状态
+  fn update(alarms: &mut Alarms, now: Instant, temperature: f32, humidity: f32) -> Vec<(String, bool)> {
+    alarms
+      .update(now, temperature, humidity)
+      .into_iter()
+      .map(|change| (change.rule.name.clone(), change.active))
+      .collect()
+  }
+
+  #[test]
+  fn test_threshold() {
+    let mut alarms = Alarms::new(vec![
+      rule("hot", Metric::Temperature, Condition::Above { value: 35_f32 }),
+      rule("dry", Metric::Humidity, Condition::Below { value: 20_f32 }),
+    ]);
+    let now = Instant::now();
+    assert!(update(&mut alarms, now, 30_f32, 50_f32).is_empty());
+    assert_eq!(update(&mut alarms, now, 36_f32, 15_f32), [("hot".to_string(), true), ("dry".to_string(), true)]);
+    assert!(alarms.active());
+
+    // 回差内不解除
+    assert!(update(&mut alarms, now, 34.5, 20.5).is_empty());
+    assert_eq!(update(&mut alarms, now, 33_f32, 22_f32), [("hot".to_string(), false), ("dry".to_string(), false)]);
+    assert!(!alarms.active());
+  }
+
+  #[test]
+  fn test_rate() {
+    let mut alarms = Alarms::new(vec![rule("rising", Metric::Temperature, Condition::Rising { per_minute: 3_f32 })]);
+    let now = Instant::now();
+    assert!(update(&mut alarms, now, 20_f32, 50_f32).is_empty());
+    // 跨越的时间太短, 不计算变化率
+    assert!(update(&mut alarms, now + Duration::from_secs(5), 25_f32, 50_f32).is_empty());
+    // 30 秒上升 2 °C, 每分钟 4 °C
+    assert_eq!(update(&mut alarms, now + Duration::from_secs(30), 22_f32, 50_f32), [("rising".to_string(), true)]);
+    // 最早的读数移出窗口后变化率下降
+    assert!(update(&mut alarms, now + Duration::from_secs(120), 22_f32, 50_f32).is_empty());
+    assert_eq!(update(&mut alarms, now + Duration::from_secs(180), 22_f32, 50_f32), [("rising".to_string(), false)]);
+
+    alarms.reset();
+    assert!(!alarms.active());
+  }
+
+  #[test]
+  fn test_deserialize() {
+    let rule: AlarmRule =
+      serde_json::from_str(r#"{ "name": "dry", "metric": "Humidity", "when": "below", "value": 20 }"#).unwrap();
+    assert_eq!(rule.condition, Condition::Below { value: 20_f32 });
+    assert_eq!(rule.sound, Sound::Alarm);
+  }
+}
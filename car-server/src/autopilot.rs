@@ -0,0 +1,92 @@
+//! 自动避障漫游: 前进, 遇到障碍物停车, 左右测距后转向空旷的一侧继续前进
+
+use car_utils::AutopilotState;
+
+use crate::config;
+
+/// 自动避障状态机
+#[derive(Debug, Default)]
+pub struct Autopilot {
+  state: AutopilotState,
+  left: Option<u16>,  // 左侧距离
+  right: Option<u16>, // 右侧距离
+}
+
+impl Autopilot {
+  pub fn state(&self) -> AutopilotState {
+    self.state
+  }
+
+  /// 根据当前状态下的测量结果转移到下一个状态
+  /// distance: Forward 时为前方距离, ScanLeft/ScanRight 时为左/右侧距离, 其余状态忽略
+  pub fn next(&mut self, distance: Option<u16>) -> AutopilotState {
+    self.state = match self.state {
+      AutopilotState::Forward if distance.is_some_and(|d| d <= config::MIN_DISTANCE) => AutopilotState::Stop,
+      AutopilotState::Forward => AutopilotState::Forward,
+      AutopilotState::Stop => AutopilotState::ScanLeft,
+      AutopilotState::ScanLeft => {
+        self.left = distance;
+        AutopilotState::ScanRight
+      }
+      AutopilotState::ScanRight => {
+        self.right = distance;
+        if clearance(self.left) <= config::MIN_DISTANCE && clearance(self.right) <= config::MIN_DISTANCE {
+          AutopilotState::BackWard
+        } else {
+          self.clearer_side()
+        }
+      }
+      AutopilotState::BackWard => self.clearer_side(),
+      AutopilotState::TurnLeft | AutopilotState::TurnRight => AutopilotState::Forward,
+    };
+
+    self.state
+  }
+
+  /// 重置到初始状态
+  pub fn reset(&mut self) {
+    *self = Self::default();
+  }
+
+  /// 选择更空旷的一侧
+  fn clearer_side(&self) -> AutopilotState {
+    if clearance(self.left) >= clearance(self.right) {
+      AutopilotState::TurnLeft
+    } else {
+      AutopilotState::TurnRight
+    }
+  }
+}
+
+/// 测量失败时视为没有空间
+fn clearance(distance: Option<u16>) -> u16 {
+  distance.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+  use super::Autopilot;
+  use car_utils::AutopilotState;
+
+  #[test]
+  fn test_avoid() {
+    let mut autopilot = Autopilot::default();
+    assert_eq!(autopilot.next(Some(100)), AutopilotState::Forward);
+    assert_eq!(autopilot.next(None), AutopilotState::Forward);
+    assert_eq!(autopilot.next(Some(10)), AutopilotState::Stop);
+    assert_eq!(autopilot.next(None), AutopilotState::ScanLeft);
+    assert_eq!(autopilot.next(Some(30)), AutopilotState::ScanRight);
+    assert_eq!(autopilot.next(Some(80)), AutopilotState::TurnRight);
+    assert_eq!(autopilot.next(None), AutopilotState::Forward);
+  }
+
+  #[test]
+  fn test_blocked() {
+    let mut autopilot = Autopilot::default();
+    autopilot.next(Some(5));
+    autopilot.next(None);
+    autopilot.next(Some(15));
+    assert_eq!(autopilot.next(None), AutopilotState::BackWard);
+    assert_eq!(autopilot.next(None), AutopilotState::TurnLeft);
+  }
+}
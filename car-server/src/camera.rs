@@ -0,0 +1,226 @@
+//! 摄像头画面来源: 摄像头设备, 视频文件, 图片序列, 合成的测试画面
+
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  thread,
+  time::{Duration, Instant},
+};
+
+use opencv::{
+  core::{Mat, Point, Scalar, Size, CV_8UC3},
+  imgcodecs, imgproc,
+  prelude::*,
+  videoio::{self, VideoCapture, CAP_ANY},
+};
+
+use crate::settings::{CameraSettings, CameraSource};
+
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// 画面来源
+pub trait FrameSource: Send {
+  /// 读取下一帧, 没有更多画面时返回 None
+  fn read(&mut self) -> opencv::Result<Option<Mat>>;
+}
+
+/// 按配置打开画面来源
+pub fn open(settings: &CameraSettings) -> opencv::Result<Box<dyn FrameSource>> {
+  let pacer = Pacer::new(settings.fps);
+  Ok(match &settings.source {
+    CameraSource::Device { index } => Box::new(Device::new(*index, settings)?),
+    CameraSource::Video { path } => Box::new(Video::new(path, pacer)?),
+    CameraSource::Images { dir } => Box::new(Images::new(dir, pacer)?),
+    CameraSource::TestPattern => Box::new(TestPattern::new(settings.width, settings.height, pacer)),
+  })
+}
+
+/// 目录下的图片, 按文件名排序
+pub fn images(dir: &Path) -> io::Result<Vec<PathBuf>> {
+  let mut images = fs::read_dir(dir)?
+    .map(|entry| entry.map(|entry| entry.path()))
+    .collect::<io::Result<Vec<_>>>()?
+    .into_iter()
+    .filter(|path| {
+      path.extension().is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+    })
+    .collect::<Vec<_>>();
+  images.sort();
+  Ok(images)
+}
+
+/// 按帧率控制读取的间隔, 文件与合成画面没有摄像头的节奏
+struct Pacer {
+  interval: Duration,
+  next: Instant,
+}
+
+impl Pacer {
+  fn new(fps: u32) -> Self {
+    Self { interval: Duration::from_secs(1) / fps.max(1), next: Instant::now() }
+  }
+
+  fn wait(&mut self) {
+    let now = Instant::now();
+    if self.next > now {
+      thread::sleep(self.next - now);
+    }
+    self.next = self.next.max(now) + self.interval;
+  }
+}
+
+/// 摄像头设备
+pub struct Device {
+  cap: VideoCapture,
+}
+
+impl Device {
+  pub fn new(index: i32, settings: &CameraSettings) -> opencv::Result<Self> {
+    let mut cap = VideoCapture::new(index, CAP_ANY)?;
+    if !cap.is_opened()? {
+      return Err(opencv::Error::new(opencv::core::StsError, format!("无法打开摄像头 {}", index)));
+    }
+
+    cap.set(videoio::CAP_PROP_FRAME_WIDTH, settings.width as f64)?;
+    cap.set(videoio::CAP_PROP_FRAME_HEIGHT, settings.height as f64)?;
+    cap.set(videoio::CAP_PROP_FPS, settings.fps as f64)?;
+    Ok(Self { cap })
+  }
+}
+
+impl FrameSource for Device {
+  fn read(&mut self) -> opencv::Result<Option<Mat>> {
+    let mut frame = Mat::default();
+    Ok((self.cap.read(&mut frame)? && !frame.empty()).then_some(frame))
+  }
+}
+
+/// 视频文件, 播放完后从头循环
+pub struct Video {
+  cap: VideoCapture,
+  pacer: Pacer,
+}
+
+impl Video {
+  fn new(path: &str, pacer: Pacer) -> opencv::Result<Self> {
+    let cap = VideoCapture::from_file(path, CAP_ANY)?;
+    if !cap.is_opened()? {
+      return Err(opencv::Error::new(opencv::core::StsError, format!("无法打开视频 {}", path)));
+    }
+    Ok(Self { cap, pacer })
+  }
+}
+
+impl FrameSource for Video {
+  fn read(&mut self) -> opencv::Result<Option<Mat>> {
+    self.pacer.wait();
+
+    let mut frame = Mat::default();
+    if !self.cap.read(&mut frame)? || frame.empty() {
+      self.cap.set(videoio::CAP_PROP_POS_FRAMES, 0_f64)?;
+      if !self.cap.read(&mut frame)? || frame.empty() {
+        return Ok(None);
+      }
+    }
+    Ok(Some(frame))
+  }
+}
+
+/// 图片序列, 按文件名顺序循环
+pub struct Images {
+  paths: Vec<PathBuf>,
+  index: usize,
+  pacer: Pacer,
+}
+
+impl Images {
+  fn new(dir: &str, pacer: Pacer) -> opencv::Result<Self> {
+    let paths = images(Path::new(dir)).map_err(|err| opencv::Error::new(opencv::core::StsError, err.to_string()))?;
+    if paths.is_empty() {
+      return Err(opencv::Error::new(opencv::core::StsError, format!("目录中没有图片 {}", dir)));
+    }
+    Ok(Self { paths, index: 0, pacer })
+  }
+}
+
+impl FrameSource for Images {
+  fn read(&mut self) -> opencv::Result<Option<Mat>> {
+    self.pacer.wait();
+
+    let path = &self.paths[self.index % self.paths.len()];
+    self.index += 1;
+    let frame = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_COLOR)?;
+    Ok((!frame.empty()).then_some(frame))
+  }
+}
+
+/// 合成的测试画面: 浅色地面上左右摆动的黑线
+pub struct TestPattern {
+  size: Size,
+  frame: u64,
+  pacer: Pacer,
+}
+
+impl TestPattern {
+  pub const PERIOD: u64 = 120; // 摆动一个来回的帧数
+
+  fn new(width: u32, height: u32, pacer: Pacer) -> Self {
+    Self { size: Size::new(width as i32, height as i32), frame: 0, pacer }
+  }
+}
+
+impl FrameSource for TestPattern {
+  fn read(&mut self) -> opencv::Result<Option<Mat>> {
+    self.pacer.wait();
+
+    let (width, height) = (self.size.width, self.size.height);
+    let mut frame = Mat::new_rows_cols_with_default(height, width, CV_8UC3, Scalar::all(200.0))?;
+
+    let phase = (self.frame % Self::PERIOD) as f64 / Self::PERIOD as f64 * std::f64::consts::TAU;
+    let offset = (phase.sin() * width as f64 * 0.25) as i32;
+    let (bottom, top) = (Point::new(width / 2 + offset, height), Point::new(width / 2 + offset * 2, 0));
+    imgproc::line(&mut frame, bottom, top, Scalar::all(20.0), width / 16, imgproc::LINE_8, 0)?;
+
+    self.frame += 1;
+    Ok(Some(frame))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use opencv::core::MatTraitConst;
+
+  use super::{open, FrameSource};
+  use crate::{
+    settings::{CameraSettings, CameraSource},
+    trace,
+  };
+
+  fn settings(source: CameraSource) -> CameraSettings {
+    CameraSettings { source, fps: 1000, ..Default::default() }
+  }
+
+  #[test]
+  fn test_images() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/trace").to_string();
+    let mut source = open(&settings(CameraSource::Images { dir })).unwrap();
+    for _ in 0..10 {
+      assert!(!source.read().unwrap().unwrap().empty());
+    }
+  }
+
+  #[test]
+  fn test_test_pattern() {
+    let mut source: Box<dyn FrameSource> = open(&settings(CameraSource::TestPattern)).unwrap();
+    let mut follower = trace::follower(Default::default(), &Default::default());
+    let mut frame = source.read().unwrap().unwrap();
+    assert_eq!((frame.cols(), frame.rows()), (320, 240));
+    assert!(follower.follow(&mut frame).unwrap().unwrap().abs() < 0.05);
+  }
+
+  #[test]
+  fn test_missing() {
+    assert!(open(&settings(CameraSource::Images { dir: "not-exists".to_string() })).is_err());
+    assert!(open(&settings(CameraSource::Video { path: "not-exists.mp4".to_string() })).is_err());
+  }
+}
@@ -0,0 +1,84 @@
+//! 本地时间
+//!
+//! 树莓派没有 RTC, 开机时常常没有网络, 系统时钟可能不准确; 客户端连接后会发送自己的时间,
+//! 两者的偏差保存在 Statistics::clock_offset 中
+
+use car_utils::Statistics;
+use time::OffsetDateTime;
+
+#[cfg(any(feature = "rasp", test))]
+use log::warn;
+#[cfg(feature = "rasp")]
+use time::Duration;
+#[cfg(any(feature = "rasp", test))]
+use time::UtcOffset;
+#[cfg(any(feature = "rasp", test))]
+use tz::TimeZone;
+
+/// 时区
+#[cfg(any(feature = "rasp", test))]
+pub struct Clock {
+  time_zone: TimeZone,
+}
+
+#[cfg(any(feature = "rasp", test))]
+impl Clock {
+  /// time_zone: 配置的时区, 如 "Asia/Shanghai" 或 POSIX TZ 字符串;
+  /// 未配置或无效时使用系统时区 (TZ 环境变量或 /etc/localtime), 都失败时使用 UTC
+  pub fn new(time_zone: Option<&str>) -> Self {
+    let time_zone = time_zone
+      .and_then(|name| TimeZone::from_posix_tz(name).inspect_err(|err| warn!("时区 {}: {}", name, err)).ok())
+      .or_else(|| TimeZone::local().inspect_err(|err| warn!("系统时区: {}, 使用 UTC", err)).ok())
+      .unwrap_or_else(TimeZone::utc);
+    Self { time_zone }
+  }
+
+  /// 校准后的本地时间
+  #[cfg(feature = "rasp")]
+  pub fn now(&self, statistics: &Statistics) -> OffsetDateTime {
+    self.to_local(OffsetDateTime::now_utc() + Duration::milliseconds(statistics.clock_offset()))
+  }
+
+  /// 转换为本地时间, 考虑夏令时
+  pub fn to_local(&self, time: OffsetDateTime) -> OffsetDateTime {
+    let offset = self.time_zone.find_local_time_type(time.unix_timestamp()).map(|t| t.ut_offset()).unwrap_or(0);
+    time.to_offset(UtcOffset::from_whole_seconds(offset).unwrap_or(UtcOffset::UTC))
+  }
+}
+
+/// 客户端时间 (Unix 时间戳 ms) 与系统时钟的偏差 ms
+pub fn offset_from(timestamp: u64) -> i64 {
+  timestamp as i64 - (OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// 校准后的 Unix 时间戳 ms
+pub fn timestamp(statistics: &Statistics) -> u64 {
+  ((OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64 + statistics.clock_offset()) as u64
+}
+
+#[cfg(test)]
+mod test {
+  use time::{Duration, OffsetDateTime};
+
+  use super::{offset_from, Clock};
+
+  #[test]
+  fn test_to_local() {
+    let utc = |timestamp| OffsetDateTime::from_unix_timestamp(timestamp).unwrap();
+
+    // 2024-08-01 20:30 UTC
+    let local = Clock::new(Some("CST-8")).to_local(utc(1722544200));
+    assert_eq!((local.day(), local.hour(), local.minute()), (2, 4, 30));
+
+    // 美国东部时间: 夏令时 UTC-4, 冬令时 UTC-5
+    let clock = Clock::new(Some("EST5EDT,M3.2.0,M11.1.0"));
+    assert_eq!(clock.to_local(utc(1719835200)).hour(), 8); // 2024-07-01 12:00 UTC
+    assert_eq!(clock.to_local(utc(1704110400)).hour(), 7); // 2024-01-01 12:00 UTC
+  }
+
+  #[test]
+  fn test_offset_from() {
+    let now = (OffsetDateTime::now_utc() + Duration::hours(1)).unix_timestamp_nanos() / 1_000_000;
+    assert!((offset_from(now as u64) - 3_600_000).abs() < 1000);
+  }
+}
@@ -1,38 +1,157 @@
+// 引脚与传感器参数只在树莓派上使用
+
 use std::net::Ipv4Addr;
 
+use crate::dht::SensorType;
+
 pub const LISTEN_ADDR: Ipv4Addr = Ipv4Addr::UNSPECIFIED; // 监听地址
 pub const LISTEN_PORT: u16 = 5000; // 监听端口号
-                                   //
+#[cfg(feature = "rasp")]
+pub const STREAM_PORT: u16 = 5001; // 推流端口号
+pub const STATISTICS_INTERVAL_MS: u64 = 1000; // 统计数据没有变化时的推送间隔 ms
+pub const STATISTICS_MIN_INTERVAL_MS: u64 = 100; // 统计数据有变化时两次推送的默认最小间隔 ms
+pub const SUBSCRIPTION_MIN_INTERVAL_MS: u64 = 20; // 订阅时可设置的最小推送间隔 ms
+pub const ACK_RESERVED_LEN: usize = 128; // 发送缓冲区为请求的处理结果保留的空间
+pub const MAX_PENDING_ACKS: usize = 16; // 等待发送的处理结果超过该数量时暂停处理请求
+#[cfg(feature = "rasp")]
+pub const STREAM_INTERVAL_MS: u64 = 66; // 推流的最小帧间隔 ms
+#[cfg(feature = "rasp")]
+pub const STREAM_JPEG_QUALITY: i32 = 70; // 推流的 JPEG 质量 0-100
+                                         //
 
 pub const MIN_DISTANCE: u16 = 20; // 可以距障碍物的最小距离 cm
-                                  // 引脚配置 BCM 编号
-                                  // RGB LED 灯
+pub const WAKE_PERIOD_MS: u64 = 50; // 检查状态并唤醒各功能线程的间隔 ms
+
+#[cfg(feature = "rasp")]
+pub const ULTRASONIC_MIN_DISTANCE: u16 = 2; // 超声波: 最小量程 cm
+#[cfg(feature = "rasp")]
+pub const ULTRASONIC_MAX_DISTANCE: u16 = 400; // 超声波: 最大量程 cm
+#[cfg(feature = "rasp")]
+pub const ULTRASONIC_WINDOW: usize = 5; // 超声波: 中值滤波窗口大小
+#[cfg(feature = "rasp")]
+pub const ULTRASONIC_SAMPLES: usize = 3; // 超声波: 转向新方向后测量的次数
+#[cfg(feature = "rasp")]
+pub const ULTRASONIC_PERIOD_MS: u64 = 60; // 超声波: 连续测距的周期 ms, 不小于两次测量的最小间隔
+#[cfg(feature = "rasp")]
+pub const ULTRASONIC_PRIORITY: u8 = 2; // 超声波: 调度优先级, 刹车依赖测距, 最优先
+#[cfg(feature = "rasp")]
+pub const ULTRASONIC_TIMEOUT_MS: u64 = 30; // 超声波: 单次测量的预计最长时间, 最大量程往返约 24 ms
+                                           // 引脚配置 BCM 编号
+                                           // RGB LED 灯
+
+pub const TRACE_PID: (f32, f32, f32) = (0.6, 0.0, 0.05); // 寻迹: 默认的 PID 参数 kp, ki, kd
+#[cfg(any(feature = "vision", test))]
+pub const TRACE_PID_INTEGRAL_LIMIT: f64 = 1_f64; // 寻迹: 积分项的上限, 防止积分饱和
+#[cfg(any(feature = "vision", test))]
+pub const TRACE_SEARCH_SPEED: f64 = 0.8; // 寻迹: 丢线后原地旋转寻找的速度 (相对基础速度)
+#[cfg(any(feature = "vision", test))]
+pub const TRACE_SEARCH_TIMEOUT_MS: u64 = 3000; // 寻迹: 丢线后寻找的最长时间 ms, 超时停车
+#[cfg(feature = "vision")]
+pub const TRACE_WINDOWS: usize = 8; // 寻迹: 滑动窗口的数量
+#[cfg(feature = "vision")]
+pub const TRACE_WINDOW_MARGIN: f64 = 0.1; // 寻迹: 滑动窗口的半宽 (相对画面宽度)
+#[cfg(feature = "vision")]
+pub const TRACE_WINDOW_MIN_PIXELS: usize = 50; // 寻迹: 窗口内像素少于该值时认为没有线
+#[cfg(feature = "vision")]
+pub const MARKER_MIN_AREA: f64 = 0.01; // 标志: 色块面积至少占画面的比例
+#[cfg(any(feature = "vision", test))]
+pub const MARKER_CONFIRM_FRAMES: usize = 3; // 标志: 连续识别到的帧数, 达到后才生效
+#[cfg(any(feature = "vision", test))]
+pub const MARKER_SLOW_FACTOR: f64 = 0.5; // 标志: 减速标志生效时的速度 (相对基础速度)
+
+pub const SCAN_SETTLE_MS: u64 = 80; // 雷达扫描: 舵机转动后等待稳定的时间 ms
+pub const SCAN_MAX_POINTS: usize = 37; // 雷达扫描: 一次扫描最多的测量点数 (受回复包长度限制)
 
-pub const CAMERA_INDEX: i32 = 0; // 寻迹摄像头
+pub const AUTOPILOT_DEFAULT_SPEED: u8 = 30; // 自动避障: 未设置速度时使用的速度
+pub const AUTOPILOT_SETTLE_MS: u64 = 400; // 自动避障: 舵机转向左/右侧后等待稳定的时间 ms
+pub const AUTOPILOT_STOP_MS: u64 = 200; // 自动避障: 停车等待的时间 ms
+pub const AUTOPILOT_TURN_MS: u64 = 500; // 自动避障: 转向持续的时间 ms
+pub const AUTOPILOT_BACKWARD_MS: u64 = 600; // 自动避障: 后退持续的时间 ms
 
+pub const MISSION_TICK_MS: u64 = 20; // 任务: 检查暂停与中止的间隔 ms
+pub const MISSION_CM_PER_SEC: f64 = 60_f64; // 任务: 满速时每秒行驶的距离 cm, 用于将距离换算为时间
+pub const MISSION_DEG_PER_SEC: f64 = 180_f64; // 任务: 满速时每秒原地转向的角度
+
+pub const MACRO_TICK_MS: u64 = 20; // 回放: 检查停止与障碍物的间隔 ms
+pub const MACRO_MIN_SCALE: u8 = 25; // 回放: 最小的速度百分比
+pub const MACRO_MAX_SCALE: u8 = 200; // 回放: 最大的速度百分比
+
+pub const SCRIPT_TICK_MS: u64 = 10; // 脚本: 检查周期回调的间隔 ms
+pub const SCRIPT_MIN_INTERVAL_MS: u64 = 20; // 脚本: 周期回调的最小间隔 ms
+pub const SCRIPT_MAX_OPERATIONS: u64 = 1_000_000; // 脚本: 顶层代码或一次回调最多执行的操作数
+
+#[cfg(feature = "rasp")]
 pub const PIN_LED_RED: u8 = 22;
+#[cfg(feature = "rasp")]
 pub const PIN_LED_GREEN: u8 = 27;
+#[cfg(feature = "rasp")]
 pub const PIN_LED_BLUE: u8 = 17;
+#[cfg(feature = "rasp")]
+pub const LED_PWM_FREQUENCY: f64 = 100_f64; // 软件 PWM 频率 Hz
+#[cfg(feature = "rasp")]
+pub const LED_TICK_MS: u64 = 20; // 灯效刷新间隔 ms
+#[cfg(any(feature = "rasp", test))]
+pub const LED_BLINK_PERIOD_MS: u64 = 1000; // 闪烁周期 ms
+#[cfg(any(feature = "rasp", test))]
+pub const LED_BREATHE_PERIOD_MS: u64 = 3000; // 呼吸周期 ms
+#[cfg(any(feature = "rasp", test))]
+pub const LED_RAINBOW_PERIOD_MS: u64 = 6000; // 彩虹渐变周期 ms
 
 // L298n 驱动板
+#[cfg(feature = "rasp")]
 pub const PIN_L298N_IN1: u8 = 5;
+#[cfg(feature = "rasp")]
 pub const PIN_L298N_IN2: u8 = 6;
+#[cfg(feature = "rasp")]
 pub const PIN_L298N_IN3: u8 = 13;
+#[cfg(feature = "rasp")]
 pub const PIN_L298N_IN4: u8 = 19;
 
 // TM1637: 4位数码管
+#[cfg(feature = "rasp")]
 pub const PIN_TM1637_CLK: u8 = 16;
+#[cfg(feature = "rasp")]
 pub const PIN_TM1637_DIO: u8 = 20;
+#[cfg(feature = "rasp")]
+pub const NIXIE_TICK_MS: u64 = 100; // 刷新间隔 ms
+#[cfg(any(feature = "rasp", test))]
+pub const NIXIE_SCROLL_MS: u64 = 300; // 滚动文字每移动一位的时间 ms
+#[cfg(any(feature = "rasp", test))]
+pub const NIXIE_CYCLE_SECS: u64 = 3; // 轮换模式下每项显示的时间 s
 
 // DHT11：温湿度传感器
+#[cfg(feature = "rasp")]
 pub const PIN_DHT11_DATA: u8 = 4;
+#[cfg(not(feature = "dht22"))]
+pub const TH_SENSOR: SensorType = SensorType::Dht11; // 传感器型号, 开启 dht22 特性时为 DHT22
+#[cfg(feature = "dht22")]
+pub const TH_SENSOR: SensorType = SensorType::Dht22;
+pub const TH_PERIOD_MS: u64 = 5000; // 采样周期 ms, 不小于传感器的最小采样间隔
+#[cfg(feature = "rasp")]
+pub const TH_RETRIES: usize = 2; // 读取失败后的重试次数, 第一次重试等待最小采样间隔, 之后每次加倍
+pub const TH_PRIORITY: u8 = 1; // 调度优先级, 越大越优先
+pub const TH_TIMEOUT_MS: u64 = 30; // 单次读取的预计最长时间: 起始信号 18 ms, 数据约 5 ms
 
 // HC-SRC04: 超声波
+#[cfg(feature = "rasp")]
 pub const PIN_HCSRC04_TRIG: u8 = 23;
+#[cfg(feature = "rasp")]
 pub const PIN_HCSRC04_ECHO: u8 = 24;
 
 // 蜂鸣器
-// pub const PIN_BUZZER_CTL: u8 = 0;
+#[cfg(feature = "rasp")]
+pub const PIN_BUZZER_CTL: u8 = 18;
+pub const BUZZER_GAP_MS: u64 = 20; // 相邻音符之间的停顿 ms
+pub const OVER_TEMPERATURE: f32 = 45_f32; // 超过该温度时报警 °C
+
+// 环境报警
+pub const ALARM_HYSTERESIS: f32 = 1_f32; // 阈值规则解除报警的回差, 避免读数在阈值附近时反复报警
+pub const ALARM_RATE_WINDOW_S: u64 = 60; // 变化率: 以该时间内最早的读数计算
+pub const ALARM_RATE_MIN_S: u64 = 10; // 变化率: 读数跨越的时间少于该值时不计算
+
+// 巡测
+pub const SURVEY_INTERVAL_MS: u64 = 1000; // 记录样本的最小间隔, 温湿传感器约 1 秒更新一次
 
 // 舵机控制
+#[cfg(feature = "rasp")]
 pub const PIN_SERVOS_CTL: u8 = 26;
@@ -1,4 +1,5 @@
 use std::{
+  collections::VecDeque,
   io::{self, ErrorKind, Read, Write},
   net::TcpStream,
   sync::Arc,
@@ -8,15 +9,17 @@ use std::{
 
 use car_utils::{
   buffer::RingBuffer,
-  command::{Command, Navigate},
-  ResponseType, Statistics, REQUEST_HEADER_LEN, RESPONSE_HEADER_LEN,
+  command::{Command, MacroAction, Navigate, RequestHeader, ScriptAction},
+  mission::{MissionAction, MissionState},
+  Ack, MacroState, NixieMode, RequestError, ResponseType, Sound, Statistics, Topic, REQUEST_HEADER_LEN,
+  RESPONSE_HEADER_LEN,
 };
 use log::{debug, info};
 use serde::Serialize;
 
-use crate::config;
 #[cfg(feature = "rasp")]
 use crate::driver::Drivers;
+use crate::{clock, config, macros::Macros, subscription::Subscriptions};
 
 pub struct Connection {
   stream: TcpStream,
@@ -25,7 +28,15 @@ pub struct Connection {
   rx_buffer: RingBuffer<1024>,
 
   statistics: Arc<Statistics>,
-  instant: Instant, // 下一次推送统计数据的时间
+  macros: Arc<Macros>,
+  subscriptions: Subscriptions, // 订阅的统计数据
+  scan_version: u32,            // 已推送的雷达扫描版本
+  measurement_version: u32,     // 已推送的任务测量结果版本
+  macros_version: u32,          // 已推送的录制列表版本
+  log_seq: u32,                 // 已推送的脚本日志序号
+  alarm_seq: u32,               // 已推送的报警事件序号
+  sample_seq: u32,              // 已推送的巡测样本序号
+  acks: VecDeque<Ack>,          // 等待发送的处理结果
 
   #[cfg(feature = "rasp")]
   drivers: Arc<Drivers>,
@@ -35,6 +46,7 @@ impl Connection {
   pub fn new(
     stream: TcpStream,
     statistics: Arc<Statistics>,
+    macros: Arc<Macros>,
     #[cfg(feature = "rasp")] drivers: Arc<Drivers>,
   ) -> Connection {
     Connection {
@@ -42,7 +54,15 @@ impl Connection {
       tx_buffer: RingBuffer::new(),
       rx_buffer: RingBuffer::new(),
       statistics,
-      instant: Instant::now(),
+      macros,
+      subscriptions: Subscriptions::default(),
+      scan_version: 0,
+      measurement_version: 0,
+      macros_version: 0,
+      log_seq: 0,
+      alarm_seq: 0,
+      sample_seq: 0,
+      acks: VecDeque::new(),
       #[cfg(feature = "rasp")]
       drivers,
     }
@@ -80,32 +100,82 @@ impl Connection {
         break;
       }
 
-      // 负载的最大长度 256
-      while self.rx_buffer.peek_one().map_or(false, |len| self.rx_buffer.len() >= len as usize + REQUEST_HEADER_LEN) {
-        let len = self.rx_buffer.dequeue_one().unwrap() as usize;
+      // 负载的最大长度 256, 处理结果积压时暂停处理请求
+      while self.acks.len() < config::MAX_PENDING_ACKS
+        && self.rx_buffer.peek_one().is_some_and(|len| self.rx_buffer.len() >= len as usize + REQUEST_HEADER_LEN)
+      {
+        let header = RequestHeader::parse([(); REQUEST_HEADER_LEN].map(|_| self.rx_buffer.dequeue_one().unwrap()));
+        let (len, seq) = (header.len as usize, header.seq);
         self.rx_buffer.congestion_alloced(len);
-        self.rx_buffer.dequeue_with(len, |_payload| {
-          let command = Command::parse(_payload)
-            .inspect_err(|err| {
-              println!("{:?}", err);
-            })
-            .unwrap_or_default();
+        let result = self.rx_buffer.dequeue_with(len, |payload| {
+          let command = Command::parse(payload).inspect_err(|err| debug!("请求 {}: {:?}", seq, err))?;
 
+          // 订阅只对本连接生效
+          match command {
+            Command::Statistics => self.subscriptions.refresh(),
+            Command::Subscribe { ref subscriptions } => self.subscriptions.subscribe(subscriptions),
+            _ => {}
+          }
           request_handler(
             #[cfg(feature = "rasp")]
             &self.drivers,
             &self.statistics,
+            &self.macros,
             command,
-          );
+          )
         });
 
-        self.send_statistics(); // 每一次请求都回复一次统计数据
+        if let Err(err) = result {
+          debug!("请求 {} 失败: {:?}", seq, err);
+        }
+        self.acks.push_back(Ack { seq, error: result.err() });
+      }
+
+      // 处理结果优先发送, 缓冲区满时留到下一次
+      while let Some(ack) = self.acks.front().copied() {
+        if !self.send_response(ResponseType::Ack, &ack) {
+          break;
+        }
+        self.acks.pop_front();
+      }
+
+      // 检查是否需要发送统计数据
+      self.send_statistics(Instant::now());
+
+      // 检查是否有新的雷达扫描结果
+      if let Some((version, scan)) = self.statistics.scan_since(self.scan_version) {
+        if self.send_response(ResponseType::Scan, &scan) {
+          self.scan_version = version;
+        }
+      }
+
+      // 检查是否有新的任务测量结果
+      if let Some((version, measurement)) = self.statistics.measurement_since(self.measurement_version) {
+        if self.send_response(ResponseType::Measurement, &measurement) {
+          self.measurement_version = version;
+        }
+      }
+
+      // 检查录制列表是否有变化
+      if let Some((version, list)) = self.macros.list_since(self.macros_version) {
+        if self.send_response(ResponseType::Macros, &list) {
+          self.macros_version = version;
+        }
       }
 
-      // 检查是需要发送统计数据
-      let now = Instant::now();
-      if self.instant <= now {
-        self.send_statistics();
+      // 检查是否有新的报警事件
+      if let Some((seq, alarms)) = self.statistics.alarms_since(self.alarm_seq) {
+        self.alarm_seq = self.send_events(ResponseType::Alarm, self.alarm_seq, seq, alarms);
+      }
+
+      // 检查是否有新的巡测样本
+      if let Some((seq, samples)) = self.statistics.samples_since(self.sample_seq) {
+        self.sample_seq = self.send_events(ResponseType::Sample, self.sample_seq, seq, samples);
+      }
+
+      // 检查是否有新的脚本日志
+      if let Some((seq, logs)) = self.statistics.logs_since(self.log_seq) {
+        self.log_seq = self.send_events(ResponseType::Log, self.log_seq, seq, logs);
       }
 
       // 发送数据
@@ -123,23 +193,60 @@ impl Connection {
     })
   }
 
-  /// 发送统计信息
-  fn send_statistics(&mut self) {
-    let now = Instant::now();
-    self.send_response(ResponseType::Statistics, &self.statistics.to_response());
-    self.instant = now + Duration::from_secs(1);
+  /// 发送统计信息: 只包含到期的主题中的字段, 客户端合并到之前收到的统计信息中
+  fn send_statistics(&mut self, now: Instant) {
+    let topics = self.subscriptions.due(self.statistics.versions(), now);
+    if topics.is_empty() {
+      return;
+    }
+    let snapshot = self.statistics.snapshot();
+    let Ok(serde_json::Value::Object(mut fields)) = serde_json::to_value(snapshot.response) else {
+      return;
+    };
+    fields.retain(|field, _| topics.contains(&Topic::of(field)));
+
+    if self.send_response(ResponseType::Statistics, &fields) {
+      self.subscriptions.pushed(&topics, snapshot.versions, now);
+    }
   }
 
-  /// 发送回复
-  fn send_response<T: Serialize>(&mut self, response_type: ResponseType, payload: &T) {
+  /// 依次发送序号比 seq 新的事件, 返回已放入 tx_buffer 的最后一个序号, 放不下的下次再发送
+  /// 没有事件 (已被新事件挤出) 时直接跳到 current
+  fn send_events<T: Serialize>(
+    &mut self,
+    response_type: ResponseType,
+    seq: u32,
+    current: u32,
+    events: Vec<(u32, T)>,
+  ) -> u32 {
+    if events.is_empty() {
+      return current;
+    }
+    let mut sent = seq;
+    for (seq, event) in events {
+      if !self.send_response(response_type, &event) {
+        break;
+      }
+      sent = seq;
+    }
+    sent
+  }
+
+  /// 发送回复, 返回是否已放入 tx_buffer
+  /// 除处理结果外的回复为处理结果保留 ACK_RESERVED_LEN 的空间
+  fn send_response<T: Serialize>(&mut self, response_type: ResponseType, payload: &T) -> bool {
     let payload = serde_json::json!(payload).to_string();
     let payload = payload.as_bytes();
-    if payload.len() > u8::MAX as usize {
-      return;
+    if payload.len() > u16::MAX as usize {
+      return false;
     }
 
-    if self.tx_buffer.free_len() >= payload.len() + RESPONSE_HEADER_LEN {
-      self.tx_buffer.enqueue_one(payload.len() as u8).unwrap();
+    let reserved = if matches!(response_type, ResponseType::Ack) { 0 } else { config::ACK_RESERVED_LEN };
+    let enqueued = self.tx_buffer.free_len() >= payload.len() + RESPONSE_HEADER_LEN + reserved;
+    if enqueued {
+      let [high, low] = (payload.len() as u16).to_be_bytes();
+      self.tx_buffer.enqueue_one(high).unwrap();
+      self.tx_buffer.enqueue_one(low).unwrap();
       self.tx_buffer.enqueue_one(response_type as u8).unwrap();
 
       let payload = self
@@ -165,30 +272,64 @@ impl Connection {
 
       // self.stream.write_all(&[&[payload.len() as u8, command as u8], payload].concat())
     }
+    enqueued
   }
 }
 
-/// 请求处理函数
+/// 请求处理函数, 返回未能执行的原因; 执行成功的命令才会被录制
 fn request_handler(
   #[cfg(feature = "rasp")] drivers: &Arc<Drivers>, //
   statistics: &Arc<Statistics>,
+  macros: &Macros,
   command: Command,
-) {
+) -> Result<(), RequestError> {
   debug!("command: {:?}", command);
+  let recorded = command.clone();
+  execute(
+    #[cfg(feature = "rasp")]
+    drivers,
+    statistics,
+    macros,
+    command,
+  )?;
+  macros.record(&recorded);
+  Ok(())
+}
 
+/// 执行命令
+fn execute(
+  #[cfg(feature = "rasp")] drivers: &Arc<Drivers>, //
+  statistics: &Arc<Statistics>,
+  macros: &Macros,
+  command: Command,
+) -> Result<(), RequestError> {
+  // 同一条命令的修改在一个写入批次中, 批次只包住 setter, 不包住硬件与文件操作
   match command {
     Command::NOP => {}
-    Command::Statistics => {}
+    Command::Statistics | Command::Subscribe { .. } => {} // 由连接处理
+    Command::Navigate { .. } if statistics.autopilot() => {
+      debug!("自动避障中, 忽略导航命令");
+      return Err(RequestError::Busy);
+    }
+    Command::Navigate { .. } if statistics.macro_state() == Some(MacroState::Playing) => {
+      debug!("回放中, 忽略导航命令");
+      return Err(RequestError::Busy);
+    }
     Command::Navigate { mut navigate, speed } => {
-      if statistics.ultrasonic()
-        && statistics.distance() <= config::MIN_DISTANCE
-        && navigate == Navigate::Forward
-        && statistics.servos() == 90
       {
-        debug!("障碍物");
-        navigate = Navigate::Brake; // 小于该距离就刹车
+        let _batch = statistics.batch();
+        take_motion(statistics, Motion::Manual);
+        if statistics.ultrasonic()
+          && statistics.distance() <= config::MIN_DISTANCE
+          && navigate == Navigate::Forward
+          && statistics.servos() == 90
+        {
+          debug!("障碍物");
+          navigate = Navigate::Brake; // 小于该距离就刹车
+          statistics.set_sound(Sound::Obstacle);
+        }
+        statistics.set_speed(speed);
       }
-      statistics.set_speed(speed);
 
       #[cfg(feature = "rasp")]
       drivers.montor.lock().unwrap().navigate(navigate, speed);
@@ -196,29 +337,242 @@ fn request_handler(
     Command::TH { enabled } => {
       statistics.set_th(enabled);
     }
-    Command::Nixie { enabled, brightness } => {
+    Command::Nixie { enabled, brightness, mode } => {
+      let _batch = statistics.batch();
       statistics.set_nixie(enabled);
       if enabled {
         statistics.set_nixie_brightness(brightness);
+        statistics.set_nixie_mode(mode);
       }
     }
+    Command::NixieText { text } => {
+      let _batch = statistics.batch();
+      statistics.set_nixie_text(text);
+      statistics.set_nixie_mode(NixieMode::Text);
+    }
+    Command::NixieSegments { segments } => {
+      let _batch = statistics.batch();
+      statistics.set_nixie_segments(segments);
+      statistics.set_nixie_mode(NixieMode::Segments);
+    }
+    Command::SyncTime { timestamp } => {
+      statistics.set_clock_offset(clock::offset_from(timestamp));
+      info!("校时: 偏差 {}ms", statistics.clock_offset());
+    }
+    Command::Servos { angle } if angle > 180 => return Err(RequestError::InvalidAngle),
+    Command::Servos { .. } if statistics.scan() || statistics.autopilot() => {
+      debug!("雷达扫描或自动避障中, 忽略舵机命令");
+      return Err(RequestError::Busy);
+    }
     Command::Servos { angle } => {
       #[cfg(feature = "rasp")]
-      if drivers.servos.lock().unwrap().rotate(angle) {
-        statistics.set_servos(angle)
-      };
-
-      #[cfg(not(feature = "rasp"))]
+      if !drivers.servos.lock().unwrap().rotate(angle) {
+        return Err(RequestError::HardwareFailure);
+      }
       statistics.set_servos(angle);
     }
-    Command::Trace { enabled } => {
+    Command::Trace { enabled, algorithm } => {
+      let _batch = statistics.batch();
+      if enabled {
+        take_motion(statistics, Motion::Trace);
+        statistics.set_trace_algorithm(algorithm);
+      }
       statistics.set_trace(enabled);
     }
+    Command::Survey { enabled } => {
+      let _batch = statistics.batch();
+      if enabled {
+        take_motion(statistics, Motion::Trace);
+        statistics.set_th(true);
+        statistics.set_trace(true);
+        statistics.start_survey();
+      } else {
+        statistics.set_survey(false);
+        statistics.set_trace(false);
+      }
+    }
+    Command::TracePid { kp, ki, kd } => {
+      statistics.set_trace_pid((kp, ki, kd));
+    }
+    Command::Stream { enabled, overlay } => {
+      let _batch = statistics.batch();
+      statistics.set_stream(enabled);
+      statistics.set_stream_overlay(overlay);
+    }
     Command::Ultrasonic { enabled } => {
       statistics.set_ultrasonic(enabled);
     }
-    Command::Led { enabled } => {
+    Command::Led { enabled, effect, color, brightness } => {
+      let _batch = statistics.batch();
+      if enabled {
+        statistics.set_led_effect(effect);
+        statistics.set_led_color(color);
+        statistics.set_led_brightness(brightness.min(100));
+      }
       statistics.set_led(enabled);
     }
+    Command::Scan { enabled: true, from, to, .. } if from > 180 || to > 180 => return Err(RequestError::InvalidAngle),
+    Command::Scan { enabled, from, to, step } => {
+      let _batch = statistics.batch();
+      if enabled {
+        take_motion(statistics, Motion::Scan);
+        statistics.set_scan_from(from);
+        statistics.set_scan_to(to);
+        statistics.set_scan_step(step);
+      }
+      statistics.set_scan(enabled);
+    }
+    Command::Autopilot { enabled } => {
+      let _batch = statistics.batch();
+      if enabled {
+        take_motion(statistics, Motion::Autopilot);
+      }
+      statistics.set_autopilot(enabled);
+    }
+    Command::Buzzer { sound } => {
+      statistics.set_sound(sound);
+    }
+    Command::Melody { melody } => {
+      let _batch = statistics.batch();
+      statistics.set_melody(melody);
+      statistics.set_sound(Sound::Custom);
+    }
+    Command::Mission { steps } => {
+      let _batch = statistics.batch();
+      take_motion(statistics, Motion::Mission);
+      info!("任务: {} 步", steps.len());
+      statistics.load_mission(steps);
+    }
+    Command::MissionControl { action } => {
+      let state = match (action, statistics.mission_state()) {
+        (MissionAction::Pause, MissionState::Running) => MissionState::Paused,
+        (MissionAction::Resume, MissionState::Paused) => MissionState::Running,
+        (MissionAction::Abort, state) if state.is_active() => MissionState::Aborted,
+        (_, state) => state,
+      };
+      statistics.set_mission_state(state);
+    }
+    Command::ScriptSource { text } => {
+      if !statistics.append_script_source(&text) {
+        statistics.log("脚本过长".to_string());
+        return Err(RequestError::InvalidArgument);
+      }
+    }
+    Command::Macro { action, scale, name } => {
+      let result = match (action, statistics.macro_state()) {
+        (MacroAction::Record, None) => macros.start_recording(&name).map(|_| {
+          info!("录制: {}", name);
+          statistics.set_macro_state(Some(MacroState::Recording));
+        }),
+        (MacroAction::Play, None) => macros.play(&name, scale).map(|_| {
+          let _batch = statistics.batch();
+          take_motion(statistics, Motion::Playback);
+          statistics.set_macro_state(Some(MacroState::Playing));
+        }),
+        (MacroAction::Stop, Some(MacroState::Recording)) => {
+          statistics.set_macro_state(None);
+          macros.finish_recording().map(|name| info!("录制完成: {:?}", name))
+        }
+        (MacroAction::Stop, Some(MacroState::Playing)) => {
+          statistics.set_macro_state(None);
+          Ok(())
+        }
+        (MacroAction::Delete, _) => macros.delete(&name),
+        (action, state) => {
+          debug!("{:?} 时忽略 {:?}", state, action);
+          return Err(RequestError::Busy);
+        }
+      };
+      result.map_err(|err| {
+        log::warn!("录制与回放: {}", err);
+        match err.kind() {
+          io::ErrorKind::InvalidInput | io::ErrorKind::NotFound => RequestError::InvalidArgument,
+          _ => RequestError::HardwareFailure,
+        }
+      })?;
+    }
+    Command::Script { action } => match action {
+      ScriptAction::Start => {
+        let _batch = statistics.batch();
+        take_motion(statistics, Motion::Script);
+        statistics.start_script();
+      }
+      ScriptAction::Stop => statistics.set_script(false),
+      ScriptAction::Clear => statistics.clear_script_source(),
+    },
+  }
+  Ok(())
+}
+
+/// 控制电机与舵机的运动模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+  Manual, // 手动导航, 只控制电机, 不停止雷达扫描
+  Trace,  // 寻迹与巡测
+  Scan,
+  Autopilot,
+  Mission,
+  Script,
+  Playback,
+}
+
+/// 切换运动模式: 同一时间只有一个模式控制电机与舵机, 停止其它模式
+fn take_motion(statistics: &Statistics, motion: Motion) {
+  let _batch = statistics.batch();
+  if motion != Motion::Trace {
+    statistics.set_trace(false);
+    statistics.set_survey(false);
+  }
+  if !matches!(motion, Motion::Scan | Motion::Manual) {
+    statistics.set_scan(false);
+  }
+  if motion != Motion::Autopilot {
+    statistics.set_autopilot(false);
+  }
+  if motion != Motion::Mission {
+    abort_mission(statistics);
+  }
+  if motion != Motion::Script {
+    statistics.set_script(false);
+  }
+  if motion != Motion::Playback && statistics.macro_state() == Some(MacroState::Playing) {
+    statistics.set_macro_state(None);
+  }
+}
+
+/// 中止正在执行的任务
+fn abort_mission(statistics: &Statistics) {
+  if statistics.mission_state().is_active() {
+    statistics.set_mission_state(MissionState::Aborted);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use car_utils::{MacroState, Statistics};
+
+  use super::{take_motion, Motion};
+
+  #[test]
+  fn test_take_motion() {
+    let statistics = Statistics::default();
+    statistics.set_trace(true);
+    statistics.set_scan(true);
+    statistics.set_script(true);
+    statistics.set_macro_state(Some(MacroState::Playing));
+    take_motion(&statistics, Motion::Autopilot);
+    assert!(!statistics.trace() && !statistics.scan() && !statistics.script());
+    assert_eq!(statistics.macro_state(), None);
+
+    // 手动导航停止其它模式, 但不停止雷达扫描
+    statistics.set_autopilot(true);
+    statistics.set_scan(true);
+    take_motion(&statistics, Motion::Manual);
+    assert!(!statistics.autopilot() && statistics.scan());
+
+    // 录制不是运动模式
+    statistics.set_macro_state(Some(MacroState::Recording));
+    take_motion(&statistics, Motion::Script);
+    assert_eq!(statistics.macro_state(), Some(MacroState::Recording));
   }
 }
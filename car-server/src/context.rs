@@ -9,25 +9,48 @@ use std::{
   time::Duration,
 };
 
-use car_utils::Statistics;
+use car_utils::{
+  command::{Command, Navigate},
+  mission::{Measurement, MissionState},
+  Alarm, AutopilotState, LedEffect, MacroState, Sensor, Sound, Statistics,
+};
 use log::{debug, info};
 
-use time::{OffsetDateTime, Time};
-
-use crate::connection::Connection;
+use crate::{
+  alarm::Alarms,
+  autopilot::Autopilot,
+  clock, config,
+  connection::Connection,
+  macros::{Macros, Playback},
+  melody,
+  mission::{Action, Timer},
+  odometer::Odometer,
+  radar::Sweep,
+  scheduler::{Scheduler, Task},
+  script::{self, Script},
+  settings::Settings,
+  survey::Survey,
+};
 
 #[cfg(feature = "rasp")]
 use crate::{
-  config,
-  driver::{Drivers, Nixie, RgbLed, Ultrasonic, TH},
-  trace::follow_line,
+  camera,
+  clock::Clock,
+  dht,
+  driver::{Buzzer, Drivers, Nixie, RgbLed, TH},
+  led,
+  marker::Signals,
+  segment,
+  steering::Steering,
+  stream::{self, Frames},
+  trace,
 };
 #[cfg(feature = "rasp")]
-use car_utils::command::Navigate;
+use car_utils::{Marker, StreamOverlay};
 #[cfg(feature = "rasp")]
 use opencv::{
-  core::{Mat, MatTraitConst},
-  videoio::{VideoCapture, VideoCaptureTrait, VideoCaptureTraitConst, CAP_ANY},
+  core::{Mat, MatTraitConst, Vector},
+  imgcodecs, imgproc,
 };
 #[cfg(feature = "rasp")]
 use rppal::gpio::Gpio;
@@ -37,17 +60,35 @@ pub struct Context {
   listener: TcpListener,
 
   statistics: Arc<Statistics>, // 统计信息
+  settings: Arc<Settings>,     // 配置
+  #[cfg(feature = "rasp")]
+  clock: Arc<Clock>, // 本地时间
+  macros: Arc<Macros>,         // 手动驾驶的录制
   #[cfg(feature = "rasp")]
   drivers: Arc<Drivers>,
+  #[cfg(feature = "rasp")]
+  frames: Arc<Frames>, // 推流的画面
 }
 
 impl Context {
-  pub fn new(addr: impl ToSocketAddrs, #[cfg(feature = "rasp")] drivers: Drivers) -> io::Result<Self> {
+  pub fn new(
+    addr: impl ToSocketAddrs,
+    settings: Settings,
+    #[cfg(feature = "rasp")] drivers: Drivers,
+  ) -> io::Result<Self> {
     let listener = TcpListener::bind(addr)?;
     info!("listen on: {:?}", listener.local_addr());
     let statistics = Statistics::default();
     statistics.set_servos(90);
     statistics.set_th(true);
+    statistics.set_led_color((255, 255, 255));
+    statistics.set_led_brightness(100);
+    statistics.set_trace_algorithm(settings.trace_algorithm);
+    statistics.set_trace_pid(config::TRACE_PID);
+    if settings.led_status_on_boot {
+      statistics.set_led_effect(LedEffect::Status);
+      statistics.set_led(true);
+    }
     // statistics.set_ultrasonic(true);
 
     Ok(Self {
@@ -55,7 +96,13 @@ impl Context {
       listener,
       statistics: Arc::new(statistics),
       #[cfg(feature = "rasp")]
+      clock: Arc::new(Clock::new(settings.time_zone.as_deref())),
+      macros: Arc::new(Macros::new(&settings.macros_dir)),
+      settings: Arc::new(settings),
+      #[cfg(feature = "rasp")]
       drivers: Arc::new(drivers),
+      #[cfg(feature = "rasp")]
+      frames: Arc::new(Frames::default()),
     })
   }
 
@@ -76,10 +123,13 @@ impl Context {
       let drivers = Arc::clone(&self.drivers);
 
       let statistics = Arc::clone(&self.statistics);
+      let macros = Arc::clone(&self.macros);
       threads.push(thread::spawn(move || {
+        let _client = statistics.client_connected();
         let mut connection = Connection::new(
           stream,
-          statistics,
+          Arc::clone(&statistics),
+          macros,
           #[cfg(feature = "rasp")]
           drivers,
         );
@@ -102,59 +152,827 @@ impl Context {
     let should_shutdown = Arc::clone(&self.should_shutdown);
     let statistics = Arc::clone(&self.statistics);
 
+    let scan_thread = self.start_scan_thread();
+    let autopilot_thread = self.start_autopilot_thread();
+    let mission_thread = self.start_mission_thread();
+    let script_thread = self.start_script_thread();
+    let macro_thread = self.start_macro_thread();
+    let buzzer_thread = self.start_buzzer_thread();
+
+    let mut alarms = Alarms::new(self.settings.alarms.clone());
+    thread::spawn(move || {
+      use std::time::Instant;
+
+      // 没有电机, 巡测时假设以设定的速度匀速前进
+      let mut odometer = Odometer::new(Instant::now());
+      let mut survey = None;
+      let mut scheduler = Scheduler::default();
+
+      // 温湿传感器: 随机生成读数
+      let th_period = Duration::from_millis(config::TH_PERIOD_MS).max(config::TH_SENSOR.min_interval());
+      statistics.set_sensor_period(Sensor::TH, th_period);
+      scheduler.register(Task::new(
+        "温湿传感器",
+        th_period,
+        config::TH_PRIORITY,
+        Duration::from_millis(config::TH_TIMEOUT_MS),
+        || {
+          use rand::{thread_rng, Rng};
+          let mut thread_rng = thread_rng();
+
+          let speed = if statistics.survey() { statistics.speed() as f64 / 100_f64 } else { 0_f64 };
+          odometer.set(speed, speed, Instant::now());
+
+          if statistics.th() {
+            let (temperature, humidity) =
+              (thread_rng.gen_range(20_f32..=30_f32), thread_rng.gen_range(40_f32..=60_f32));
+            {
+              let _batch = statistics.batch(); // 温度与湿度一起更新
+              statistics.set_temperature(temperature);
+              statistics.set_humidity(humidity);
+              statistics.sensor_updated(Sensor::TH);
+            }
+            check_alarms(&mut alarms, &statistics, temperature, humidity);
+            record_sample(&mut survey, &statistics, odometer.distance(Instant::now()), temperature, humidity);
+          } else if statistics.alarm() {
+            alarms.reset();
+            statistics.set_alarm(false);
+          }
+          true
+        },
+      ));
+
+      // 唤醒各功能线程
+      scheduler.register(Task::new(
+        "唤醒",
+        Duration::from_millis(config::WAKE_PERIOD_MS),
+        0,
+        Duration::from_millis(1),
+        || {
+          // 寻迹模块
+          // if statistics.trace() {}
+
+          // 雷达扫描
+          if statistics.scan() {
+            scan_thread.thread().unpark();
+          }
+
+          // 自动避障
+          if statistics.autopilot() {
+            autopilot_thread.thread().unpark();
+          }
+
+          // 任务
+          if statistics.mission_state().is_active() {
+            mission_thread.thread().unpark();
+          }
+
+          // 脚本
+          if statistics.script() {
+            script_thread.thread().unpark();
+          }
+
+          // 回放
+          if statistics.macro_state() == Some(MacroState::Playing) {
+            macro_thread.thread().unpark();
+          }
+
+          // 蜂鸣器
+          if statistics.sound() != Sound::Stop {
+            buzzer_thread.thread().unpark();
+          }
+          true
+        },
+      ));
+
+      while !should_shutdown.load(Ordering::Acquire) {
+        let next = scheduler.tick(Instant::now());
+        thread::sleep(next.saturating_duration_since(Instant::now()));
+      }
+      drop(scheduler);
+
+      scan_thread.join().unwrap();
+      autopilot_thread.join().unwrap();
+      mission_thread.join().unwrap();
+      script_thread.join().unwrap();
+      macro_thread.join().unwrap();
+      buzzer_thread.join().unwrap();
+    })
+  }
+}
+
+impl Context {
+  /// 雷达扫描线程: 舵机在 [scan_from, scan_to] 之间来回转动, 每转完一趟发布一次扫描结果
+  pub fn start_scan_thread(&mut self) -> JoinHandle<()> {
+    let should_shutdown = Arc::clone(&self.should_shutdown);
+    let statistics = Arc::clone(&self.statistics);
+    #[cfg(feature = "rasp")]
+    let driver = Arc::clone(&self.drivers);
+
+    thread::spawn(move || {
+      while !should_shutdown.load(Ordering::Acquire) {
+        thread::park();
+
+        let origin = statistics.servos(); // 扫描前的角度
+        let mut reverse = false;
+        while statistics.scan() {
+          let sweep = Sweep::new(statistics.scan_from(), statistics.scan_to(), statistics.scan_step());
+          let angles: Vec<u8> = if reverse { sweep.angles().rev().collect() } else { sweep.angles().collect() };
+
+          let mut distances = Vec::with_capacity(angles.len());
+          for angle in angles {
+            if !statistics.scan() {
+              break;
+            }
+
+            if !rotate_servos(
+              #[cfg(feature = "rasp")]
+              &driver,
+              &statistics,
+              angle,
+            ) {
+              distances.push(None);
+              continue;
+            }
+            thread::sleep(Duration::from_millis(config::SCAN_SETTLE_MS));
+
+            distances.push(measure_distance(
+              #[cfg(feature = "rasp")]
+              &driver,
+              &statistics,
+            ));
+          }
+
+          // 中途关闭的扫描不发布
+          if distances.len() == sweep.angles().count() {
+            if reverse {
+              distances.reverse();
+            }
+            statistics.publish_scan(sweep.to_scan(distances));
+          }
+          reverse = !reverse;
+        }
+
+        rotate_servos(
+          #[cfg(feature = "rasp")]
+          &driver,
+          &statistics,
+          origin,
+        );
+      }
+    })
+  }
+
+  /// 自动避障线程
+  pub fn start_autopilot_thread(&mut self) -> JoinHandle<()> {
+    let should_shutdown = Arc::clone(&self.should_shutdown);
+    let statistics = Arc::clone(&self.statistics);
+    #[cfg(feature = "rasp")]
+    let driver = Arc::clone(&self.drivers);
+
+    thread::spawn(move || {
+      let mut autopilot = Autopilot::default();
+      let rotate = |angle| {
+        rotate_servos(
+          #[cfg(feature = "rasp")]
+          &driver,
+          &statistics,
+          angle,
+        )
+      };
+      let measure = || {
+        measure_distance(
+          #[cfg(feature = "rasp")]
+          &driver,
+          &statistics,
+        )
+      };
+      let navigate = |navigate| {
+        let speed = match statistics.speed() {
+          0 => config::AUTOPILOT_DEFAULT_SPEED,
+          speed => speed,
+        };
+        #[cfg(feature = "rasp")]
+        driver.montor.lock().unwrap().navigate(navigate, speed);
+        #[cfg(not(feature = "rasp"))]
+        debug!("自动避障: {:?} {}", navigate, speed);
+      };
+
+      while !should_shutdown.load(Ordering::Acquire) {
+        thread::park();
+
+        autopilot.reset();
+        let mut last = None;
+        rotate(90);
+        while statistics.autopilot() {
+          let state = autopilot.state();
+          statistics.set_autopilot_state(state);
+
+          let distance = match state {
+            AutopilotState::Forward => {
+              if last != Some(state) {
+                navigate(Navigate::Forward);
+              }
+              thread::sleep(Duration::from_millis(50));
+              measure()
+            }
+            AutopilotState::Stop => {
+              navigate(Navigate::Brake);
+              thread::sleep(Duration::from_millis(config::AUTOPILOT_STOP_MS));
+              None
+            }
+            AutopilotState::ScanLeft | AutopilotState::ScanRight => {
+              rotate(if state == AutopilotState::ScanLeft { 180 } else { 0 });
+              thread::sleep(Duration::from_millis(config::AUTOPILOT_SETTLE_MS));
+              let distance = measure();
+              rotate(90);
+              distance
+            }
+            AutopilotState::TurnLeft | AutopilotState::TurnRight | AutopilotState::BackWard => {
+              navigate(match state {
+                AutopilotState::TurnLeft => Navigate::Left,
+                AutopilotState::TurnRight => Navigate::Right,
+                _ => Navigate::BackWard,
+              });
+              let duration = if state == AutopilotState::BackWard {
+                config::AUTOPILOT_BACKWARD_MS
+              } else {
+                config::AUTOPILOT_TURN_MS
+              };
+              thread::sleep(Duration::from_millis(duration));
+              navigate(Navigate::Brake);
+              None
+            }
+          };
+
+          last = Some(state);
+          autopilot.next(distance);
+        }
+
+        navigate(Navigate::Brake);
+      }
+    })
+  }
+}
+
+impl Context {
+  /// 任务线程: 依次执行任务的步骤, 暂停时刹车; 中止或上传了新任务时结束当前任务
+  pub fn start_mission_thread(&mut self) -> JoinHandle<()> {
+    let should_shutdown = Arc::clone(&self.should_shutdown);
+    let statistics = Arc::clone(&self.statistics);
+    #[cfg(feature = "rasp")]
+    let driver = Arc::clone(&self.drivers);
+
+    thread::spawn(move || {
+      let tick = Duration::from_millis(config::MISSION_TICK_MS);
+      let navigate = |navigate, speed| {
+        #[cfg(feature = "rasp")]
+        driver.montor.lock().unwrap().navigate(navigate, speed);
+        #[cfg(not(feature = "rasp"))]
+        debug!("任务: {:?} {}", navigate, speed);
+      };
+      let measure = || {
+        measure_distance(
+          #[cfg(feature = "rasp")]
+          &driver,
+          &statistics,
+        )
+      };
+      let running = || statistics.mission_state() == MissionState::Running;
+
+      while !should_shutdown.load(Ordering::Acquire) {
+        thread::park();
+
+        let version = statistics.mission_version();
+        let steps = statistics.mission_steps();
+        // 是否仍在执行同一个任务
+        let current = || statistics.mission_version() == version && statistics.mission_state().is_active();
+
+        for (index, &step) in steps.iter().enumerate() {
+          // 暂停时不开始下一步
+          while current() && !running() {
+            thread::sleep(tick);
+          }
+          if !current() {
+            break;
+          }
+
+          statistics.set_mission_step(index as u8);
+          debug!("任务: {} {:?}", index, step);
+          match Action::from(step) {
+            Action::Drive { navigate: direction, speed, duration } => {
+              let mut timer = Timer::new(duration);
+              let mut moving = false;
+              while current() {
+                let running = running();
+                if running != moving {
+                  navigate(if running { direction } else { Navigate::Brake }, speed);
+                  moving = running;
+                }
+                thread::sleep(tick);
+                if running && timer.tick(tick) {
+                  break;
+                }
+              }
+              navigate(Navigate::Brake, speed);
+            }
+            Action::Wait(duration) => {
+              let mut timer = Timer::new(duration);
+              while current() {
+                let running = running();
+                thread::sleep(tick);
+                if running && timer.tick(tick) {
+                  break;
+                }
+              }
+            }
+            Action::WaitDistance(cm) => {
+              while current() && !(running() && measure().is_some_and(|distance| distance <= cm)) {
+                thread::sleep(tick);
+              }
+            }
+            Action::Servo(angle) => {
+              rotate_servos(
+                #[cfg(feature = "rasp")]
+                &driver,
+                &statistics,
+                angle,
+              );
+              thread::sleep(Duration::from_millis(config::SCAN_SETTLE_MS));
+            }
+            Action::Beep => {
+              statistics.set_sound(Sound::Beep);
+            }
+            Action::Measure => {
+              let distance = measure();
+              statistics.publish_measurement(Measurement {
+                step: index as u8,
+                distance: distance.map(|distance| distance as f32 / 100_f32),
+                th: statistics.th().then(|| (statistics.temperature(), statistics.humidity())),
+              });
+            }
+          }
+        }
+
+        if current() {
+          statistics.set_mission_step(steps.len() as u8);
+          statistics.set_mission_state(MissionState::Done);
+          info!("任务完成");
+        }
+      }
+    })
+  }
+}
+
+impl Context {
+  /// 脚本线程: 运行脚本的顶层代码, 之后按时执行周期回调; 停止或重新启动时结束当前脚本
+  pub fn start_script_thread(&mut self) -> JoinHandle<()> {
+    use std::time::Instant;
+
+    let should_shutdown = Arc::clone(&self.should_shutdown);
+    let statistics = Arc::clone(&self.statistics);
+    let host = Arc::new(ScriptHost {
+      #[cfg(feature = "rasp")]
+      driver: Arc::clone(&self.drivers),
+      statistics: Arc::clone(&self.statistics),
+      moving: AtomicBool::new(false),
+    });
+
+    thread::spawn(move || {
+      while !should_shutdown.load(Ordering::Acquire) {
+        thread::park();
+        if !statistics.script() {
+          continue;
+        }
+
+        let version = statistics.script_version();
+        let running = {
+          let statistics = Arc::clone(&statistics);
+          move || statistics.script() && statistics.script_version() == version
+        };
+
+        statistics.log("脚本启动".to_string());
+        let result =
+          Script::start(&statistics.script_source(), Arc::clone(&host) as _, Arc::clone(&statistics), running.clone())
+            .and_then(|mut script| {
+              while running() && script.tick(Instant::now())? {
+                thread::sleep(Duration::from_millis(config::SCRIPT_TICK_MS));
+              }
+              Ok(())
+            });
+
+        // 被停止时中断的错误不需要报告
+        match result {
+          Err(err) if running() => statistics.log(format!("脚本错误: {}", err)),
+          _ => statistics.log("脚本结束".to_string()),
+        }
+        if statistics.script_version() == version {
+          statistics.set_script(false);
+        }
+        // 脚本结束时电机还在转才刹车, 不影响之后手动或其它模式的导航
+        if host.moving.load(Ordering::SeqCst) {
+          script::Host::navigate(host.as_ref(), Navigate::Brake, 0);
+        }
+      }
+    })
+  }
+}
+
+/// 脚本控制的硬件
+struct ScriptHost {
+  #[cfg(feature = "rasp")]
+  driver: Arc<Drivers>,
+  statistics: Arc<Statistics>,
+  moving: AtomicBool, // 脚本最后一次导航后电机是否在转
+}
+
+impl script::Host for ScriptHost {
+  fn navigate(&self, navigate: Navigate, speed: u8) {
+    self.moving.store(navigate != Navigate::Brake && speed > 0, Ordering::SeqCst);
+    #[cfg(feature = "rasp")]
+    self.driver.montor.lock().unwrap().navigate(navigate, speed);
+    #[cfg(not(feature = "rasp"))]
+    debug!("脚本: {:?} {}", navigate, speed);
+  }
+
+  fn drive(&self, left: f64, right: f64) {
+    self.moving.store(left != 0_f64 || right != 0_f64, Ordering::SeqCst);
+    #[cfg(feature = "rasp")]
+    self.driver.montor.lock().unwrap().drive(left, right);
+    #[cfg(not(feature = "rasp"))]
+    debug!("脚本: ({:.2}, {:.2})", left, right);
+  }
+
+  fn servo(&self, angle: u8) {
+    rotate_servos(
+      #[cfg(feature = "rasp")]
+      &self.driver,
+      &self.statistics,
+      angle,
+    );
+  }
+}
+
+impl Context {
+  /// 回放线程: 按录制的时间依次执行命令, 前进时遇到障碍物中止回放
+  pub fn start_macro_thread(&mut self) -> JoinHandle<()> {
+    use std::time::Instant;
+
+    let should_shutdown = Arc::clone(&self.should_shutdown);
+    let statistics = Arc::clone(&self.statistics);
+    let macros = Arc::clone(&self.macros);
+    #[cfg(feature = "rasp")]
+    let driver = Arc::clone(&self.drivers);
+
     thread::spawn(move || {
-      //
+      let tick = Duration::from_millis(config::MACRO_TICK_MS);
+      let navigate = |navigate, speed| {
+        statistics.set_speed(speed);
+        #[cfg(feature = "rasp")]
+        driver.montor.lock().unwrap().navigate(navigate, speed);
+        #[cfg(not(feature = "rasp"))]
+        debug!("回放: {:?} {}", navigate, speed);
+      };
+      // 舵机朝前时前方是否有障碍物
+      let obstacle = || {
+        statistics.servos() == 90
+          && measure_distance(
+            #[cfg(feature = "rasp")]
+            &driver,
+            &statistics,
+          )
+          .is_some_and(|distance| distance <= config::MIN_DISTANCE)
+      };
       while !should_shutdown.load(Ordering::Acquire) {
-        use rand::{thread_rng, Rng};
-        let mut thread_rng = thread_rng();
+        thread::park();
+
+        let Some(Playback { generation, name, scale }) = macros.take_pending() else {
+          continue;
+        };
+        // 被停止或有新的回放请求时结束
+        let playing = || statistics.macro_state() == Some(MacroState::Playing) && macros.is_current(generation);
+        let entries = match macros.load(&name) {
+          Ok(entries) => entries,
+          Err(err) => {
+            log::warn!("回放 {}: {}", name, err);
+            statistics.set_macro_state(None);
+            continue;
+          }
+        };
+
+        info!("回放: {} {}%", name, scale);
+        let scale = scale.clamp(config::MACRO_MIN_SCALE, config::MACRO_MAX_SCALE) as f64 / 100_f64;
+        let start = Instant::now();
+        let mut forward = false; // 是否正在前进
+        'playback: for entry in entries {
+          let (at, command) = entry.scaled(scale);
+          while start.elapsed() < at {
+            if !playing() {
+              break 'playback;
+            }
+            if forward && obstacle() {
+              info!("回放: 遇到障碍物, 中止");
+              statistics.set_sound(Sound::Obstacle);
+              statistics.set_macro_state(None);
+              break 'playback;
+            }
+            thread::sleep(tick);
+          }
+          if !playing() {
+            break;
+          }
+
+          match command {
+            Command::Navigate { navigate: direction, speed } => {
+              navigate(direction, speed);
+              forward = direction == Navigate::Forward;
+            }
+            Command::Servos { angle } => {
+              rotate_servos(
+                #[cfg(feature = "rasp")]
+                &driver,
+                &statistics,
+                angle,
+              );
+            }
+            Command::Buzzer { sound } => statistics.set_sound(sound),
+            _ => {}
+          }
+        }
 
-        if statistics.th() {
-          statistics.set_temperature(thread_rng.gen_range(20_f32..=30_f32));
-          statistics.set_humidity(thread_rng.gen_range(40_f32..=60_f32));
+        navigate(Navigate::Brake, statistics.speed());
+        if playing() {
+          statistics.set_macro_state(None);
         }
+      }
+    })
+  }
+}
 
-        // 寻迹模块
-        // if statistics.trace() {}
+impl Context {
+  /// 蜂鸣器线程: 播放 statistics.sound(), 有新的声音时打断当前的播放
+  pub fn start_buzzer_thread(&mut self) -> JoinHandle<()> {
+    let should_shutdown = Arc::clone(&self.should_shutdown);
+    let statistics = Arc::clone(&self.statistics);
+
+    #[cfg(feature = "rasp")]
+    let mut buzzer = Buzzer::new(&Gpio::new().unwrap());
+
+    thread::spawn(move || {
+      while !should_shutdown.load(Ordering::Acquire) {
+        thread::park();
 
-        thread::sleep(Duration::from_millis(50)); //
+        let (sound, request) = statistics.sound_request();
+        let tones = match sound {
+          Sound::Stop => continue,
+          Sound::Custom => melody::parse(&statistics.melody()),
+          sound => melody::parse(melody::builtin(sound)),
+        };
+        let tones = tones.inspect_err(|err| debug!("乐谱: {:?}", err)).unwrap_or_default();
+
+        for tone in tones {
+          if statistics.sound_request().1 != request {
+            break; // 被新的声音或重新播放打断
+          }
+
+          let gap = Duration::from_millis(config::BUZZER_GAP_MS).min(tone.duration);
+          #[cfg(feature = "rasp")]
+          match tone.frequency {
+            Some(frequency) => buzzer.tone(frequency),
+            None => buzzer.off(),
+          }
+          #[cfg(not(feature = "rasp"))]
+          debug!("蜂鸣器: {:?}", tone);
+
+          thread::sleep(tone.duration - gap);
+          #[cfg(feature = "rasp")]
+          buzzer.off();
+          thread::sleep(gap);
+        }
+
+        statistics.finish_sound(request);
       }
     })
   }
 }
 
+/// 转动舵机, 并同步到统计信息
+fn rotate_servos(#[cfg(feature = "rasp")] driver: &Drivers, statistics: &Statistics, angle: u8) -> bool {
+  #[cfg(feature = "rasp")]
+  if !driver.servos.lock().unwrap().rotate(angle) {
+    return false;
+  }
+
+  statistics.set_servos(angle);
+  true
+}
+
+/// 以新的温湿度读数检查报警规则, 记录并推送报警事件, 开始报警时播放规则的声音
+fn check_alarms(alarms: &mut Alarms, statistics: &Statistics, temperature: f32, humidity: f32) {
+  use std::time::Instant;
+
+  let time = clock::timestamp(statistics);
+  for change in alarms.update(Instant::now(), temperature, humidity) {
+    let rule = change.rule;
+    if change.active {
+      log::warn!("报警: {} ({:?} {})", rule.name, rule.metric, change.value);
+      statistics.set_sound(rule.sound);
+    } else {
+      info!("解除报警: {} ({:?} {})", rule.name, rule.metric, change.value);
+    }
+    statistics.publish_alarm(Alarm {
+      time,
+      name: rule.name.clone(),
+      metric: rule.metric,
+      value: change.value,
+      active: change.active,
+    });
+  }
+  statistics.set_alarm(alarms.active());
+}
+
+/// 巡测中时记录温湿度样本, 开始新的巡测时以当前里程为起点
+fn record_sample(survey: &mut Option<Survey>, statistics: &Statistics, distance: f64, temperature: f32, humidity: f32) {
+  use std::time::Instant;
+
+  if !statistics.survey() {
+    *survey = None;
+    return;
+  }
+
+  let now = Instant::now();
+  let version = statistics.survey_version();
+  let survey = match survey {
+    Some(survey) if survey.version() == version => survey,
+    _ => survey.insert(Survey::new(version, now, distance)),
+  };
+  if let Some(sample) = survey.sample(now, distance, temperature, humidity) {
+    statistics.publish_sample(sample);
+  }
+}
+
+/// 测量舵机当前方向上的障碍物距离 cm
+fn measure_distance(#[cfg(feature = "rasp")] driver: &Drivers, statistics: &Statistics) -> Option<u16> {
+  #[cfg(feature = "rasp")]
+  {
+    driver
+      .ultrasonic
+      .lock()
+      .unwrap()
+      .measure(statistics.th().then_some(statistics.temperature()), config::ULTRASONIC_SAMPLES)
+      .inspect_err(|err| debug!("超声波: {:?}", err))
+      .ok()
+  }
+
+  #[cfg(not(feature = "rasp"))]
+  {
+    use rand::{thread_rng, Rng};
+
+    let _ = statistics;
+    let mut thread_rng = thread_rng();
+    thread_rng.gen_bool(0.9).then(|| thread_rng.gen_range(5..=400))
+  }
+}
+
 #[cfg(feature = "rasp")]
 impl Context {
-  /// 寻迹线程
+  /// 寻迹线程, 开启推流时即使没有寻迹也会采集画面
   pub fn start_trace_thread(&mut self) -> JoinHandle<()> {
-    let mut cap = VideoCapture::new(config::CAMERA_INDEX, CAP_ANY).unwrap();
-    if !cap.is_opened().unwrap() {
-      panic!("Failed to open camera");
-    }
+    use std::time::Instant;
 
     let should_shutdown = Arc::clone(&self.should_shutdown);
     let statistics = Arc::clone(&self.statistics);
+    let settings = Arc::clone(&self.settings);
     let driver = Arc::clone(&self.drivers);
+    let frames = Arc::clone(&self.frames);
 
     thread::spawn(move || {
+      // 摄像头不可用时不退出线程, 下次开启寻迹或推流时重新打开
+      let open = |statistics: &Statistics| {
+        let source = camera::open(&settings.camera).inspect_err(|err| log::warn!("摄像头: {}", err)).ok();
+        statistics.set_camera(source.is_some());
+        source
+      };
+      let mut source = open(&statistics);
+      let mut lost = false; // 是否已丢线
+      let gains = |statistics: &Statistics| {
+        let (kp, ki, kd) = statistics.trace_pid();
+        (kp as f64, ki as f64, kd as f64)
+      };
       while !should_shutdown.load(Ordering::Acquire) {
         thread::park();
+        if !statistics.trace() && !statistics.stream() {
+          continue;
+        }
+
+        if source.is_none() {
+          source = open(&statistics);
+        }
+        if source.is_none() {
+          let _batch = statistics.batch();
+          statistics.set_trace(false);
+          statistics.set_stream(false);
+          continue;
+        }
+
+        let mut algorithm = statistics.trace_algorithm();
+        let mut follower = trace::follower(algorithm, &settings);
+        let mut steering = Steering::new(gains(&statistics));
+        let detector = trace::MarkerDetector::new(settings.markers.clone());
+        let mut signals = Signals::default();
+        let mut last = Instant::now();
+        let mut last_frame: Option<Instant> = None; // 上一次推流的时间
+        let mut tracing = false; // 上一帧是否在寻迹
+        while statistics.trace() || statistics.stream() {
+          // 推流时关闭寻迹: 停车, 否则电机保持最后一次的速度
+          if tracing && !statistics.trace() {
+            driver.montor.lock().unwrap().navigate(Navigate::Brake, statistics.speed());
+          }
+          tracing = statistics.trace();
+
+          // 切换寻迹算法
+          if statistics.trace_algorithm() != algorithm {
+            algorithm = statistics.trace_algorithm();
+            follower = trace::follower(algorithm, &settings);
+          }
 
-        while statistics.trace() {
           // 寻迹模块
-          let mut frame = Mat::default();
-          cap.read(&mut frame).unwrap();
-          if frame.empty() {
+          let frame = source.as_mut().and_then(|cap| cap.read().inspect_err(|err| log::warn!("摄像头: {}", err)).ok());
+          let Some(mut frame) = frame.flatten() else {
+            source = None;
+            let _batch = statistics.batch();
+            statistics.set_camera(false);
+            statistics.set_trace(false);
+            statistics.set_stream(false);
             break;
+          };
+
+          // 推流且不显示识别结果时保留原始画面
+          let overlay = statistics.stream_overlay();
+          let stream = statistics.stream()
+            && frames.has_viewers()
+            && last_frame.is_none_or(|t| t.elapsed() >= Duration::from_millis(config::STREAM_INTERVAL_MS));
+          let original = (stream && !overlay.centroid).then(|| frame.clone());
+
+          // 赛道标志在画线之前识别
+          let marker = if statistics.trace() {
+            detector.detect(&frame).unwrap_or_else(|err| {
+              debug!("标志: {}", err);
+              None
+            })
+          } else {
+            None
+          };
+          let error = follower.follow(&mut frame).unwrap_or_else(|err| {
+            debug!("寻迹: {}", err);
+            None
+          });
+          if let Some(marker) = marker {
+            let _ = trace::draw_marker(&mut frame, marker);
+          }
+
+          if statistics.trace() {
+            if let Some(marker) = signals.update(marker) {
+              info!("标志: {:?}", marker);
+              statistics.set_marker(Some(marker));
+            }
+
+            steering.pid.gains = gains(&statistics); // 可以在寻迹时调整
+            let speed = statistics.speed() as f64 / 100_f64 * signals.speed_factor();
+            let (left, right) = match signals.active() {
+              // 停车标志: 原地等待通行标志
+              Some(Marker::Stop) => {
+                steering.pid.reset();
+                (0_f64, 0_f64)
+              }
+              _ => steering.update(error, speed, last.elapsed()),
+            };
+            debug!("寻迹：{:?}, ({:.2}, {:.2})", error, left, right);
+
+            // 丢线报警
+            if steering.lost() && !lost {
+              statistics.set_sound(Sound::LostLine);
+            }
+            lost = steering.lost();
+            driver.montor.lock().unwrap().drive(left, right);
+          } else if signals.active().is_some() {
+            signals = Signals::default();
+            statistics.set_marker(None);
           }
+          last = Instant::now();
 
-          let navigate = follow_line(&mut frame);
-          debug!("寻迹：{:?}", navigate);
-          // let montor = driver.montor.lock().unwrap();
-          driver.montor.lock().unwrap().navigate(navigate, statistics.speed());
-          // imgcodecs::imwrite("frame.png", &frame, &Vector::new()).unwrap();
+          if stream {
+            last_frame = Some(Instant::now());
+            let _ = publish_frame(&frames, frame, original, follower.mask(), overlay)
+              .inspect_err(|err| debug!("推流: {}", err));
+          }
         }
 
-        driver.montor.lock().unwrap().navigate(Navigate::Brake, statistics.speed());
+        statistics.set_marker(None);
+        // 只推流时不停车, 以免打断手动驾驶
+        if tracing {
+          driver.montor.lock().unwrap().navigate(Navigate::Brake, statistics.speed());
+        }
       }
     })
   }
@@ -167,29 +985,98 @@ impl Context {
     let led_thread = self.start_led_thread();
     let nixie_thread = self.start_nixie_thread();
     let trace_thread = self.start_trace_thread();
+    let stream_thread = stream::serve(
+      (config::LISTEN_ADDR, config::STREAM_PORT),
+      Arc::clone(&self.frames),
+      Arc::clone(&self.should_shutdown),
+    )
+    .inspect_err(|err| log::warn!("推流: {}", err))
+    .ok();
+    let scan_thread = self.start_scan_thread();
+    let autopilot_thread = self.start_autopilot_thread();
+    let mission_thread = self.start_mission_thread();
+    let script_thread = self.start_script_thread();
+    let macro_thread = self.start_macro_thread();
+    let buzzer_thread = self.start_buzzer_thread();
 
     let gpio = Gpio::new().unwrap();
-    let mut th = TH::new(&gpio);
-    let mut ultrasonic = Ultrasonic::new(&gpio);
+    let mut th = TH::new(&gpio, config::TH_SENSOR);
+    let mut alarms = Alarms::new(self.settings.alarms.clone());
     thread::spawn(move || {
-      //
-      while !should_shutdown.load(Ordering::Acquire) {
-        // 温湿传感器
-        if statistics.th() {
-          // debug!("温室传感器: {:?}", th.measure());
-          if let Some(m) = th.measure() {
-            // debug!("温室传感器: {:?}", m);
-            statistics.set_temperature(m.temperature.floor());
-            statistics.set_humidity(m.humidity.floor());
+      use std::time::Instant;
+
+      let mut survey = None;
+      let mut scheduler = Scheduler::default();
+
+      // 温湿传感器: DHT11 每秒最多读取一次, 失败后从最小采样间隔开始退避重试
+      let th_period = Duration::from_millis(config::TH_PERIOD_MS).max(config::TH_SENSOR.min_interval());
+      statistics.set_sensor_period(Sensor::TH, th_period);
+      let th_task = Task::new(
+        "温湿传感器",
+        th_period,
+        config::TH_PRIORITY,
+        Duration::from_millis(config::TH_TIMEOUT_MS),
+        || {
+          if !statistics.th() {
+            if statistics.alarm() {
+              alarms.reset();
+              statistics.set_alarm(false);
+            }
+            return true;
           }
-        }
 
-        // 超声波模块
-        if statistics.ultrasonic() {
-          let distance = ultrasonic.get_distance(statistics.th().then_some(statistics.temperature()));
-          if let Some(distance) = distance {
-            statistics.set_distance(distance);
+          let m = match th.measure() {
+            Ok(m) => m,
+            // 调度推迟后下一个周期可能提前到期, 跳过这一次
+            Err(dht::Error::TooSoon) => return true,
+            Err(err) => {
+              statistics.sensor_failed(Sensor::TH, err.into());
+              return false;
+            }
+          };
+          {
+            let _batch = statistics.batch(); // 温度与湿度一起更新
+            statistics.set_temperature(m.temperature);
+            statistics.set_humidity(m.humidity);
+            statistics.sensor_updated(Sensor::TH);
+          }
+
+          // 环境报警, 只在状态变化时报警一次
+          check_alarms(&mut alarms, &statistics, m.temperature, m.humidity);
+          let distance = driver.montor.lock().unwrap().distance();
+          record_sample(&mut survey, &statistics, distance, m.temperature, m.humidity);
+          true
+        },
+      );
+      scheduler.register(th_task.retry(config::TH_RETRIES, config::TH_SENSOR.min_interval()));
+
+      // 超声波模块
+      let ultrasonic_period = Duration::from_millis(config::ULTRASONIC_PERIOD_MS);
+      statistics.set_sensor_period(Sensor::Ultrasonic, ultrasonic_period);
+      scheduler.register(Task::new(
+        "超声波",
+        ultrasonic_period,
+        config::ULTRASONIC_PRIORITY,
+        Duration::from_millis(config::ULTRASONIC_TIMEOUT_MS),
+        || {
+          if !statistics.ultrasonic() {
+            return true;
           }
+
+          let distance =
+            driver.ultrasonic.lock().unwrap().get_distance(statistics.th().then_some(statistics.temperature()));
+          let ok = match distance {
+            Ok(distance) => {
+              statistics.set_distance(distance);
+              statistics.sensor_updated(Sensor::Ultrasonic);
+              true
+            }
+            Err(err) => {
+              debug!("超声波: {:?}", err);
+              statistics.sensor_failed(Sensor::Ultrasonic, err.into());
+              false
+            }
+          };
           let mut montor = driver.montor.lock().unwrap();
           if statistics.servos() == 90
             && statistics.distance() <= config::MIN_DISTANCE
@@ -197,37 +1084,101 @@ impl Context {
           {
             println!("小于该距离");
             montor.navigate(Navigate::Brake, statistics.speed());
+            statistics.set_sound(Sound::Obstacle);
           }
-        }
-        // 寻迹{}
-        if statistics.trace() {
-          trace_thread.thread().unpark();
-        }
+          ok
+        },
+      ));
 
-        // 数码管状态
-        if statistics.led() {
-          led_thread.thread().unpark();
-        }
+      // 唤醒各功能线程, 耗时很短, 优先级最低
+      scheduler.register(Task::new(
+        "唤醒",
+        Duration::from_millis(config::WAKE_PERIOD_MS),
+        0,
+        Duration::from_millis(1),
+        || {
+          // 巡测沿赛道寻迹行驶, 寻迹结束 (停车标志, 摄像头不可用或手动关闭) 时巡测完成
+          if statistics.survey() && !statistics.trace() {
+            info!("巡测完成");
+            statistics.set_survey(false);
+          }
 
-        if statistics.nixie() {
-          nixie_thread.thread().unpark();
-        }
+          // 寻迹{}
+          if statistics.trace() || statistics.stream() {
+            trace_thread.thread().unpark();
+          }
+
+          // 数码管状态
+          if statistics.led() {
+            led_thread.thread().unpark();
+          }
+
+          if statistics.nixie() {
+            nixie_thread.thread().unpark();
+          }
+
+          // 雷达扫描
+          if statistics.scan() {
+            scan_thread.thread().unpark();
+          }
+
+          // 自动避障
+          if statistics.autopilot() {
+            autopilot_thread.thread().unpark();
+          }
+
+          // 任务
+          if statistics.mission_state().is_active() {
+            mission_thread.thread().unpark();
+          }
 
-        thread::sleep(Duration::from_millis(50)); //
+          // 脚本
+          if statistics.script() {
+            script_thread.thread().unpark();
+          }
+
+          // 回放
+          if statistics.macro_state() == Some(MacroState::Playing) {
+            macro_thread.thread().unpark();
+          }
+
+          // 蜂鸣器
+          if statistics.sound() != Sound::Stop {
+            buzzer_thread.thread().unpark();
+          }
+          true
+        },
+      ));
+
+      while !should_shutdown.load(Ordering::Acquire) {
+        let next = scheduler.tick(Instant::now());
+        thread::sleep(next.saturating_duration_since(Instant::now()));
       }
+      drop(scheduler);
 
       // 等待所有线程结束
       trace_thread.join().unwrap();
       led_thread.join().unwrap();
       nixie_thread.join().unwrap();
+      scan_thread.join().unwrap();
+      autopilot_thread.join().unwrap();
+      mission_thread.join().unwrap();
+      script_thread.join().unwrap();
+      macro_thread.join().unwrap();
+      buzzer_thread.join().unwrap();
+      if let Some(stream_thread) = stream_thread {
+        stream_thread.join().unwrap();
+      }
     })
   }
 
-  /// FIX: 不够实时
-  /// 开启
+  /// LED 灯效线程: 每 LED_TICK_MS 刷新一次, 效果的改变立即生效
   pub fn start_led_thread(&mut self) -> JoinHandle<()> {
+    use std::time::Instant;
+
     let should_shutdown = Arc::clone(&self.should_shutdown);
     let statistics = Arc::clone(&self.statistics);
+    let settings = Arc::clone(&self.settings);
 
     let gpio = Gpio::new().unwrap();
     let mut rgb_led = RgbLed::new(&gpio);
@@ -235,25 +1186,27 @@ impl Context {
     thread::spawn(move || {
       while !should_shutdown.load(Ordering::Acquire) {
         thread::park();
+
+        let (mut current, mut start, mut last) = (None, Instant::now(), None);
         while statistics.led() {
-          rgb_led.red.set_high();
-          thread::sleep(Duration::from_secs(1));
-          rgb_led.green.set_high(); // RG
-          thread::sleep(Duration::from_secs(1));
-          rgb_led.green.set_low();
-          rgb_led.blue.set_high(); // RB
-          thread::sleep(Duration::from_secs(1));
-          rgb_led.red.set_low();
-          rgb_led.blue.set_low();
-          rgb_led.green.set_high(); // G
-          thread::sleep(Duration::from_secs(1));
-          rgb_led.blue.set_high(); // GB
-          thread::sleep(Duration::from_secs(1));
-          rgb_led.green.set_low(); // B
-          thread::sleep(Duration::from_secs(1));
-          rgb_led.red.set_high();
-          rgb_led.blue.set_high();
-          thread::sleep(Duration::from_secs(1));
+          // 状态指示由规则决定实际的效果与颜色
+          let (effect, color) = match statistics.led_effect() {
+            LedEffect::Status => led::status(&settings.led_status, &statistics),
+            effect => (effect, statistics.led_color()),
+          };
+
+          // 切换效果后从头开始
+          if current != Some((statistics.led_effect(), effect)) {
+            (current, start) = (Some((statistics.led_effect(), effect)), Instant::now());
+          }
+
+          let duty = led::render(effect, color, statistics.led_brightness(), start.elapsed());
+          if last != Some(duty) {
+            rgb_led.set(duty);
+            last = Some(duty);
+          }
+
+          thread::sleep(Duration::from_millis(config::LED_TICK_MS));
         }
 
         rgb_led.off();
@@ -262,21 +1215,37 @@ impl Context {
   }
 
   pub fn start_nixie_thread(&mut self) -> JoinHandle<()> {
+    use std::time::Instant;
+
     let should_shutdown = Arc::clone(&self.should_shutdown);
     let statistics = Arc::clone(&self.statistics);
+    let clock = Arc::clone(&self.clock);
 
     let gpio = Gpio::new().unwrap();
     let mut nixie = Nixie::new(&gpio);
     thread::spawn(move || {
-      //
       while !should_shutdown.load(Ordering::Acquire) {
         thread::park();
+
+        let mut last = None;
+        let mut current = None; // 当前的显示模式与开始显示的时间
         while statistics.nixie() {
-          let now = get_local_time();
-          nixie.display_time(&now, true, statistics.nixie_brightness());
-          thread::sleep(Duration::from_millis(500));
-          nixie.display_time(&now, false, statistics.nixie_brightness());
-          thread::sleep(Duration::from_millis(500));
+          let mode = statistics.nixie_mode();
+          let start = match current {
+            Some((current, start)) if current == mode => start,
+            _ => Instant::now(),
+          };
+          current = Some((mode, start));
+
+          let now = clock.now(&statistics);
+          let segments = segment::render(mode, &statistics, (now.hour(), now.minute()), start.elapsed());
+          let brightness = statistics.nixie_brightness();
+          if last != Some((segments, brightness)) {
+            nixie.display_segments(&segments, brightness);
+            last = Some((segments, brightness));
+          }
+
+          thread::sleep(Duration::from_millis(config::NIXIE_TICK_MS));
         }
       }
 
@@ -285,7 +1254,32 @@ impl Context {
   }
 }
 
-/// FIX: OffsetDateTime::now_local()
-fn get_local_time() -> Time {
-  (OffsetDateTime::now_utc() + Duration::from_secs(8 * 60 * 60)).time()
+/// 按叠加层合成画面, 编码为 JPEG 后发布
+/// original: 不显示识别结果时保留的原始画面
+#[cfg(feature = "rasp")]
+fn publish_frame(
+  frames: &Frames,
+  frame: Mat,
+  original: Option<Mat>,
+  mask: &Mat,
+  overlay: StreamOverlay,
+) -> opencv::Result<()> {
+  let mut image = match original {
+    _ if overlay.mask && !mask.empty() => {
+      let mut image = Mat::default();
+      imgproc::cvt_color(mask, &mut image, imgproc::COLOR_GRAY2BGR, 0)?;
+      image
+    }
+    Some(original) => original,
+    None => frame,
+  };
+  if overlay.roi {
+    trace::draw_roi(&mut image)?;
+  }
+
+  let mut jpeg = Vector::<u8>::new();
+  let params = Vector::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, config::STREAM_JPEG_QUALITY]);
+  imgcodecs::imencode(".jpg", &image, &mut jpeg, &params)?;
+  frames.publish(jpeg.to_vec());
+  Ok(())
 }
@@ -0,0 +1,207 @@
+//! DHT11/DHT22 温湿度传感器的数据解码
+
+#[cfg(any(feature = "rasp", test))]
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use car_utils::SensorError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+  NoResponse,       // 传感器没有响应起始信号
+  Timeout,          // 读取数据位超时
+  Incomplete,       // 数据位不足 40 位
+  ChecksumMismatch, // 校验和错误
+  OutOfRange,       // 读数超出量程, 通常是干扰导致校验和碰巧正确
+  TooSoon,          // 距上一次读取不足 SensorType::min_interval, 没有读取
+}
+
+impl From<Error> for SensorError {
+  fn from(err: Error) -> Self {
+    match err {
+      Error::NoResponse => SensorError::NoResponse,
+      // 过早读取由调用者跳过, 不作为传感器错误上报
+      Error::Timeout | Error::Incomplete | Error::TooSoon => SensorError::Timeout,
+      Error::ChecksumMismatch => SensorError::Checksum,
+      Error::OutOfRange => SensorError::OutOfRange,
+    }
+  }
+}
+
+/// 传感器型号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorType {
+  #[cfg(any(not(feature = "dht22"), test))]
+  Dht11,
+  #[cfg(any(feature = "dht22", test))]
+  Dht22,
+}
+
+impl SensorType {
+  /// 两次采样之间的最小间隔
+  pub fn min_interval(&self) -> Duration {
+    match self {
+      #[cfg(any(not(feature = "dht22"), test))]
+      SensorType::Dht11 => Duration::from_secs(1),
+      #[cfg(any(feature = "dht22", test))]
+      SensorType::Dht22 => Duration::from_secs(2),
+    }
+  }
+
+  /// 量程: 温度 °C, 湿度 %
+  #[cfg(any(feature = "rasp", test))]
+  pub fn range(&self) -> (RangeInclusive<f32>, RangeInclusive<f32>) {
+    match self {
+      #[cfg(any(not(feature = "dht22"), test))]
+      SensorType::Dht11 => (0_f32..=50_f32, 5_f32..=95_f32),
+      #[cfg(any(feature = "dht22", test))]
+      SensorType::Dht22 => (-40_f32..=80_f32, 0_f32..=100_f32),
+    }
+  }
+
+  /// 起始信号拉低的时间
+  #[cfg(feature = "rasp")]
+  pub fn start_signal(&self) -> Duration {
+    match self {
+      #[cfg(any(not(feature = "dht22"), test))]
+      SensorType::Dht11 => Duration::from_millis(18),
+      #[cfg(any(feature = "dht22", test))]
+      SensorType::Dht22 => Duration::from_micros(1100),
+    }
+  }
+}
+
+#[cfg(any(feature = "rasp", test))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+  pub temperature: f32,
+  pub humidity: f32,
+}
+
+/// 读取的错误统计
+#[cfg(any(feature = "rasp", test))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ErrorStatistics {
+  pub reads: u32, // 读取次数
+  pub no_response: u32,
+  pub timeout: u32,
+  pub checksum: u32,
+  pub out_of_range: u32,
+}
+
+#[cfg(any(feature = "rasp", test))]
+impl ErrorStatistics {
+  /// 记录一次读取, 过早而没有读取的不计入
+  pub fn record<T>(&mut self, result: &Result<T, Error>) {
+    if matches!(result, Err(Error::TooSoon)) {
+      return;
+    }
+    self.reads += 1;
+    match result {
+      Ok(_) => {}
+      Err(Error::NoResponse) => self.no_response += 1,
+      Err(Error::Timeout | Error::Incomplete) => self.timeout += 1,
+      Err(Error::ChecksumMismatch) => self.checksum += 1,
+      Err(Error::OutOfRange) => self.out_of_range += 1,
+      Err(Error::TooSoon) => {}
+    }
+  }
+
+  /// 失败次数
+  pub fn failures(&self) -> u32 {
+    self.no_response + self.timeout + self.checksum + self.out_of_range
+  }
+}
+
+/// 高电平持续时间超过该值的数据位为 1 (0: 26-28us, 1: 70us)
+#[cfg(any(feature = "rasp", test))]
+pub const BIT_THRESHOLD: Duration = Duration::from_micros(50);
+
+/// 将 40 个数据位的高电平持续时间解码为 5 个字节
+#[cfg(any(feature = "rasp", test))]
+pub fn decode_pulses(pulses: &[Duration]) -> Result<[u8; 5], Error> {
+  if pulses.len() < 40 {
+    return Err(Error::Incomplete);
+  }
+
+  let mut bytes = [0; 5];
+  for (i, pulse) in pulses.iter().take(40).enumerate() {
+    bytes[i / 8] = (bytes[i / 8] << 1) | (*pulse > BIT_THRESHOLD) as u8;
+  }
+  Ok(bytes)
+}
+
+/// 校验并解析 5 个字节的数据
+#[cfg(any(feature = "rasp", test))]
+pub fn decode(sensor: SensorType, bytes: [u8; 5]) -> Result<Measurement, Error> {
+  let [b0, b1, b2, b3, checksum] = bytes;
+  if [b0, b1, b2, b3].iter().fold(0u8, |sum, v| sum.wrapping_add(*v)) != checksum {
+    return Err(Error::ChecksumMismatch);
+  }
+
+  let (temperature, humidity, negative) = match sensor {
+    // 整数部分, 小数部分 (0.1); 部分 DHT11 使用温度小数部分的最高位作为符号位
+    #[cfg(any(not(feature = "dht22"), test))]
+    SensorType::Dht11 => (b2 as f32 + (b3 & 0x7f) as f32 / 10_f32, b0 as f32 + b1 as f32 / 10_f32, b3 & 0x80 != 0),
+    // 16 位, 单位 0.1; 温度的最高位为符号位
+    #[cfg(any(feature = "dht22", test))]
+    SensorType::Dht22 => (
+      u16::from_be_bytes([b2 & 0x7f, b3]) as f32 / 10_f32,
+      u16::from_be_bytes([b0, b1]) as f32 / 10_f32,
+      b2 & 0x80 != 0,
+    ),
+  };
+  let temperature = if negative { -temperature } else { temperature };
+
+  let (temperature_range, humidity_range) = sensor.range();
+  if !temperature_range.contains(&temperature) || !humidity_range.contains(&humidity) {
+    return Err(Error::OutOfRange);
+  }
+  Ok(Measurement { temperature, humidity })
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use super::{decode, decode_pulses, Error, ErrorStatistics, Measurement, SensorType};
+
+  /// 根据字节生成脉冲, 带有调度带来的抖动
+  fn pulses(bytes: [u8; 5]) -> Vec<Duration> {
+    (0..40)
+      .map(|i| {
+        let bit = bytes[i / 8] >> (7 - i % 8) & 1;
+        let jitter = (i as u64 * 7) % 15;
+        Duration::from_micros(if bit == 1 { 65 + jitter } else { 22 + jitter })
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_dht11() {
+    let bytes = decode_pulses(&pulses([45, 0, 23, 4, 72])).unwrap();
+    assert_eq!(bytes, [45, 0, 23, 4, 72]);
+    assert_eq!(decode(SensorType::Dht11, bytes), Ok(Measurement { temperature: 23.4, humidity: 45.0 }));
+  }
+
+  #[test]
+  fn test_dht22() {
+    // 湿度 65.2%, 温度 -10.1°C
+    let bytes = [0x02, 0x8c, 0x80, 0x65, 0x73];
+    assert_eq!(decode_pulses(&pulses(bytes)), Ok(bytes));
+    assert_eq!(decode(SensorType::Dht22, bytes), Ok(Measurement { temperature: -10.1, humidity: 65.2 }));
+  }
+
+  #[test]
+  fn test_error() {
+    assert_eq!(decode_pulses(&pulses([0; 5])[..39]), Err(Error::Incomplete));
+    assert_eq!(decode(SensorType::Dht11, [45, 0, 23, 4, 0]), Err(Error::ChecksumMismatch));
+    // 湿度 120%
+    assert_eq!(decode(SensorType::Dht11, [120, 0, 23, 4, 147]), Err(Error::OutOfRange));
+
+    let mut errors = ErrorStatistics::default();
+    errors.record(&decode(SensorType::Dht11, [45, 0, 23, 4, 0]));
+    errors.record::<Measurement>(&Err(Error::TooSoon));
+    assert_eq!((errors.reads, errors.failures()), (1, 1));
+  }
+}
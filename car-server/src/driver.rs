@@ -1,4 +1,4 @@
-// mod buzzer;
+mod buzzer;
 mod montor;
 mod nixie;
 mod rgb_led;
@@ -7,6 +7,7 @@ mod th;
 // mod trace;
 mod ultrasonic;
 
+pub use buzzer::Buzzer;
 pub use montor::Montor;
 pub use nixie::Nixie;
 pub use rgb_led::RgbLed;
@@ -24,7 +25,7 @@ pub struct Drivers {
   // pub nixie: Mutex<Nixie>,
   pub servos: Mutex<Servos>,
   // pub th: Mutex<TH>,
-  // pub ultrasonic: Mutex<Ultrasonic>,
+  pub ultrasonic: Mutex<Ultrasonic>,
 }
 
 impl Drivers {
@@ -35,7 +36,7 @@ impl Drivers {
       // nixie: Mutex::new(Nixie::new(gpio)),
       servos: Mutex::new(Servos::new(gpio)),
       // th: Mutex::new(TH::new(gpio)),
-      // ultrasonic: Mutex::new(Ultrasonic::new(gpio)),
+      ultrasonic: Mutex::new(Ultrasonic::new(gpio)),
     }
   }
 }
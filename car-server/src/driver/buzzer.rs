@@ -0,0 +1,30 @@
+//! 无源蜂鸣器: PWM 输出不同频率的方波发声
+
+use log::debug;
+use rppal::gpio::{Gpio, OutputPin};
+
+use crate::config;
+
+/// 蜂鸣器
+pub struct Buzzer {
+  ctl: OutputPin,
+}
+
+impl Buzzer {
+  pub const DUTY_CYCLE: f64 = 0.5;
+
+  pub fn new(gpio: &Gpio) -> Self {
+    Self { ctl: gpio.get(config::PIN_BUZZER_CTL).unwrap().into_output_low() }
+  }
+
+  /// 以 frequency Hz 发声
+  pub fn tone(&mut self, frequency: f64) {
+    let _ = self.ctl.set_pwm_frequency(frequency, Self::DUTY_CYCLE).inspect_err(|e| debug!("buzzer: {}", e));
+  }
+
+  /// 停止发声
+  pub fn off(&mut self) {
+    let _ = self.ctl.clear_pwm();
+    self.ctl.set_low();
+  }
+}
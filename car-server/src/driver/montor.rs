@@ -1,9 +1,11 @@
 //! L298n 驱动板: 驱动电机转动速度
 
+use std::time::Instant;
+
 use car_utils::command::Navigate;
 use rppal::gpio::{Gpio, OutputPin};
 
-use crate::config;
+use crate::{config, odometer::Odometer};
 
 /// INT2, INT4 == 1; 前进
 pub struct Montor {
@@ -14,6 +16,7 @@ pub struct Montor {
 
   pub navigate: Navigate, // 当前状态
   frequency: f64,         // 频率
+  odometer: Odometer,     // 估算的里程
 }
 
 impl Montor {
@@ -29,6 +32,7 @@ impl Montor {
       in4: gpio.get(config::PIN_L298N_IN4).unwrap().into_output_low(),
       frequency: Self::DEFAULT_FREQUENCY,
       navigate: Navigate::Brake,
+      odometer: Odometer::new(Instant::now()),
     }
   }
 
@@ -38,6 +42,14 @@ impl Montor {
     //   speed = (speed << 1).min(100); // 左转右转速度加倍
     // }
     let duty_cycle = speed as f64 / 100.0;
+    let (left, right) = match navigate {
+      Navigate::Brake => (0_f64, 0_f64),
+      Navigate::Left => (-duty_cycle, duty_cycle),
+      Navigate::Right => (duty_cycle, -duty_cycle),
+      Navigate::Forward => (duty_cycle, duty_cycle),
+      Navigate::BackWard => (-duty_cycle, -duty_cycle),
+    };
+    self.odometer.set(left, right, Instant::now());
 
     match navigate {
       Navigate::Brake => {
@@ -65,6 +77,35 @@ impl Montor {
     self.navigate = navigate;
   }
 
+  /// 差速驱动
+  /// left, right: 左右轮的占空比 -1 到 1, 负数为后退
+  pub fn drive(&mut self, left: f64, right: f64) {
+    self.odometer.set(left.clamp(-1_f64, 1_f64), right.clamp(-1_f64, 1_f64), Instant::now());
+    match left {
+      left if left > 0_f64 => self.set_left_forward(left.min(1_f64)),
+      left if left < 0_f64 => self.set_left_backward((-left).min(1_f64)),
+      _ => self.set_left_stop(),
+    }
+    match right {
+      right if right > 0_f64 => self.set_right_forward(right.min(1_f64)),
+      right if right < 0_f64 => self.set_right_backward((-right).min(1_f64)),
+      _ => self.set_right_stop(),
+    }
+
+    self.navigate = match (left, right) {
+      (left, right) if left == 0_f64 && right == 0_f64 => Navigate::Brake,
+      (left, right) if left > 0_f64 && right > 0_f64 => Navigate::Forward,
+      (left, right) if left < 0_f64 && right < 0_f64 => Navigate::BackWard,
+      (left, right) if left < right => Navigate::Left,
+      _ => Navigate::Right,
+    };
+  }
+
+  /// 估算的累计里程 cm, 后退时减少
+  pub fn distance(&self) -> f64 {
+    self.odometer.distance(Instant::now())
+  }
+
   pub fn set_left_forward(&mut self, duty_cycle: f64) {
     self.in1.clear_pwm().unwrap();
     self.in2.set_pwm_frequency(self.frequency, duty_cycle).unwrap();
@@ -3,14 +3,9 @@ use rppal::{
   gpio::{Gpio, IoPin, Mode, OutputPin},
   hal::Delay,
 };
-use time::Time;
-use tm1637_embedded_hal::{
-  blocking::TM1637,
-  mappings::{DigitBits, SegmentBits},
-  Brightness,
-};
+use tm1637_embedded_hal::{blocking::TM1637, Brightness};
 
-use crate::config;
+use crate::{config, segment::Segments};
 
 /// TM1637: 4位LED 数码管
 pub struct Nixie {
@@ -25,9 +20,9 @@ impl Nixie {
     Self { tm1637: TM1637::builder(clk, dio, Delay).brightness(Brightness::L0).build() }
   }
 
-  /// 显示时间
-  /// flag: 是否显示冒号
-  pub fn display_time(&mut self, time: &Time, has_colon: bool, brightness: u8) {
+  /// 显示 4 位段码
+  /// brightness: 亮度等级 0-7
+  pub fn display_segments(&mut self, segments: &Segments, brightness: u8) {
     let brightness = match brightness {
       0 => Brightness::L0,
       1 => Brightness::L1,
@@ -43,18 +38,10 @@ impl Nixie {
       }
     };
 
-    let (h, m, _) = time.as_hms();
-    let bytes = [
-      DigitBits::from_digit(h / 10) as u8,
-      DigitBits::from_digit(h % 10) as u8 | (has_colon as u8 * (SegmentBits::SegPoint as u8)),
-      DigitBits::from_digit(m / 10) as u8,
-      DigitBits::from_digit(m % 10) as u8,
-    ];
-
     let _ = self.tm1637.write_brightness(brightness).inspect_err(|_| {
       debug!("nixie brightness setting failed");
     });
-    let _ = self.tm1637.write_segments_raw(0, &bytes);
+    let _ = self.tm1637.write_segments_raw(0, segments);
   }
 
   /// 关闭显示
@@ -1,14 +1,15 @@
 //! RGB LED
 
+use log::debug;
 use rppal::gpio::{Gpio, OutputPin};
 
 use crate::config;
 
-/// TODO: 设置亮度
+/// 软件 PWM 控制各通道亮度
 pub struct RgbLed {
-  pub red: OutputPin,
-  pub green: OutputPin,
-  pub blue: OutputPin,
+  red: OutputPin,
+  green: OutputPin,
+  blue: OutputPin,
 }
 
 impl RgbLed {
@@ -21,10 +22,22 @@ impl RgbLed {
     }
   }
 
+  /// 设置各通道的占空比 0-1
+  pub fn set(&mut self, (r, g, b): (f64, f64, f64)) {
+    for (pin, duty_cycle) in [(&mut self.red, r), (&mut self.green, g), (&mut self.blue, b)] {
+      let result = if duty_cycle <= 0_f64 {
+        pin.clear_pwm().map(|_| pin.set_low())
+      } else if duty_cycle >= 1_f64 {
+        pin.clear_pwm().map(|_| pin.set_high())
+      } else {
+        pin.set_pwm_frequency(config::LED_PWM_FREQUENCY, duty_cycle)
+      };
+      let _ = result.inspect_err(|e| debug!("rgb led: {}", e));
+    }
+  }
+
   /// 关闭灯
   pub fn off(&mut self) {
-    self.red.set_low();
-    self.green.set_low();
-    self.blue.set_low();
+    self.set((0_f64, 0_f64, 0_f64));
   }
 }
@@ -28,7 +28,7 @@ impl Servos {
     servos
   }
 
-  /// 转动
+  /// 转动, angle: 0-180
   pub fn rotate(&mut self, angle: u8) -> bool {
     let pulse_width = match angle {
       0 => Self::PULSE_WIDTH_ANGLE_0,
@@ -36,6 +36,11 @@ impl Servos {
       90 => Self::PULSE_WIDTH_ANGLE_90,
       135 => Self::PULSE_WIDTH_ANGLE_135,
       180 => Self::PULSE_WIDTH_ANGLE_180,
+      1..=179 => {
+        // 线性插值
+        let range = Self::PULSE_WIDTH_ANGLE_180 - Self::PULSE_WIDTH_ANGLE_0;
+        Self::PULSE_WIDTH_ANGLE_0 + range * angle as u32 / 180
+      }
       _ => {
         debug!("servos: 不可用的角度");
         return false;
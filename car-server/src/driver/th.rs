@@ -1,109 +1,95 @@
-//! DHT11 传感器: 传感温度与湿度
+//! DHT11/DHT22 传感器: 传感温度与湿度
+
+use std::time::{Duration, Instant};
 
 use embedded_hal::delay::DelayNs;
+use log::debug;
 use rppal::{
-  gpio::{Gpio, IoPin, Mode},
+  gpio::{Gpio, IoPin, Level, Mode},
   hal::Delay,
 };
 
-use crate::config;
-
-#[derive(Debug)]
-pub enum Error {
-  NoResponse,
-  ChecksumMismatch,
-  Timeout,
-}
-
-#[derive(Debug)]
-pub struct Measurement {
-  pub temperature: f32,
-  pub humidity: f32,
-}
+use crate::{
+  config,
+  dht::{decode, decode_pulses, Error, ErrorStatistics, Measurement, SensorType},
+};
 
 /// 温湿度传感器
 pub struct TH {
   pin: IoPin, // data pin
   delay: Delay,
+  sensor: SensorType,
+  last_attempt: Option<Instant>, // 上一次读取的时间, 无论成功与否
+
+  pub errors: ErrorStatistics, // 错误统计
 }
 
 impl TH {
-  pub fn new(gpio: &Gpio) -> Self {
-    Self { pin: gpio.get(config::PIN_DHT11_DATA).unwrap().into_io(rppal::gpio::Mode::Input), delay: Delay::new() }
-  }
-
-  /// 测量温度与湿度
-  pub fn measure(&mut self) -> Option<Measurement> {
-    self
-      .read_raw()
-      .map(|[hi, hf, ti, tf]| Measurement {
-        temperature: format!("{:}.{:}", ti, tf).parse::<f32>().unwrap(),
-        humidity: format!("{:}.{:}", hi, hf).parse::<f32>().unwrap(),
-      })
-      .inspect_err(|_err| {
-        // println!("th: {:?}", err);
-      })
-      .ok()
+  pub const EDGE_TIMEOUT: Duration = Duration::from_micros(200); // 等待电平变化的最长时间
+
+  pub fn new(gpio: &Gpio, sensor: SensorType) -> Self {
+    Self {
+      pin: gpio.get(config::PIN_DHT11_DATA).unwrap().into_io(Mode::Input),
+      delay: Delay::new(),
+      sensor,
+      last_attempt: None,
+      errors: ErrorStatistics::default(),
+    }
   }
 
-  fn read_bit(&mut self) -> Result<u8, Error> {
-    wait_until_timeout(&mut self.delay, || self.pin.is_high(), 100)?;
-    self.delay.delay_us(28);
-    let bit = self.pin.is_high() as u8;
-    wait_until_timeout(&mut self.delay, || self.pin.is_low(), 100)?;
-    Ok(bit)
-  }
+  /// 测量一次温度与湿度
+  /// 采样间隔与失败后的重试由调度器安排, 见 SensorType::min_interval 与 config::TH_RETRIES
+  /// 距上一次读取 (包括失败的读取) 不足最小间隔时不读取, 返回 Error::TooSoon
+  pub fn measure(&mut self) -> Result<Measurement, Error> {
+    let now = Instant::now();
+    if self.last_attempt.is_some_and(|last| now.duration_since(last) < self.sensor.min_interval()) {
+      return Err(Error::TooSoon);
+    }
+    self.last_attempt = Some(now);
 
-  fn read_byte(&mut self) -> Result<u8, Error> {
-    let mut byte: u8 = 0;
-    for _ in 0..8 {
-      byte <<= 1;
-      byte |= self.read_bit()?;
+    let result = self.read_pulses().and_then(|pulses| decode_pulses(&pulses)).and_then(|b| decode(self.sensor, b));
+    self.errors.record(&result);
+    if let Err(err) = &result {
+      debug!("th: {:?}, {:?}", err, self.errors);
     }
-    Ok(byte)
+    result
   }
 
-  fn read_raw(&mut self) -> Result<[u8; 4], Error> {
+  /// 读取 40 个数据位的高电平持续时间
+  fn read_pulses(&mut self) -> Result<[Duration; 40], Error> {
+    // 起始信号
     self.pin.set_mode(Mode::Output);
-    self.delay.delay_us(140);
-    self.pin.set_high();
-    self.delay.delay_us(140);
-
     self.pin.set_low();
-    self.delay.delay_ms(18_u32);
+    self.delay.delay_us(self.sensor.start_signal().as_micros() as u32);
     self.pin.set_high();
-    self.delay.delay_us(48_u32);
-
     self.pin.set_mode(Mode::Input);
-    if self.pin.is_low() {
-      wait_until_timeout(&mut self.delay, || self.pin.is_high(), 100)?;
-      wait_until_timeout(&mut self.delay, || self.pin.is_low(), 100)?;
-
-      let mut data = [0; 4];
-      for b in data.iter_mut() {
-        *b = self.read_byte()?;
-      }
 
-      let checksum = self.read_byte()?;
-      if data.iter().fold(0u8, |sum, v| sum.wrapping_add(*v)) != checksum {
-        Err(Error::ChecksumMismatch)
-      } else {
-        Ok(data)
-      }
-    } else {
-      Err(Error::NoResponse)
+    // 响应信号: 低电平 80us, 高电平 80us
+    self.wait_for(Level::Low).map_err(|_| Error::NoResponse)?;
+    self.wait_for(Level::High).map_err(|_| Error::NoResponse)?;
+    self.wait_for(Level::Low).map_err(|_| Error::NoResponse)?;
+
+    // 每一位以 50us 的低电平开始, 高电平的持续时间决定该位的值
+    // 记录边沿的时间戳而非在固定时刻采样, 以容忍调度带来的延迟
+    let mut pulses = [Duration::ZERO; 40];
+    for pulse in pulses.iter_mut() {
+      self.wait_for(Level::High)?;
+      let start = Instant::now();
+      self.wait_for(Level::Low)?;
+      *pulse = start.elapsed();
     }
+
+    Ok(pulses)
   }
-}
 
-/// wait until the given function returns true or the timeout is reached.
-fn wait_until_timeout(delay: &mut Delay, func: impl Fn() -> bool, timeout_us: u8) -> Result<(), Error> {
-  for _ in 0..timeout_us {
-    if func() {
-      return Ok(());
+  /// 等待引脚变为 level
+  fn wait_for(&self, level: Level) -> Result<(), Error> {
+    let start = Instant::now();
+    while self.pin.read() != level {
+      if start.elapsed() > Self::EDGE_TIMEOUT {
+        return Err(Error::Timeout);
+      }
     }
-
-    delay.delay_us(1_u32);
+    Ok(())
   }
-  Err(Error::Timeout)
 }
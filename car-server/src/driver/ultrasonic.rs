@@ -1,32 +1,124 @@
-use hc_sr04::HcSr04;
+//! HC-SR04 超声波测距
+
+use std::{
+  thread,
+  time::{Duration, Instant},
+};
+
+use car_utils::SensorError;
+use embedded_hal::delay::DelayNs;
 use log::debug;
-use rppal::gpio::Gpio;
+use rppal::{
+  gpio::{Gpio, InputPin, OutputPin},
+  hal::Delay,
+};
+
+use crate::{
+  config,
+  filter::{median, MedianFilter},
+};
+
+#[derive(Debug)]
+pub enum Error {
+  NoEcho,     // 等待回波超时
+  OutOfRange, // 超出量程
+}
+
+impl From<Error> for SensorError {
+  fn from(err: Error) -> Self {
+    match err {
+      Error::NoEcho => SensorError::Timeout,
+      Error::OutOfRange => SensorError::OutOfRange,
+    }
+  }
+}
 
-use crate::config;
+/// 超声波传感器
+pub struct Ultrasonic {
+  trig: OutputPin,
+  echo: InputPin,
+  delay: Delay,
 
-pub struct Ultrasonic {}
+  filter: MedianFilter<u16>,
+  last_ping: Option<Instant>, // 上一次测量的时间
+}
 
 impl Ultrasonic {
-  pub fn new(_gpio: &Gpio) -> Self {
-    Self {}
+  pub const PING_INTERVAL: Duration = Duration::from_millis(60); // 两次测量之间的最小间隔, 避免收到上一次的回波
+  pub const ECHO_TIMEOUT: Duration = Duration::from_millis(5); // 触发后等待回波拉高的最长时间
+
+  pub fn new(gpio: &Gpio) -> Self {
+    Self {
+      trig: gpio.get(config::PIN_HCSRC04_TRIG).unwrap().into_output_low(),
+      echo: gpio.get(config::PIN_HCSRC04_ECHO).unwrap().into_input(),
+      delay: Delay::new(),
+      filter: MedianFilter::new(config::ULTRASONIC_WINDOW),
+      last_ping: None,
+    }
+  }
+
+  /// 获取距离 cm: 连续测量时使用, 结果经过滑动窗口中值滤波
+  pub fn get_distance(&mut self, temperature: Option<f32>) -> Result<u16, Error> {
+    self.ping(temperature).map(|distance| self.filter.push(distance))
   }
 
-  /// FIX: 只有第一次测是相对准确的
-  /// 获取距离 cm
-  pub fn get_distance(&mut self, _temperature: Option<f32>) -> Option<u16> {
-    let mut hcsrc04 = HcSr04::new(
-      config::PIN_HCSRC04_TRIG,
-      config::PIN_HCSRC04_ECHO,
-      _temperature, //
-    )
-    .expect("失败");
-
-    hcsrc04
-      .measure_distance(hc_sr04::Unit::Centimeters)
-      .inspect_err(|e| {
-        debug!("get_distance: {}", e); //
-      })
-      .unwrap_or_default()
-      .map(|x| x.floor() as u16)
+  /// 在新的方向上测量 samples 次, 返回中值 cm
+  pub fn measure(&mut self, temperature: Option<f32>, samples: usize) -> Result<u16, Error> {
+    self.filter.clear(); // 方向改变后旧的读数不再有效
+
+    let mut error = Error::NoEcho;
+    let mut distances = Vec::with_capacity(samples);
+    for _ in 0..samples {
+      match self.ping(temperature) {
+        Ok(distance) => distances.push(distance),
+        Err(err) => error = err,
+      }
+    }
+
+    median(&mut distances).ok_or(error)
+  }
+
+  /// 单次测量 cm
+  fn ping(&mut self, temperature: Option<f32>) -> Result<u16, Error> {
+    if let Some(elapsed) = self.last_ping.map(|last_ping| last_ping.elapsed()) {
+      thread::sleep(Self::PING_INTERVAL.saturating_sub(elapsed));
+    }
+    self.last_ping = Some(Instant::now());
+
+    // 至少 10us 的触发脉冲
+    self.trig.set_high();
+    self.delay.delay_us(10);
+    self.trig.set_low();
+
+    let speed = sound_speed(temperature);
+    let max_duration = Duration::from_secs_f32(config::ULTRASONIC_MAX_DISTANCE as f32 * 2_f32 / 100_f32 / speed);
+
+    let start = Instant::now();
+    while self.echo.is_low() {
+      if start.elapsed() > Self::ECHO_TIMEOUT {
+        return Err(Error::NoEcho);
+      }
+    }
+
+    let start = Instant::now();
+    while self.echo.is_high() {
+      if start.elapsed() > max_duration {
+        return Err(Error::OutOfRange);
+      }
+    }
+    let duration = start.elapsed();
+
+    let distance = (duration.as_secs_f32() * speed * 100_f32 / 2_f32) as u16;
+    if distance < config::ULTRASONIC_MIN_DISTANCE {
+      debug!("ultrasonic: {}cm 小于最小量程", distance);
+      return Err(Error::OutOfRange);
+    }
+
+    Ok(distance)
   }
 }
+
+/// 声速 m/s, 随温度变化
+fn sound_speed(temperature: Option<f32>) -> f32 {
+  331.3 + 0.606 * temperature.unwrap_or(20_f32)
+}
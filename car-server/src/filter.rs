@@ -0,0 +1,66 @@
+//! 传感器读数滤波
+
+use std::collections::VecDeque;
+
+/// 滑动窗口中值滤波: 去除偶发的异常读数
+#[derive(Debug)]
+pub struct MedianFilter<T> {
+  window: VecDeque<T>,
+  capacity: usize,
+}
+
+impl<T: Copy + Ord> MedianFilter<T> {
+  pub fn new(capacity: usize) -> Self {
+    Self { window: VecDeque::with_capacity(capacity), capacity: capacity.max(1) }
+  }
+
+  /// 加入一个读数, 返回当前窗口的中值
+  pub fn push(&mut self, value: T) -> T {
+    if self.window.len() == self.capacity {
+      self.window.pop_front();
+    }
+    self.window.push_back(value);
+
+    self.median().unwrap()
+  }
+
+  /// 当前窗口的中值
+  pub fn median(&self) -> Option<T> {
+    let mut values: Vec<T> = self.window.iter().copied().collect();
+    median(&mut values)
+  }
+
+  /// 清空窗口 (如测量方向改变后)
+  pub fn clear(&mut self) {
+    self.window.clear();
+  }
+}
+
+/// 求中值, 偶数个时取较小的一个
+pub fn median<T: Copy + Ord>(values: &mut [T]) -> Option<T> {
+  if values.is_empty() {
+    return None;
+  }
+
+  values.sort_unstable();
+  Some(values[(values.len() - 1) / 2])
+}
+
+#[cfg(test)]
+mod test {
+  use super::MedianFilter;
+
+  #[test]
+  fn test_median_filter() {
+    let mut filter = MedianFilter::new(3);
+    assert_eq!(filter.median(), None);
+    assert_eq!(filter.push(50), 50);
+    assert_eq!(filter.push(52), 50);
+    assert_eq!(filter.push(400), 52); // 偶发的异常读数被滤掉
+    assert_eq!(filter.push(51), 52);
+    assert_eq!(filter.push(49), 51);
+
+    filter.clear();
+    assert_eq!(filter.push(10), 10);
+  }
+}
@@ -0,0 +1,156 @@
+//! RGB LED 灯效果
+
+use serde::Deserialize;
+
+#[cfg(any(feature = "rasp", test))]
+use crate::config;
+#[cfg(any(feature = "rasp", test))]
+use car_utils::{LedEffect, Statistics};
+#[cfg(any(feature = "rasp", test))]
+use std::{f64::consts::PI, time::Duration};
+
+/// 各通道的占空比 0-1
+#[cfg(any(feature = "rasp", test))]
+pub type Duty = (f64, f64, f64);
+
+/// 计算效果在 elapsed 时刻各通道的占空比
+/// color: 效果的颜色, Rainbow 忽略该颜色; brightness: 0-100
+/// Status 效果应先通过 status 得到实际的效果与颜色
+#[cfg(any(feature = "rasp", test))]
+pub fn render(effect: LedEffect, color: (u8, u8, u8), brightness: u8, elapsed: Duration) -> Duty {
+  let phase = |period_ms: u64| (elapsed.as_millis() as u64 % period_ms) as f64 / period_ms as f64;
+
+  let (color, level) = match effect {
+    LedEffect::Solid | LedEffect::Status => (color, 1_f64),
+    LedEffect::Blink => (color, if phase(config::LED_BLINK_PERIOD_MS) < 0.5 { 1_f64 } else { 0_f64 }),
+    LedEffect::Breathe => (color, (1_f64 - (2_f64 * PI * phase(config::LED_BREATHE_PERIOD_MS)).cos()) / 2_f64),
+    LedEffect::Rainbow => (hue_to_rgb(phase(config::LED_RAINBOW_PERIOD_MS)), 1_f64),
+  };
+
+  let level = level * brightness.min(100) as f64 / 100_f64;
+  let (r, g, b) = color;
+  (r as f64 / 255_f64 * level, g as f64 / 255_f64 * level, b as f64 / 255_f64 * level)
+}
+
+/// 状态指示的条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusCondition {
+  Always,          // 总是匹配
+  Connected,       // 有客户端连接
+  Disconnected,    // 没有客户端连接
+  Obstacle,        // 前方障碍物小于刹车距离
+  Trace,           // 寻迹中
+  Autopilot,       // 自动避障中
+  Scan,            // 雷达扫描中
+  OverTemperature, // 温度过高
+  Alarm,           // 有正在报警的环境规则
+}
+
+#[cfg(any(feature = "rasp", test))]
+impl StatusCondition {
+  pub fn matches(&self, statistics: &Statistics) -> bool {
+    match self {
+      StatusCondition::Always => true,
+      StatusCondition::Connected => statistics.clients() > 0,
+      StatusCondition::Disconnected => statistics.clients() == 0,
+      StatusCondition::Obstacle => {
+        statistics.ultrasonic() && statistics.servos() == 90 && statistics.distance() <= config::MIN_DISTANCE
+      }
+      StatusCondition::Trace => statistics.trace(),
+      StatusCondition::Autopilot => statistics.autopilot(),
+      StatusCondition::Scan => statistics.scan(),
+      StatusCondition::OverTemperature => statistics.th() && statistics.temperature() >= config::OVER_TEMPERATURE,
+      StatusCondition::Alarm => statistics.alarm(),
+    }
+  }
+}
+
+/// 状态指示规则: 条件满足时显示的颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct StatusRule {
+  pub when: StatusCondition,
+  pub color: (u8, u8, u8),
+  #[serde(default)]
+  pub blink: bool, // 是否闪烁
+}
+
+impl StatusRule {
+  /// 默认规则
+  pub fn defaults() -> Vec<StatusRule> {
+    [
+      (StatusCondition::Obstacle, (255, 0, 0), true), // 红色闪烁: 障碍物刹车
+      (StatusCondition::Alarm, (255, 0, 255), true),  // 紫色闪烁: 环境报警
+      (StatusCondition::Disconnected, (255, 255, 0), false), // 黄: 没有客户端连接
+      (StatusCondition::Trace, (0, 0, 255), false),   // 蓝: 寻迹
+      (StatusCondition::Autopilot, (0, 255, 255), false), // 青: 自动避障
+      (StatusCondition::Always, (0, 255, 0), false),  // 绿: 空闲
+    ]
+    .into_iter()
+    .map(|(when, color, blink)| StatusRule { when, color, blink })
+    .collect()
+  }
+}
+
+/// 按顺序匹配第一条满足的规则, 返回对应的效果与颜色
+#[cfg(any(feature = "rasp", test))]
+pub fn status(rules: &[StatusRule], statistics: &Statistics) -> (LedEffect, (u8, u8, u8)) {
+  rules
+    .iter()
+    .find(|rule| rule.when.matches(statistics))
+    .map(|rule| (if rule.blink { LedEffect::Blink } else { LedEffect::Solid }, rule.color))
+    .unwrap_or((LedEffect::Solid, (0, 0, 0)))
+}
+
+/// 色相 (0-1) 对应的饱和颜色
+#[cfg(any(feature = "rasp", test))]
+fn hue_to_rgb(hue: f64) -> (u8, u8, u8) {
+  let h = hue * 6_f64;
+  let x = ((1_f64 - (h % 2_f64 - 1_f64).abs()) * 255_f64) as u8;
+  match h as u8 {
+    0 => (255, x, 0),
+    1 => (x, 255, 0),
+    2 => (0, 255, x),
+    3 => (0, x, 255),
+    4 => (x, 0, 255),
+    _ => (255, 0, x),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use super::{render, status, StatusRule};
+  use car_utils::{LedEffect, Statistics};
+
+  #[test]
+  fn test_render() {
+    assert_eq!(render(LedEffect::Solid, (255, 0, 51), 50, Duration::ZERO), (0.5, 0_f64, 0.1));
+    assert_eq!(render(LedEffect::Blink, (255, 255, 255), 100, Duration::from_millis(100)), (1_f64, 1_f64, 1_f64));
+    assert_eq!(render(LedEffect::Blink, (255, 255, 255), 100, Duration::from_millis(700)), (0_f64, 0_f64, 0_f64));
+    assert_eq!(render(LedEffect::Breathe, (255, 255, 255), 100, Duration::ZERO), (0_f64, 0_f64, 0_f64));
+    assert_eq!(render(LedEffect::Rainbow, (0, 0, 0), 100, Duration::ZERO), (1_f64, 0_f64, 0_f64));
+  }
+
+  #[test]
+  fn test_status() {
+    let rules = StatusRule::defaults();
+    let statistics = Statistics::default();
+    assert_eq!(status(&rules, &statistics), (LedEffect::Solid, (255, 255, 0)));
+
+    let _client = statistics.client_connected();
+    assert_eq!(status(&rules, &statistics), (LedEffect::Solid, (0, 255, 0)));
+
+    statistics.set_trace(true);
+    assert_eq!(status(&rules, &statistics), (LedEffect::Solid, (0, 0, 255)));
+
+    statistics.set_alarm(true);
+    assert_eq!(status(&rules, &statistics), (LedEffect::Blink, (255, 0, 255)));
+
+    statistics.set_ultrasonic(true);
+    statistics.set_servos(90);
+    statistics.set_distance(10);
+    assert_eq!(status(&rules, &statistics), (LedEffect::Blink, (255, 0, 0)));
+  }
+}
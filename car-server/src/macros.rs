@@ -0,0 +1,231 @@
+//! 录制与回放: 记录手动驾驶时的导航, 舵机与蜂鸣器命令及其时间, 保存为目录下的 <名称>.json
+
+use std::{
+  fs, io,
+  path::PathBuf,
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Mutex,
+  },
+  time::{Duration, Instant},
+};
+
+use car_utils::command::Command;
+use serde::{Deserialize, Serialize};
+
+const MAX_NAME_LEN: usize = 32;
+
+/// 录制的一条命令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+  pub at_ms: u64, // 距录制开始的时间
+  pub command: Command,
+}
+
+impl Entry {
+  /// 按回放速度换算: scale 倍速时命令提前, 速度按比例调整
+  pub fn scaled(&self, scale: f64) -> (Duration, Command) {
+    let at = Duration::from_millis(self.at_ms).div_f64(scale);
+    let command = match self.command {
+      Command::Navigate { navigate, speed } => {
+        Command::Navigate { navigate, speed: (speed as f64 * scale).round().clamp(0_f64, 100_f64) as u8 }
+      }
+      ref command => command.clone(),
+    };
+    (at, command)
+  }
+}
+
+struct Recording {
+  name: String,
+  start: Instant,
+  entries: Vec<Entry>,
+}
+
+/// 等待回放的录制
+pub struct Playback {
+  pub generation: u32, // 第几次请求回放
+  pub name: String,
+  pub scale: u8, // 速度百分比
+}
+
+pub struct Macros {
+  dir: PathBuf,
+  recording: Mutex<Option<Recording>>,
+  pending: Mutex<Option<Playback>>,
+  generation: AtomicU32, // 每请求一次回放加一, 之前的回放随之结束
+  version: AtomicU32,    // 录制列表每变化一次加一
+}
+
+impl Macros {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self {
+      dir: dir.into(),
+      recording: Mutex::new(None),
+      pending: Mutex::new(None),
+      generation: AtomicU32::new(0),
+      version: AtomicU32::new(1),
+    }
+  }
+
+  /// 开始录制, 同名的录制在保存时被覆盖
+  pub fn start_recording(&self, name: &str) -> io::Result<()> {
+    validate(name)?;
+    *self.recording.lock().unwrap() =
+      Some(Recording { name: name.to_string(), start: Instant::now(), entries: Vec::new() });
+    Ok(())
+  }
+
+  /// 录制中时记录手动驾驶的命令
+  pub fn record(&self, command: &Command) {
+    if !matches!(command, Command::Navigate { .. } | Command::Servos { .. } | Command::Buzzer { .. }) {
+      return;
+    }
+    if let Some(recording) = self.recording.lock().unwrap().as_mut() {
+      let at_ms = recording.start.elapsed().as_millis() as u64;
+      recording.entries.push(Entry { at_ms, command: command.clone() });
+    }
+  }
+
+  /// 结束录制并保存, 返回录制的名称
+  pub fn finish_recording(&self) -> io::Result<Option<String>> {
+    let Some(recording) = self.recording.lock().unwrap().take() else {
+      return Ok(None);
+    };
+
+    fs::create_dir_all(&self.dir)?;
+    fs::write(self.path(&recording.name), serde_json::to_string(&recording.entries)?)?;
+    self.version.fetch_add(1, Ordering::SeqCst);
+    Ok(Some(recording.name))
+  }
+
+  pub fn load(&self, name: &str) -> io::Result<Vec<Entry>> {
+    validate(name)?;
+    Ok(serde_json::from_str(&fs::read_to_string(self.path(name))?)?)
+  }
+
+  pub fn delete(&self, name: &str) -> io::Result<()> {
+    validate(name)?;
+    fs::remove_file(self.path(name))?;
+    self.version.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+  }
+
+  /// 已保存的录制, 按名称排序
+  pub fn list(&self) -> io::Result<Vec<String>> {
+    let mut names = match fs::read_dir(&self.dir) {
+      Ok(entries) => entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .collect(),
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+      Err(err) => return Err(err),
+    };
+    names.sort();
+    Ok(names)
+  }
+
+  /// 获取比 version 更新的录制列表
+  pub fn list_since(&self, version: u32) -> Option<(u32, Vec<String>)> {
+    let current = self.version.load(Ordering::SeqCst);
+    (current != version).then(|| (current, self.list().unwrap_or_default()))
+  }
+
+  /// 请求回放, 由回放线程取出; 正在进行的回放随之结束
+  pub fn play(&self, name: &str, scale: u8) -> io::Result<()> {
+    validate(name)?;
+    let mut pending = self.pending.lock().unwrap();
+    let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    *pending = Some(Playback { generation, name: name.to_string(), scale });
+    Ok(())
+  }
+  pub fn take_pending(&self) -> Option<Playback> {
+    self.pending.lock().unwrap().take()
+  }
+
+  /// 是否是最近一次请求的回放
+  pub fn is_current(&self, generation: u32) -> bool {
+    self.generation.load(Ordering::SeqCst) == generation
+  }
+
+  fn path(&self, name: &str) -> PathBuf {
+    self.dir.join(name).with_extension("json")
+  }
+}
+
+/// 名称只能包含字母, 数字, '-' 与 '_', 防止访问目录外的文件
+fn validate(name: &str) -> io::Result<()> {
+  if name.is_empty()
+    || name.len() > MAX_NAME_LEN
+    || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+  {
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("无效的名称: {}", name)));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use car_utils::command::{Command, Navigate};
+
+  use super::{Entry, Macros, Playback};
+
+  #[test]
+  fn test_record() {
+    let dir = std::env::temp_dir().join(format!("car-server-macros-{}", std::process::id()));
+    let macros = Macros::new(&dir);
+    assert_eq!(macros.list().unwrap(), Vec::<String>::new());
+    let (version, _) = macros.list_since(0).unwrap();
+
+    assert!(macros.start_recording("../etc").is_err());
+    macros.start_recording("lap_1").unwrap();
+    macros.record(&Command::Navigate { navigate: Navigate::Forward, speed: 50 });
+    macros.record(&Command::TH { enabled: true }); // 不是手动驾驶的命令
+    macros.record(&Command::Servos { angle: 45 });
+    assert_eq!(macros.finish_recording().unwrap().as_deref(), Some("lap_1"));
+    assert_eq!(macros.finish_recording().unwrap(), None);
+
+    assert_eq!(macros.list_since(version).map(|(_, list)| list), Some(vec!["lap_1".to_string()]));
+    let entries = macros.load("lap_1").unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(matches!(entries[1].command, Command::Servos { angle: 45 }));
+
+    macros.delete("lap_1").unwrap();
+    assert!(macros.load("lap_1").is_err());
+    assert!(macros.list().unwrap().is_empty());
+    let _ = std::fs::remove_dir_all(dir);
+  }
+
+  #[test]
+  fn test_play() {
+    let macros = Macros::new(std::env::temp_dir());
+    assert!(macros.play("../etc", 100).is_err());
+    macros.play("lap_1", 100).unwrap();
+    let Some(Playback { generation, name, scale }) = macros.take_pending() else { panic!() };
+    assert_eq!((name.as_str(), scale), ("lap_1", 100));
+    assert!(macros.take_pending().is_none());
+    assert!(macros.is_current(generation));
+
+    // 停止后立即再次回放时, 之前的回放不再是最近的
+    macros.play("lap_2", 50).unwrap();
+    assert!(!macros.is_current(generation));
+    assert!(macros.is_current(macros.take_pending().unwrap().generation));
+  }
+
+  #[test]
+  fn test_scaled() {
+    let entry = Entry { at_ms: 1000, command: Command::Navigate { navigate: Navigate::Left, speed: 80 } };
+    let (at, command) = entry.scaled(2_f64);
+    assert_eq!(at, Duration::from_millis(500));
+    assert!(matches!(command, Command::Navigate { navigate: Navigate::Left, speed: 100 }));
+
+    let (at, command) = entry.scaled(0.5);
+    assert_eq!(at, Duration::from_secs(2));
+    assert!(matches!(command, Command::Navigate { speed: 40, .. }));
+  }
+}
@@ -1,21 +1,57 @@
+mod alarm;
+mod autopilot;
+#[cfg(feature = "vision")]
+mod camera;
+mod clock;
 mod config;
 mod connection;
 mod context;
+mod dht;
 #[cfg(feature = "rasp")]
 mod driver;
-#[cfg(feature = "rasp")]
+#[cfg(any(feature = "rasp", test))]
+mod filter;
+mod led;
+mod macros;
+#[cfg(any(feature = "vision", test))]
+mod marker;
+mod melody;
+mod mission;
+mod odometer;
+mod radar;
+#[cfg(feature = "vision")]
+mod replay;
+mod scheduler;
+mod script;
+#[cfg(any(feature = "rasp", test))]
+mod segment;
+mod settings;
+#[cfg(any(feature = "vision", test))]
+mod steering;
+#[cfg(any(feature = "rasp", test))]
+mod stream;
+mod subscription;
+mod survey;
+#[cfg(feature = "vision")]
 mod trace;
 
 use context::Context;
 use log::LevelFilter;
 #[cfg(feature = "rasp")]
 use rppal::gpio::Gpio;
+use settings::Settings;
 use std::io;
 
 /// TODO: 优雅的结束进程
 fn main() -> io::Result<()> {
   env_logger::builder().filter_level(LevelFilter::Debug).init();
 
+  // 离线寻迹: car-server trace <图片目录或视频> <输出目录> [算法]
+  #[cfg(feature = "vision")]
+  if std::env::args().nth(1).as_deref() == Some("trace") {
+    return replay::run(&std::env::args().skip(2).collect::<Vec<_>>());
+  }
+
   #[cfg(feature = "rasp")]
   let gpio = Gpio::new().unwrap();
   #[cfg(feature = "rasp")]
@@ -30,6 +66,7 @@ fn main() -> io::Result<()> {
 
   let mut context = Context::new(
     (config::LISTEN_ADDR, config::LISTEN_PORT),
+    Settings::load(),
     #[cfg(feature = "rasp")]
     driver,
   )
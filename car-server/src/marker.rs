@@ -0,0 +1,89 @@
+//! 赛道标志: 连续识别到同一个标志若干帧后生效, 停车与减速保持到识别到通行标志
+
+use car_utils::Marker;
+
+use crate::config;
+
+#[derive(Debug, Default)]
+pub struct Signals {
+  candidate: Option<(Marker, usize)>, // 正在确认的标志与连续识别到的帧数
+  active: Option<Marker>,             // 生效的标志
+}
+
+impl Signals {
+  /// 输入一帧的识别结果, 返回新生效的标志
+  pub fn update(&mut self, detected: Option<Marker>) -> Option<Marker> {
+    let Some(marker) = detected else {
+      self.candidate = None;
+      return None;
+    };
+
+    let count = match self.candidate {
+      Some((candidate, count)) if candidate == marker => count + 1,
+      _ => 1,
+    };
+    self.candidate = Some((marker, count));
+
+    (count >= config::MARKER_CONFIRM_FRAMES && self.active != Some(marker)).then(|| {
+      self.active = Some(marker);
+      marker
+    })
+  }
+
+  pub fn active(&self) -> Option<Marker> {
+    self.active
+  }
+
+  /// 生效的标志对应的速度 (相对基础速度)
+  pub fn speed_factor(&self) -> f64 {
+    match self.active {
+      Some(Marker::Stop) => 0_f64,
+      Some(Marker::Slow) => config::MARKER_SLOW_FACTOR,
+      Some(Marker::Go) | None => 1_f64,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use car_utils::Marker;
+
+  use super::Signals;
+  use crate::config;
+
+  #[test]
+  fn test_confirm() {
+    let mut signals = Signals::default();
+    assert_eq!(signals.speed_factor(), 1_f64);
+
+    // 偶尔的误识别不生效
+    for _ in 1..config::MARKER_CONFIRM_FRAMES {
+      assert_eq!(signals.update(Some(Marker::Stop)), None);
+    }
+    assert_eq!(signals.update(None), None);
+    assert_eq!(signals.active(), None);
+
+    for _ in 1..config::MARKER_CONFIRM_FRAMES {
+      assert_eq!(signals.update(Some(Marker::Stop)), None);
+    }
+    assert_eq!(signals.update(Some(Marker::Stop)), Some(Marker::Stop));
+    assert_eq!(signals.update(Some(Marker::Stop)), None);
+    assert_eq!(signals.speed_factor(), 0_f64);
+  }
+
+  fn confirm(signals: &mut Signals, marker: Marker) -> Option<Marker> {
+    (0..config::MARKER_CONFIRM_FRAMES).filter_map(|_| signals.update(Some(marker))).last()
+  }
+
+  #[test]
+  fn test_hold() {
+    let mut signals = Signals::default();
+    assert_eq!(confirm(&mut signals, Marker::Slow), Some(Marker::Slow));
+    // 标志离开画面后保持减速
+    assert_eq!(signals.update(None), None);
+    assert_eq!(signals.speed_factor(), config::MARKER_SLOW_FACTOR);
+
+    assert_eq!(confirm(&mut signals, Marker::Go), Some(Marker::Go));
+    assert_eq!(signals.speed_factor(), 1_f64);
+  }
+}
@@ -0,0 +1,113 @@
+//! 蜂鸣器乐谱
+//!
+//! 乐谱由空格分隔的音符组成:
+//! - `T<bpm>`: 设置速度 (每分钟四分音符数), 默认 120
+//! - `<音名>[#][八度][/时值]`: 音名为 C D E F G A B, 八度默认 4, 时值为几分音符, 默认 4
+//! - `R[/时值]`: 休止符
+//!
+//! 例如: `T180 E5/8 C5/8 R/4`
+
+use std::time::Duration;
+
+use car_utils::Sound;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+  InvalidToken(String), // 无法解析的音符
+}
+
+/// 一个音符
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+  pub frequency: Option<f64>, // 频率 Hz, 休止符为 None
+  pub duration: Duration,
+}
+
+/// 内置声音的乐谱
+pub fn builtin(sound: Sound) -> &'static str {
+  match sound {
+    Sound::Stop | Sound::Custom => "",
+    Sound::Beep => "T120 A5/8",
+    Sound::Obstacle => "T240 A5/16 R/16 A5/16 R/16 A5/16",
+    Sound::LostLine => "T180 E5/8 C5/8",
+    Sound::OverTemperature => "T200 C6/8 G5/8 C6/8 G5/8 C6/8 G5/8",
+    Sound::Alarm => "T160 A5/8 E5/8 A5/8 E5/8",
+  }
+}
+
+/// 解析乐谱
+pub fn parse(melody: &str) -> Result<Vec<Tone>, Error> {
+  let mut bpm = 120_u32;
+  let mut tones = Vec::new();
+
+  for token in melody.split_whitespace() {
+    let invalid = || Error::InvalidToken(token.to_string());
+
+    if let Some(value) = token.strip_prefix('T') {
+      bpm = value.parse().ok().filter(|bpm| *bpm > 0).ok_or_else(invalid)?;
+      continue;
+    }
+
+    let (pitch, length) = token.split_once('/').unwrap_or((token, "4"));
+    let length: u32 = length.parse().ok().filter(|length| *length > 0).ok_or_else(invalid)?;
+    // 四分音符的时长为 60 / bpm 秒
+    let duration = Duration::from_secs(60) * 4 / bpm / length;
+
+    let frequency = if pitch == "R" { None } else { Some(frequency(pitch).ok_or_else(invalid)?) };
+    tones.push(Tone { frequency, duration });
+  }
+
+  Ok(tones)
+}
+
+/// 音高对应的频率 Hz
+fn frequency(pitch: &str) -> Option<f64> {
+  let mut chars = pitch.chars();
+  let semitone = match chars.next()? {
+    'C' => 0,
+    'D' => 2,
+    'E' => 4,
+    'F' => 5,
+    'G' => 7,
+    'A' => 9,
+    'B' => 11,
+    _ => return None,
+  };
+  let rest = chars.as_str();
+  let (semitone, octave) = match rest.strip_prefix('#') {
+    Some(octave) => (semitone + 1, octave),
+    None => (semitone, rest),
+  };
+  let octave: i32 = if octave.is_empty() { 4 } else { octave.parse().ok().filter(|o| (0..=8).contains(o))? };
+
+  // MIDI 编号, A4 = 69 = 440Hz
+  let note = (octave + 1) * 12 + semitone;
+  Some(440_f64 * 2_f64.powf((note - 69) as f64 / 12_f64))
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use super::{builtin, parse, Error};
+  use car_utils::Sound;
+
+  #[test]
+  fn test_parse() {
+    let tones = parse("T60 A4 C#5/2 R/8").unwrap();
+    assert_eq!(tones.len(), 3);
+    assert_eq!(tones[0].frequency, Some(440_f64));
+    assert_eq!(tones[0].duration, Duration::from_secs(1));
+    assert!((tones[1].frequency.unwrap() - 554.37).abs() < 0.01);
+    assert_eq!(tones[1].duration, Duration::from_secs(2));
+    assert_eq!(tones[2].frequency, None);
+    assert_eq!(tones[2].duration, Duration::from_millis(500));
+
+    assert_eq!(parse("A4 H4"), Err(Error::InvalidToken("H4".to_string())));
+    assert!(parse("A4/0").is_err());
+
+    for sound in [Sound::Beep, Sound::Obstacle, Sound::LostLine, Sound::OverTemperature, Sound::Alarm] {
+      assert!(!parse(builtin(sound)).unwrap().is_empty());
+    }
+  }
+}
@@ -0,0 +1,99 @@
+//! 任务执行: 将任务步骤换算为电机, 舵机等动作; 行驶距离与转向角度按满速时的标定值换算为时间
+
+use std::time::Duration;
+
+use car_utils::{command::Navigate, mission::MissionStep};
+
+use crate::config;
+
+/// 任务步骤对应的动作
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+  Drive { navigate: Navigate, speed: u8, duration: Duration }, // 行驶一段时间后刹车
+  Servo(u8),                                                   // 转动舵机
+  WaitDistance(u16),                                           // 等待前方障碍物距离不大于 cm
+  Wait(Duration),                                              // 等待
+  Beep,                                                        // 鸣笛
+  Measure,                                                     // 测量
+}
+
+impl From<MissionStep> for Action {
+  fn from(step: MissionStep) -> Self {
+    match step {
+      MissionStep::Move { forward, speed, cm } => Action::Drive {
+        navigate: if forward { Navigate::Forward } else { Navigate::BackWard },
+        speed: speed.clamp(1, 100),
+        duration: duration(cm as f64, config::MISSION_CM_PER_SEC, speed),
+      },
+      MissionStep::Drive { navigate, speed, ms } => {
+        Action::Drive { navigate, speed: speed.min(100), duration: Duration::from_millis(ms as u64) }
+      }
+      MissionStep::Turn { degrees, speed } => Action::Drive {
+        navigate: if degrees < 0 { Navigate::Left } else { Navigate::Right },
+        speed: speed.clamp(1, 100),
+        duration: duration(degrees.unsigned_abs() as f64, config::MISSION_DEG_PER_SEC, speed),
+      },
+      MissionStep::Servo { angle } => Action::Servo(angle.min(180)),
+      MissionStep::WaitDistance { cm } => Action::WaitDistance(cm),
+      MissionStep::Wait { ms } => Action::Wait(Duration::from_millis(ms as u64)),
+      MissionStep::Beep => Action::Beep,
+      MissionStep::Measure => Action::Measure,
+    }
+  }
+}
+
+/// 以 speed 百分比的速度完成 amount 所需的时间, rate 为满速时每秒的量
+fn duration(amount: f64, rate: f64, speed: u8) -> Duration {
+  Duration::from_secs_f64(amount / (rate * speed.clamp(1, 100) as f64 / 100_f64))
+}
+
+/// 只在任务运行时计时, 暂停期间不计入
+#[derive(Debug)]
+pub struct Timer {
+  remaining: Duration,
+}
+
+impl Timer {
+  pub fn new(duration: Duration) -> Self {
+    Self { remaining: duration }
+  }
+
+  /// 经过 dt, 返回是否到时
+  pub fn tick(&mut self, dt: Duration) -> bool {
+    self.remaining = self.remaining.saturating_sub(dt);
+    self.remaining.is_zero()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use car_utils::{command::Navigate, mission::MissionStep};
+
+  use super::{Action, Timer};
+
+  #[test]
+  fn test_action() {
+    // 满速 60 cm/s, 半速行驶 60 cm 需要 2 秒
+    assert_eq!(
+      Action::from(MissionStep::Move { forward: false, speed: 50, cm: 60 }),
+      Action::Drive { navigate: Navigate::BackWard, speed: 50, duration: Duration::from_secs(2) }
+    );
+    // 满速 180 度/s, 左转 90 度需要 0.5 秒
+    assert_eq!(
+      Action::from(MissionStep::Turn { degrees: -90, speed: 100 }),
+      Action::Drive { navigate: Navigate::Left, speed: 100, duration: Duration::from_millis(500) }
+    );
+    assert_eq!(Action::from(MissionStep::Servo { angle: 200 }), Action::Servo(180));
+    assert_eq!(Action::from(MissionStep::Wait { ms: 1500 }), Action::Wait(Duration::from_millis(1500)));
+  }
+
+  #[test]
+  fn test_timer() {
+    let mut timer = Timer::new(Duration::from_millis(50));
+    assert!(!timer.tick(Duration::from_millis(20)));
+    assert!(!timer.tick(Duration::from_millis(20)));
+    assert!(timer.tick(Duration::from_millis(20)));
+  }
+}
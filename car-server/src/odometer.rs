@@ -0,0 +1,55 @@
+//! 里程估算: 小车没有编码器, 按左右轮的占空比与满速时的速度积分
+
+use std::time::Instant;
+
+use crate::config;
+
+#[derive(Debug)]
+pub struct Odometer {
+  speed: f64,     // 左右轮占空比的平均值, 负数为后退
+  since: Instant, // 占空比最近一次变化的时间
+  distance: f64,  // 到 since 为止累计的距离 cm
+}
+
+impl Odometer {
+  pub fn new(now: Instant) -> Self {
+    Self { speed: 0_f64, since: now, distance: 0_f64 }
+  }
+
+  /// 左右轮的占空比变化时调用, -1 到 1, 负数为后退
+  pub fn set(&mut self, left: f64, right: f64, now: Instant) {
+    self.distance = self.distance(now);
+    self.speed = (left + right) / 2_f64;
+    self.since = now;
+  }
+
+  /// 累计行驶的距离 cm, 后退时减少, 原地转向不计入
+  pub fn distance(&self, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(self.since).as_secs_f64();
+    self.distance + self.speed * config::MISSION_CM_PER_SEC * elapsed
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::{Duration, Instant};
+
+  use super::Odometer;
+  use crate::config;
+
+  #[test]
+  fn test_distance() {
+    let now = Instant::now();
+    let at = |ms| now + Duration::from_millis(ms);
+    let mut odometer = Odometer::new(now);
+    odometer.set(0.5, 0.5, now);
+    assert_eq!(odometer.distance(at(1000)), config::MISSION_CM_PER_SEC / 2_f64);
+
+    // 原地转向不计入
+    odometer.set(-1_f64, 1_f64, at(1000));
+    assert_eq!(odometer.distance(at(3000)), config::MISSION_CM_PER_SEC / 2_f64);
+
+    odometer.set(-0.5, -0.5, at(3000));
+    assert_eq!(odometer.distance(at(4000)), 0_f64);
+  }
+}
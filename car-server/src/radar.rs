@@ -0,0 +1,55 @@
+//! 雷达扫描: 舵机带动超声波在一段弧度内转动, 得到极坐标距离图
+
+use car_utils::Scan;
+
+use crate::config;
+
+/// 一次扫描的弧度范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sweep {
+  from: u8,
+  to: u8,
+  step: u8,
+}
+
+impl Sweep {
+  pub const MAX_ANGLE: u8 = 180;
+
+  /// 规范化扫描范围: from <= to <= 180, 且点数不超过 config::SCAN_MAX_POINTS
+  pub fn new(from: u8, to: u8, step: u8) -> Self {
+    let (from, to) = (from.min(to), to.max(from).min(Self::MAX_ANGLE));
+    let from = from.min(to);
+    let min_step = (to - from).div_ceil(config::SCAN_MAX_POINTS as u8 - 1).max(1);
+
+    Self { from, to, step: step.max(min_step) }
+  }
+
+  /// 扫描经过的角度 (从小到大)
+  pub fn angles(&self) -> impl DoubleEndedIterator<Item = u8> {
+    (self.from..=self.to).step_by(self.step as usize)
+  }
+
+  /// 将按角度从小到大排列的测量结果组装为扫描结果
+  pub fn to_scan(self, distances: Vec<Option<u16>>) -> Scan {
+    Scan { from: self.from, step: self.step, distances }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Sweep;
+  use crate::config;
+
+  #[test]
+  fn test_normalize() {
+    let sweep = Sweep::new(150, 30, 10);
+    assert_eq!(sweep.angles().collect::<Vec<_>>(), vec![30, 40, 50, 60, 70, 80, 90, 100, 110, 120, 130, 140, 150]);
+
+    let sweep = Sweep::new(0, 255, 0);
+    assert_eq!(sweep.angles().last(), Some(180));
+    assert!(sweep.angles().count() <= config::SCAN_MAX_POINTS);
+
+    let sweep = Sweep::new(90, 90, 5);
+    assert_eq!(sweep.angles().collect::<Vec<_>>(), vec![90]);
+  }
+}
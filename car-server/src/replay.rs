@@ -0,0 +1,93 @@
+//! 离线寻迹: 对图片目录或视频逐帧运行寻迹算法, 将标注后的画面写入输出目录
+//!
+//! car-server trace <图片目录或视频> <输出目录> [算法, 默认使用配置文件中的算法]
+
+use std::{fs, io, path::Path, time::Duration};
+
+use car_utils::{Marker, TraceAlgorithm};
+use log::info;
+use opencv::{
+  core::{Mat, MatTraitConst, Point, Scalar, Vector},
+  imgcodecs, imgproc,
+  videoio::{VideoCapture, VideoCaptureTrait, CAP_ANY},
+};
+
+use crate::{camera::images, config, marker::Signals, settings::Settings, steering::Steering, trace};
+
+pub fn run(args: &[String]) -> io::Result<()> {
+  let (Some(input), Some(output)) = (args.first(), args.get(1)) else {
+    return Err(io::Error::other("用法: car-server trace <图片目录或视频> <输出目录> [算法]"));
+  };
+  let settings = Settings::load();
+  let algorithm = match args.get(2) {
+    Some(name) => serde_json::from_value::<TraceAlgorithm>(serde_json::json!(name))
+      .map_err(|_| io::Error::other(format!("未知的寻迹算法: {}", name)))?,
+    None => settings.trace_algorithm,
+  };
+  fs::create_dir_all(output)?;
+
+  let mut follower = trace::follower(algorithm, &settings);
+  let (kp, ki, kd) = config::TRACE_PID;
+  let mut steering = Steering::new((kp as f64, ki as f64, kd as f64));
+  let detector = trace::MarkerDetector::new(settings.markers.clone());
+  let mut signals = Signals::default();
+
+  let mut process = |name: &str, mut frame: Mat| -> io::Result<()> {
+    let marker = detector.detect(&frame).map_err(io::Error::other)?;
+    let error = follower.follow(&mut frame).map_err(io::Error::other)?;
+    if let Some(marker) = marker {
+      trace::draw_marker(&mut frame, marker).map_err(io::Error::other)?;
+    }
+    signals.update(marker);
+    // 按 30 帧/秒计算
+    let (left, right) = match signals.active() {
+      Some(Marker::Stop) => (0_f64, 0_f64),
+      _ => steering.update(error, 0.5 * signals.speed_factor(), Duration::from_millis(33)),
+    };
+    println!("{}\t{:?}\t{:?}\t{:.2}\t{:.2}", name, error, signals.active(), left, right);
+
+    let text = match error {
+      Some(error) => format!("{:?} error {:+.3} L {:+.2} R {:+.2}", algorithm, error, left, right),
+      None => format!("{:?} lost L {:+.2} R {:+.2}", algorithm, left, right),
+    };
+    imgproc::put_text(
+      &mut frame,
+      &text,
+      Point::new(5, 15),
+      imgproc::FONT_HERSHEY_SIMPLEX,
+      0.4,
+      Scalar::new(0.0, 0.0, 255.0, 0.0),
+      1,
+      imgproc::LINE_AA,
+      false,
+    )
+    .map_err(io::Error::other)?;
+
+    let path = Path::new(output).join(name).with_extension("png");
+    imgcodecs::imwrite(&path.to_string_lossy(), &frame, &Vector::new()).map_err(io::Error::other)?;
+    Ok(())
+  };
+
+  let input = Path::new(input);
+  if input.is_dir() {
+    for path in images(input)? {
+      let frame = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_COLOR).map_err(io::Error::other)?;
+      if frame.empty() {
+        info!("无法读取: {}", path.display());
+        continue;
+      }
+      process(&path.file_stem().unwrap_or_default().to_string_lossy(), frame)?;
+    }
+  } else {
+    let mut cap = VideoCapture::from_file(&input.to_string_lossy(), CAP_ANY).map_err(io::Error::other)?;
+    for index in 0.. {
+      let mut frame = Mat::default();
+      if !cap.read(&mut frame).map_err(io::Error::other)? || frame.empty() {
+        break;
+      }
+      process(&format!("frame_{:05}", index), frame)?;
+    }
+  }
+
+  Ok(())
+}
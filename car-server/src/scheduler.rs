@@ -0,0 +1,235 @@
+//! 传感器调度: 每个传感器按各自的周期读取, 同一线程中依次执行
+//!
+//! 同时到期时优先级高的先执行; 低优先级任务的预计耗时 (timeout) 会使更高优先级的任务错过到期时间时推迟执行,
+//! 推迟超过一个周期后不再推迟, 避免饿死. 执行失败时可按退避时间重试, 重试不阻塞其它任务,
+//! 第一次重试的等待时间不应小于传感器的最小采样间隔
+
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+pub struct Task<'a> {
+  name: &'static str,
+  period: Duration,
+  priority: u8,
+  timeout: Duration,                  // 单次执行的预计最长时间, 实际超过时警告
+  retries: usize,                     // 失败后的重试次数
+  backoff: Duration,                  // 第一次重试前的等待时间, 之后每次加倍
+  due: Instant,                       // 按周期到期的时间
+  next: Instant,                      // 下一次执行的时间, 重试时早于下一个周期
+  failures: usize,                    // 连续失败的次数
+  run: Box<dyn FnMut() -> bool + 'a>, // 返回是否成功
+}
+
+impl<'a> Task<'a> {
+  pub fn new(
+    name: &'static str,
+    period: Duration,
+    priority: u8,
+    timeout: Duration,
+    run: impl FnMut() -> bool + 'a,
+  ) -> Self {
+    Self {
+      name,
+      period,
+      priority,
+      timeout,
+      retries: 0,
+      backoff: Duration::ZERO,
+      due: Instant::now(),
+      next: Instant::now(),
+      failures: 0,
+      run: Box::new(run),
+    }
+  }
+
+  /// 失败后最多重试 retries 次, 重试晚于下一个周期时不再重试
+  #[cfg(any(feature = "rasp", test))]
+  pub fn retry(mut self, retries: usize, backoff: Duration) -> Self {
+    self.retries = retries;
+    self.backoff = backoff;
+    self
+  }
+}
+
+#[derive(Default)]
+pub struct Scheduler<'a> {
+  tasks: Vec<Task<'a>>,
+}
+
+impl<'a> Scheduler<'a> {
+  /// 注册任务, 注册后立即到期
+  pub fn register(&mut self, task: Task<'a>) {
+    self.tasks.push(task);
+  }
+
+  /// 执行一个到期的任务, 返回下一次需要调用的时间
+  pub fn tick(&mut self, now: Instant) -> Instant {
+    let mut due: Vec<usize> = (0..self.tasks.len()).filter(|&i| self.tasks[i].next <= now).collect();
+    due.sort_by_key(|&i| (std::cmp::Reverse(self.tasks[i].priority), self.tasks[i].next));
+
+    let selected = due.into_iter().find(|&i| {
+      let task = &self.tasks[i];
+      // 更高优先级的任务最早的到期时间
+      let deadline = self.tasks.iter().filter(|other| other.priority > task.priority).map(|other| other.next).min();
+      now.duration_since(task.next) >= task.period || deadline.is_none_or(|deadline| now + task.timeout <= deadline)
+    });
+
+    let Some(i) = selected else {
+      // 没有可以执行的任务, 等到下一个任务到期
+      return self.tasks.iter().map(|task| task.next).filter(|next| *next > now).min().unwrap_or(now);
+    };
+
+    let task = &mut self.tasks[i];
+    let start = Instant::now();
+    let ok = (task.run)();
+    let elapsed = start.elapsed();
+    if elapsed > task.timeout {
+      warn!("{}: 执行 {:?}, 超过 {:?}", task.name, elapsed, task.timeout);
+    }
+
+    let due = (task.due + task.period).max(now);
+    // 重试从本次执行开始计时, 不早于传感器记录的上一次读取
+    let retry = start.max(now) + task.backoff * 2_u32.pow(task.failures as u32);
+    if !ok && task.failures < task.retries && retry < due {
+      task.failures += 1;
+      task.next = retry;
+    } else {
+      task.failures = 0;
+      task.due = due;
+      task.next = due;
+    }
+    now
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+  };
+
+  use super::{Scheduler, Task};
+
+  fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+  }
+
+  /// 设置任务的到期时间
+  fn schedule(scheduler: &mut Scheduler, i: usize, at: Instant) {
+    scheduler.tasks[i].due = at;
+    scheduler.tasks[i].next = at;
+  }
+
+  #[test]
+  fn test_priority() {
+    let runs = RefCell::new(Vec::new());
+    let mut scheduler = Scheduler::default();
+    let start = Instant::now();
+    scheduler.register(Task::new("low", ms(1000), 0, ms(30), || {
+      runs.borrow_mut().push("low");
+      true
+    }));
+    scheduler.register(Task::new("high", ms(60), 1, ms(10), || {
+      runs.borrow_mut().push("high");
+      true
+    }));
+
+    schedule(&mut scheduler, 0, start);
+    schedule(&mut scheduler, 1, start);
+    let now = start + ms(1);
+    assert_eq!(scheduler.tick(now), now);
+    assert_eq!(scheduler.tick(now), now);
+    assert_eq!(*runs.borrow(), ["high", "low"]);
+
+    // 低优先级的任务不能在高优先级的任务到期前完成时推迟
+    let mut scheduler = Scheduler::default();
+    scheduler.register(Task::new("low", ms(1000), 0, ms(30), || {
+      runs.borrow_mut().push("low");
+      true
+    }));
+    scheduler.register(Task::new("high", ms(60), 1, ms(10), || {
+      runs.borrow_mut().push("high");
+      true
+    }));
+    runs.borrow_mut().clear();
+    schedule(&mut scheduler, 0, start);
+    schedule(&mut scheduler, 1, start + ms(20));
+    assert_eq!(scheduler.tick(start), start + ms(20));
+    assert!(runs.borrow().is_empty());
+    scheduler.tick(start + ms(20));
+    scheduler.tick(start + ms(21));
+    assert_eq!(*runs.borrow(), ["high", "low"]);
+  }
+
+  #[test]
+  fn test_starvation() {
+    let runs = RefCell::new(0);
+    let mut scheduler = Scheduler::default();
+    let start = Instant::now();
+    scheduler.register(Task::new("low", ms(100), 0, ms(50), || {
+      *runs.borrow_mut() += 1;
+      true
+    }));
+    scheduler.register(Task::new("high", ms(20), 1, ms(0), || true));
+    schedule(&mut scheduler, 0, start);
+
+    // 高优先级的任务每 20 ms 到期, 低优先级的任务推迟一个周期后执行
+    for t in (0..100).step_by(20) {
+      schedule(&mut scheduler, 1, start + ms(t + 20));
+      scheduler.tick(start + ms(t));
+    }
+    assert_eq!(*runs.borrow(), 0);
+    schedule(&mut scheduler, 1, start + ms(120));
+    scheduler.tick(start + ms(100));
+    assert_eq!(*runs.borrow(), 1);
+  }
+
+  #[test]
+  fn test_failure() {
+    let runs = RefCell::new(0);
+    let mut scheduler = Scheduler::default();
+    let start = Instant::now();
+    scheduler.register(Task::new("th", ms(1000), 0, ms(30), || {
+      *runs.borrow_mut() += 1;
+      false
+    }));
+    schedule(&mut scheduler, 0, start);
+
+    // 没有设置重试时, 失败后等待下一个周期
+    scheduler.tick(start);
+    assert_eq!(scheduler.tick(start + ms(50)), start + ms(1000));
+    scheduler.tick(start + ms(1000));
+    assert_eq!(*runs.borrow(), 2);
+  }
+
+  #[test]
+  fn test_retry() {
+    let runs = RefCell::new(0);
+    let mut scheduler = Scheduler::default();
+    // 晚于当前时间, 使重试从 tick 的参数开始计时
+    let start = Instant::now() + ms(1000);
+    scheduler.register(
+      Task::new("th", ms(5000), 0, ms(30), || {
+        *runs.borrow_mut() += 1;
+        false
+      })
+      .retry(3, ms(1000)),
+    );
+    schedule(&mut scheduler, 0, start);
+
+    scheduler.tick(start);
+    assert_eq!(scheduler.tick(start), start + ms(1000));
+    scheduler.tick(start + ms(1000));
+    assert_eq!(scheduler.tick(start + ms(1000)), start + ms(3000));
+    // 第三次重试晚于下一个周期, 等待下一个周期
+    scheduler.tick(start + ms(3000));
+    assert_eq!(scheduler.tick(start + ms(3000)), start + ms(5000));
+    assert_eq!(*runs.borrow(), 3);
+
+    // 下一个周期失败后重新开始重试
+    scheduler.tick(start + ms(5000));
+    assert_eq!(scheduler.tick(start + ms(5000)), start + ms(6000));
+  }
+}
@@ -0,0 +1,245 @@
+//! 脚本: 以 Rhai 编写的自定义行为, 无需重新编译部署即可试验
+//!
+//! 脚本的顶层代码运行一次, 可以用 every(ms, 回调) 注册周期回调, 有回调时脚本持续运行直到停止
+//! 脚本无法访问文件与网络, 单次运行的操作数有上限, 停止脚本时正在运行的代码也会被中断
+
+use std::{
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use car_utils::{command::Navigate, LedEffect, NixieMode, Sound, Statistics};
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, AST};
+
+use crate::config;
+
+/// 脚本可以控制的硬件
+pub trait Host: Send + Sync {
+  fn navigate(&self, navigate: Navigate, speed: u8);
+  /// 左右轮的速度 -1 到 1, 负数为后退
+  fn drive(&self, left: f64, right: f64);
+  fn servo(&self, angle: u8);
+}
+
+/// 周期回调
+struct Periodic {
+  interval: Duration,
+  next: Instant,
+  callback: FnPtr,
+}
+
+pub struct Script {
+  engine: Engine,
+  ast: AST,
+  periodics: Arc<Mutex<Vec<Periodic>>>,
+}
+
+impl Script {
+  /// 编译并运行脚本的顶层代码; running 返回 false 时中断正在运行的代码
+  pub fn start(
+    source: &str,
+    host: Arc<dyn Host>,
+    statistics: Arc<Statistics>,
+    running: impl Fn() -> bool + Send + Sync + 'static,
+  ) -> Result<Self, String> {
+    let periodics = Arc::new(Mutex::new(Vec::new()));
+    let engine = engine(host, statistics, Arc::clone(&periodics), running);
+    let ast = engine.compile(source).map_err(|err| err.to_string())?;
+    engine.run_ast(&ast).map_err(|err| err.to_string())?;
+
+    Ok(Self { engine, ast, periodics })
+  }
+
+  /// 执行到时的周期回调, 返回是否还有周期回调
+  pub fn tick(&mut self, now: Instant) -> Result<bool, String> {
+    // 回调中可能注册新的回调, 执行时不能持有锁
+    let due: Vec<FnPtr> = self
+      .periodics
+      .lock()
+      .unwrap()
+      .iter_mut()
+      .filter(|periodic| periodic.next <= now)
+      .map(|periodic| {
+        periodic.next = now + periodic.interval;
+        periodic.callback.clone()
+      })
+      .collect();
+
+    for callback in due {
+      let _ = callback.call::<Dynamic>(&self.engine, &self.ast, ()).map_err(|err| err.to_string())?;
+    }
+    Ok(!self.periodics.lock().unwrap().is_empty())
+  }
+}
+
+fn engine(
+  host: Arc<dyn Host>,
+  statistics: Arc<Statistics>,
+  periodics: Arc<Mutex<Vec<Periodic>>>,
+  running: impl Fn() -> bool + Send + Sync + 'static,
+) -> Engine {
+  let mut engine = Engine::new();
+
+  // 沙盒
+  engine.disable_symbol("eval");
+  engine.set_max_operations(config::SCRIPT_MAX_OPERATIONS);
+  engine.set_max_call_levels(32);
+  engine.set_max_string_size(4096);
+  engine.set_max_array_size(1024);
+  engine.set_max_map_size(256);
+  engine.on_progress(move |_| (!running()).then_some(Dynamic::UNIT));
+
+  let s = Arc::clone(&statistics);
+  engine.on_print(move |text| s.log(text.to_string()));
+  let s = Arc::clone(&statistics);
+  engine.on_debug(move |text, _, _| s.log(text.to_string()));
+
+  // 读取统计信息, 字段与 Response 一致
+  let s = Arc::clone(&statistics);
+  engine.register_fn("statistics", move || rhai::serde::to_dynamic(s.to_response()));
+  let start = Instant::now();
+  engine.register_fn("millis", move || start.elapsed().as_millis() as i64);
+
+  // 电机与舵机
+  let h = Arc::clone(&host);
+  engine.register_fn("navigate", move |direction: &str, speed: i64| -> Result<(), Box<EvalAltResult>> {
+    let navigate = serde_json::from_value::<Navigate>(serde_json::json!(direction))
+      .map_err(|_| format!("未知的方向: {}", direction))?;
+    h.navigate(navigate, speed.clamp(0, 100) as u8);
+    Ok(())
+  });
+  let h = Arc::clone(&host);
+  engine.register_fn("drive", move |left: f64, right: f64| h.drive(left.clamp(-1.0, 1.0), right.clamp(-1.0, 1.0)));
+  let h = Arc::clone(&host);
+  engine.register_fn("stop", move || h.navigate(Navigate::Brake, 0));
+  let h = Arc::clone(&host);
+  engine.register_fn("servo", move |angle: i64| h.servo(angle.clamp(0, 180) as u8));
+
+  // LED 灯, 数码管, 蜂鸣器
+  let s = Arc::clone(&statistics);
+  engine.register_fn("led", move |r: i64, g: i64, b: i64| {
+    let channel = |value: i64| value.clamp(0, 255) as u8;
+    s.set_led_color((channel(r), channel(g), channel(b)));
+    s.set_led_effect(LedEffect::Solid);
+    s.set_led(true);
+  });
+  let s = Arc::clone(&statistics);
+  engine.register_fn("led_off", move || s.set_led(false));
+  let s = Arc::clone(&statistics);
+  engine.register_fn("nixie", move |text: &str| {
+    s.set_nixie_text(text.to_string());
+    s.set_nixie_mode(NixieMode::Text);
+    s.set_nixie(true);
+  });
+  let s = Arc::clone(&statistics);
+  engine.register_fn("beep", move || s.set_sound(Sound::Beep));
+
+  // 周期回调
+  engine.register_fn("every", move |ms: i64, callback: FnPtr| -> Result<(), Box<EvalAltResult>> {
+    if ms < config::SCRIPT_MIN_INTERVAL_MS as i64 {
+      return Err(format!("回调间隔不能小于 {} ms", config::SCRIPT_MIN_INTERVAL_MS).into());
+    }
+    let interval = Duration::from_millis(ms as u64);
+    periodics.lock().unwrap().push(Periodic { interval, next: Instant::now() + interval, callback });
+    Ok(())
+  });
+
+  engine
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    sync::{
+      atomic::{AtomicBool, Ordering},
+      Arc, Mutex,
+    },
+    time::{Duration, Instant},
+  };
+
+  use car_utils::{command::Navigate, Statistics};
+
+  use super::{Host, Script};
+
+  #[derive(Default)]
+  struct Recorder {
+    calls: Mutex<Vec<String>>,
+  }
+
+  impl Host for Recorder {
+    fn navigate(&self, navigate: Navigate, speed: u8) {
+      self.calls.lock().unwrap().push(format!("navigate {:?} {}", navigate, speed));
+    }
+    fn drive(&self, left: f64, right: f64) {
+      self.calls.lock().unwrap().push(format!("drive {} {}", left, right));
+    }
+    fn servo(&self, angle: u8) {
+      self.calls.lock().unwrap().push(format!("servo {}", angle));
+    }
+  }
+
+  fn start(source: &str) -> (Result<Script, String>, Arc<Recorder>, Arc<Statistics>) {
+    let host = Arc::new(Recorder::default());
+    let statistics = Arc::new(Statistics::default());
+    let script = Script::start(source, host.clone(), statistics.clone(), || true);
+    (script, host, statistics)
+  }
+
+  #[test]
+  fn test_bindings() {
+    let (script, host, statistics) = start(
+      r#"
+        navigate("Forward", 150);
+        drive(0.5, -2.0);
+        servo(45);
+        led(255, 0, 0);
+        print(`speed ${statistics().speed_percent}`);
+      "#,
+    );
+    assert!(script.is_ok());
+    assert_eq!(*host.calls.lock().unwrap(), ["navigate Forward 100", "drive 0.5 -1", "servo 45"]);
+    assert!(statistics.led());
+    assert_eq!(statistics.led_color(), (255, 0, 0));
+    assert_eq!(statistics.logs_since(0), Some((1, vec![(1, "speed 0".to_string())])));
+
+    let (script, ..) = start(r#"navigate("Up", 50);"#);
+    assert!(script.is_err_and(|err| err.contains("Up")));
+    assert!(start("let x = ;").0.is_err());
+  }
+
+  #[test]
+  fn test_every() {
+    let (script, host, _) = start("let n = 0; every(100, || servo(90));");
+    let mut script = script.unwrap();
+    let now = Instant::now();
+    assert!(script.tick(now).unwrap());
+    assert!(host.calls.lock().unwrap().is_empty());
+
+    assert!(script.tick(now + Duration::from_millis(150)).unwrap());
+    assert!(script.tick(now + Duration::from_millis(200)).unwrap());
+    assert!(script.tick(now + Duration::from_millis(300)).unwrap());
+    assert_eq!(host.calls.lock().unwrap().len(), 2);
+
+    // 没有回调的脚本运行完顶层代码即结束
+    let (script, ..) = start("servo(0);");
+    assert!(!script.unwrap().tick(Instant::now()).unwrap());
+  }
+
+  #[test]
+  fn test_sandbox() {
+    // 停止时中断死循环
+    let running = Arc::new(AtomicBool::new(true));
+    let flag = Arc::clone(&running);
+    let handle = std::thread::spawn(move || {
+      Script::start("loop {}", Arc::new(Recorder::default()), Arc::new(Statistics::default()), move || {
+        flag.load(Ordering::SeqCst)
+      })
+      .is_err()
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    running.store(false, Ordering::SeqCst);
+    assert!(handle.join().unwrap());
+
+    assert!(start(r#"eval("1")"#).0.is_err());
+  }
+}
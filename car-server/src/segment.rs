@@ -0,0 +1,183 @@
+//! 4 位 7 段数码管的显示内容
+//!
+//! 段码的第 0-6 位依次为 A-G 段, 第 7 位为小数点 (时钟模式下为冒号)
+
+use std::time::Duration;
+
+use car_utils::{NixieMode, Statistics};
+
+use crate::config;
+
+pub const DIGITS: usize = 4;
+pub const SEG_DP: u8 = 0x80;
+
+/// 4 位的段码
+pub type Segments = [u8; DIGITS];
+
+/// 轮换模式依次显示的内容
+const CYCLE: [NixieMode; 4] = [NixieMode::Clock, NixieMode::Temperature, NixieMode::Humidity, NixieMode::Distance];
+
+/// 字符对应的段码, 无法显示的字符为空白
+pub fn encode_char(c: char) -> u8 {
+  match c {
+    '0' | 'O' => 0x3f,
+    '1' | 'I' => 0x06,
+    '2' | 'Z' | 'z' => 0x5b,
+    '3' => 0x4f,
+    '4' => 0x66,
+    '5' | 'S' | 's' => 0x6d,
+    '6' => 0x7d,
+    '7' => 0x07,
+    '8' | 'B' => 0x7f,
+    '9' | 'g' => 0x6f,
+    'A' | 'a' => 0x77,
+    'b' => 0x7c,
+    'C' => 0x39,
+    'c' => 0x58,
+    'D' | 'd' => 0x5e,
+    'E' | 'e' => 0x79,
+    'F' | 'f' => 0x71,
+    'G' => 0x3d,
+    'H' | 'X' | 'x' => 0x76,
+    'h' => 0x74,
+    'i' => 0x04,
+    'J' | 'j' => 0x1e,
+    'L' | 'l' => 0x38,
+    'N' | 'n' | 'M' | 'm' => 0x54,
+    'o' => 0x5c,
+    'P' | 'p' => 0x73,
+    'Q' | 'q' => 0x67,
+    'R' | 'r' => 0x50,
+    'T' | 't' => 0x78,
+    'U' | 'V' => 0x3e,
+    'u' | 'v' => 0x1c,
+    'Y' | 'y' => 0x6e,
+    '-' => 0x40,
+    '_' => 0x08,
+    '=' => 0x48,
+    '°' => 0x63,
+    _ => 0x00,
+  }
+}
+
+/// 文字对应的段码, '.' 合并到前一位的小数点
+pub fn encode(text: &str) -> Vec<u8> {
+  let mut segments: Vec<u8> = Vec::with_capacity(text.len());
+  for c in text.chars() {
+    match segments.last_mut() {
+      Some(last) if c == '.' && *last & SEG_DP == 0 => *last |= SEG_DP,
+      _ if c == '.' => segments.push(SEG_DP),
+      _ => segments.push(encode_char(c)),
+    }
+  }
+  segments
+}
+
+/// 右对齐显示, 超出的部分被截断
+pub fn fit(segments: &[u8]) -> Segments {
+  let mut digits = [0; DIGITS];
+  let len = segments.len().min(DIGITS);
+  digits[DIGITS - len..].copy_from_slice(&segments[..len]);
+  digits
+}
+
+/// 时钟 HH:MM
+pub fn clock(hour: u8, minute: u8, colon: bool) -> Segments {
+  let mut digits = fit(&encode(&format!("{:02}{:02}", hour, minute)));
+  if colon {
+    digits[1] |= SEG_DP;
+  }
+  digits
+}
+
+/// 带一位小数的数值与单位, 位数不足时省略小数
+fn number(value: f32, unit: char) -> Segments {
+  let segments = encode(&format!("{:.1}{}", value, unit));
+  if segments.len() <= DIGITS {
+    fit(&segments)
+  } else {
+    fit(&encode(&format!("{:.0}{}", value, unit)))
+  }
+}
+
+/// 滚动文字的第 step 帧, 文字不超过 4 位时不滚动
+pub fn scroll(text: &str, step: usize) -> Segments {
+  let mut segments = encode(text);
+  if segments.len() <= DIGITS {
+    return fit(&segments);
+  }
+
+  // 文字末尾留出空白, 循环滚动
+  segments.extend([0; DIGITS]);
+  let mut digits = [0; DIGITS];
+  for (i, digit) in digits.iter_mut().enumerate() {
+    *digit = segments[(step + i) % segments.len()];
+  }
+  digits
+}
+
+/// 根据显示模式生成 elapsed 时刻显示的内容
+/// time: 本地时间 (时, 分); elapsed: 进入该模式后经过的时间
+pub fn render(mode: NixieMode, statistics: &Statistics, time: (u8, u8), elapsed: Duration) -> Segments {
+  let millis = elapsed.as_millis() as u64;
+  match mode {
+    NixieMode::Clock => clock(time.0, time.1, millis % 1000 < 500),
+    NixieMode::Temperature => number(statistics.temperature(), 'C'),
+    NixieMode::Humidity => number(statistics.humidity(), 'H'),
+    NixieMode::Distance => fit(&encode(&format!("{}c", statistics.distance()))),
+    NixieMode::Speed => fit(&encode(&format!("S{:>3}", statistics.speed()))),
+    NixieMode::Text => scroll(&statistics.nixie_text(), (millis / config::NIXIE_SCROLL_MS) as usize),
+    NixieMode::Segments => statistics.nixie_segments(),
+    NixieMode::Cycle => {
+      let mode = CYCLE[(millis / 1000 / config::NIXIE_CYCLE_SECS) as usize % CYCLE.len()];
+      render(mode, statistics, time, elapsed)
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use super::{clock, encode, fit, render, scroll, SEG_DP};
+  use car_utils::{NixieMode, Statistics};
+
+  #[test]
+  fn test_encode() {
+    assert_eq!(encode("1.2"), vec![0x06 | SEG_DP, 0x5b]);
+    assert_eq!(encode("..1"), vec![SEG_DP, SEG_DP, 0x06]);
+    assert_eq!(fit(&encode("1")), [0, 0, 0, 0x06]);
+    assert_eq!(fit(&encode("12345")), [0x06, 0x5b, 0x4f, 0x66]);
+    assert_eq!(clock(9, 5, true), [0x3f, 0x6f | SEG_DP, 0x3f, 0x6d]);
+  }
+
+  #[test]
+  fn test_scroll() {
+    assert_eq!(scroll("Hi", 3), [0, 0, 0x76, 0x04]);
+    assert_eq!(scroll("HELLO", 0), [0x76, 0x79, 0x38, 0x38]);
+    assert_eq!(scroll("HELLO", 4), [0x3f, 0, 0, 0]);
+    assert_eq!(scroll("HELLO", 9), scroll("HELLO", 0));
+  }
+
+  #[test]
+  fn test_render() {
+    let statistics = Statistics::default();
+    statistics.set_temperature(23.4);
+    statistics.set_humidity(45_f32);
+    statistics.set_distance(400);
+    statistics.set_speed(30);
+
+    let render = |mode| render(mode, &statistics, (12, 30), Duration::ZERO);
+    assert_eq!(render(NixieMode::Temperature), [0x5b, 0x4f | SEG_DP, 0x66, 0x39]);
+    assert_eq!(render(NixieMode::Humidity), [0x66, 0x6d | SEG_DP, 0x3f, 0x76]);
+    assert_eq!(render(NixieMode::Distance), [0x66, 0x3f, 0x3f, 0x58]);
+    assert_eq!(render(NixieMode::Speed), [0x6d, 0, 0x4f, 0x3f]);
+    assert_eq!(render(NixieMode::Cycle), render(NixieMode::Clock));
+
+    statistics.set_temperature(-10.1);
+    assert_eq!(render(NixieMode::Temperature), [0x40, 0x06, 0x3f, 0x39]);
+
+    statistics.set_nixie_segments([1, 2, 4, 8]);
+    assert_eq!(render(NixieMode::Segments), [1, 2, 4, 8]);
+  }
+}
@@ -0,0 +1,160 @@
+//! 运行时配置: 从配置文件读取, 文件不存在时使用默认值
+//!
+//! 配置文件默认为工作目录下的 car-server.json, 可通过环境变量 CAR_SERVER_CONFIG 指定
+
+use ::config::{Config, ConfigError, File, FileFormat};
+use car_utils::{Marker, TraceAlgorithm};
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::{alarm::AlarmRule, led::StatusRule};
+
+pub const DEFAULT_PATH: &str = "car-server.json";
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+  pub led_status_on_boot: bool,        // 启动时是否以状态指示效果打开 LED 灯
+  pub led_status: Vec<StatusRule>,     // 状态指示规则, 按顺序匹配第一条
+  pub time_zone: Option<String>,       // 时区, 如 "Asia/Shanghai", 默认使用系统时区
+  pub trace_algorithm: TraceAlgorithm, // 默认的寻迹算法
+  pub trace_tape: HsvRange,            // 按颜色寻迹时胶带的颜色范围
+  pub camera: CameraSettings,          // 寻迹摄像头
+  pub markers: Vec<MarkerRule>,        // 赛道标志的颜色, 为空时不识别标志
+  pub macros_dir: String,              // 保存手动驾驶录制的目录
+  pub alarms: Vec<AlarmRule>,          // 环境报警规则
+}
+
+/// 赛道标志与其颜色, 红色跨越色相两端, 可以配置多个范围
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MarkerRule {
+  pub marker: Marker,
+  pub ranges: Vec<HsvRange>,
+}
+
+impl MarkerRule {
+  /// 默认红色停车、绿色通行、黄色减速, 与默认的蓝色胶带不重叠
+  pub fn defaults() -> Vec<Self> {
+    let range = |lower, upper| HsvRange { lower, upper };
+    vec![
+      Self {
+        marker: Marker::Stop,
+        ranges: vec![range((0, 120, 70), (10, 255, 255)), range((170, 120, 70), (180, 255, 255))],
+      },
+      Self { marker: Marker::Go, ranges: vec![range((40, 80, 60), (80, 255, 255))] },
+      Self { marker: Marker::Slow, ranges: vec![range((20, 120, 100), (35, 255, 255))] },
+    ]
+  }
+}
+
+/// 摄像头画面来源与参数
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+  pub source: CameraSource,
+  pub width: u32,
+  pub height: u32,
+  pub fps: u32,
+}
+
+/// 画面来源
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CameraSource {
+  Device { index: i32 },  // 摄像头设备编号
+  Video { path: String }, // 视频文件, 循环播放
+  Images { dir: String }, // 图片目录, 按文件名循环
+  TestPattern,            // 合成的测试画面
+}
+
+impl Default for CameraSettings {
+  fn default() -> Self {
+    Self { source: CameraSource::Device { index: 0 }, width: 320, height: 240, fps: 30 }
+  }
+}
+
+/// HSV 颜色范围, 与 OpenCV 一致: H 0-180, S 与 V 0-255
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct HsvRange {
+  pub lower: (u8, u8, u8),
+  pub upper: (u8, u8, u8),
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      led_status_on_boot: true,
+      led_status: StatusRule::defaults(),
+      time_zone: None,
+      trace_algorithm: TraceAlgorithm::Contour,
+      trace_tape: HsvRange { lower: (100, 120, 60), upper: (130, 255, 255) }, // 蓝色
+      camera: CameraSettings::default(),
+      markers: MarkerRule::defaults(),
+      macros_dir: "macros".to_string(),
+      alarms: AlarmRule::defaults(),
+    }
+  }
+}
+
+impl Settings {
+  /// 加载配置, 失败时使用默认配置
+  pub fn load() -> Self {
+    let path = std::env::var("CAR_SERVER_CONFIG").unwrap_or_else(|_| DEFAULT_PATH.to_string());
+    Self::load_from(&path)
+      .inspect(|_| info!("config: {}", path))
+      .inspect_err(|err| warn!("config: {}, 使用默认配置", err))
+      .unwrap_or_default()
+  }
+
+  fn load_from(path: &str) -> Result<Self, ConfigError> {
+    Config::builder().add_source(File::new(path, FileFormat::Json).required(false)).build()?.try_deserialize()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{CameraSource, HsvRange, MarkerRule, Settings};
+  use crate::led::{StatusCondition, StatusRule};
+  use car_utils::{Marker, TraceAlgorithm};
+
+  #[test]
+  fn test_load() {
+    let settings = Settings::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/car-server.example.json")).unwrap();
+    assert!(settings.led_status_on_boot);
+    assert_eq!(settings.time_zone.as_deref(), Some("Asia/Shanghai"));
+    assert_eq!(settings.trace_algorithm, TraceAlgorithm::SlidingWindow);
+    assert_eq!(settings.trace_tape, HsvRange { lower: (20, 100, 100), upper: (35, 255, 255) });
+    assert_eq!(settings.camera.source, CameraSource::Device { index: 0 });
+    assert_eq!((settings.camera.width, settings.camera.height, settings.camera.fps), (640, 480, 15));
+    assert_eq!(settings.markers.len(), 2);
+    assert_eq!(settings.markers[1].marker, Marker::Go);
+    assert_eq!(settings.led_status.len(), 6);
+    assert_eq!(
+      settings.led_status[2],
+      StatusRule { when: StatusCondition::Disconnected, color: (255, 255, 0), blink: false }
+    );
+
+    // 文件不存在时使用默认配置
+    let settings = Settings::load_from("not-exists.json").unwrap();
+    assert_eq!(settings.led_status, StatusRule::defaults());
+    assert_eq!(settings.camera.fps, 30);
+    assert_eq!(settings.markers, MarkerRule::defaults());
+  }
+
+  #[test]
+  fn test_markers() {
+    // 默认标志的颜色不能与默认胶带的颜色重叠, 否则会把胶带识别为标志
+    let tape = Settings::default().trace_tape;
+    let overlaps = |a: &HsvRange, b: &HsvRange| {
+      a.lower.0 <= b.upper.0
+        && b.lower.0 <= a.upper.0
+        && a.lower.1 <= b.upper.1
+        && b.lower.1 <= a.upper.1
+        && a.lower.2 <= b.upper.2
+        && b.lower.2 <= a.upper.2
+    };
+    for rule in MarkerRule::defaults() {
+      assert!(rule.ranges.iter().all(|range| !overlaps(range, &tape)), "{:?}", rule.marker);
+    }
+  }
+}
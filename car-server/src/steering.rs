@@ -0,0 +1,143 @@
+//! 寻迹的转向控制
+//!
+//! PID 控制器将线的横向偏差转换为左右轮的差速; 丢线后向最后一次看到线的一侧原地旋转寻找,
+//! 超过一定时间仍未找到则停车
+
+use std::time::Duration;
+
+use crate::config;
+
+/// PID 控制器
+#[derive(Debug, Default)]
+pub struct Pid {
+  pub gains: (f64, f64, f64), // kp, ki, kd
+  integral: f64,
+  last_error: Option<f64>,
+}
+
+impl Pid {
+  pub fn new(gains: (f64, f64, f64)) -> Self {
+    Self { gains, ..Default::default() }
+  }
+
+  /// 根据偏差与距上一次的时间计算输出
+  pub fn update(&mut self, error: f64, dt: Duration) -> f64 {
+    let (kp, ki, kd) = self.gains;
+    let dt = dt.as_secs_f64();
+
+    let limit = config::TRACE_PID_INTEGRAL_LIMIT;
+    self.integral = (self.integral + error * dt).clamp(-limit, limit);
+    let derivative = match self.last_error {
+      Some(last_error) if dt > 0_f64 => (error - last_error) / dt,
+      _ => 0_f64,
+    };
+    self.last_error = Some(error);
+
+    kp * error + ki * self.integral + kd * derivative
+  }
+
+  pub fn reset(&mut self) {
+    self.integral = 0_f64;
+    self.last_error = None;
+  }
+}
+
+/// 寻迹转向
+#[derive(Debug, Default)]
+pub struct Steering {
+  pub pid: Pid,
+  last_side: Option<f64>, // 最后一次看到线时偏差的符号
+  lost: Option<Duration>, // 已丢线的时间
+}
+
+impl Steering {
+  pub fn new(gains: (f64, f64, f64)) -> Self {
+    Self { pid: Pid::new(gains), ..Default::default() }
+  }
+
+  /// 是否已丢线
+  #[cfg(any(feature = "rasp", test))]
+  pub fn lost(&self) -> bool {
+    self.lost.is_some()
+  }
+
+  /// 根据线的横向偏差计算左右轮的占空比, -1 到 1, 负数为后退
+  /// error: 线相对画面中心的偏差, -1 (最左) 到 1 (最右), 没有识别到线时为 None; speed: 基础速度 0-1
+  /// 与原有的导航方向一致, 偏差为负时向右转
+  pub fn update(&mut self, error: Option<f64>, speed: f64, dt: Duration) -> (f64, f64) {
+    match error {
+      Some(error) => {
+        self.lost = None;
+        if error != 0_f64 {
+          self.last_side = Some(error.signum());
+        }
+
+        let correction = self.pid.update(error, dt);
+        ((speed - correction).clamp(-1_f64, 1_f64), (speed + correction).clamp(-1_f64, 1_f64))
+      }
+      None => {
+        self.pid.reset();
+        let lost = self.lost.map_or(Duration::ZERO, |lost| lost + dt);
+        self.lost = Some(lost);
+
+        // 原地旋转寻找, 超时或从未看到过线时停车
+        let search = speed * config::TRACE_SEARCH_SPEED;
+        match self.last_side {
+          _ if lost >= Duration::from_millis(config::TRACE_SEARCH_TIMEOUT_MS) => (0_f64, 0_f64),
+          Some(side) if side < 0_f64 => (search, -search),
+          Some(_) => (-search, search),
+          None => (0_f64, 0_f64),
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use super::{Pid, Steering};
+  use crate::config;
+
+  #[test]
+  fn test_pid() {
+    let dt = Duration::from_millis(100);
+    let mut pid = Pid::new((1_f64, 0_f64, 0_f64));
+    assert_eq!(pid.update(0.5, dt), 0.5);
+
+    let mut pid = Pid::new((0_f64, 1_f64, 0_f64));
+    pid.update(0.5, dt);
+    assert!((pid.update(0.5, dt) - 0.1).abs() < 1e-9);
+
+    let mut pid = Pid::new((0_f64, 0_f64, 1_f64));
+    assert_eq!(pid.update(0.5, dt), 0_f64);
+    assert!((pid.update(0.6, dt) - 1_f64).abs() < 1e-9);
+    pid.reset();
+    assert_eq!(pid.update(0.6, dt), 0_f64);
+  }
+
+  #[test]
+  fn test_steering() {
+    let dt = Duration::from_millis(100);
+    let mut steering = Steering::new((0.5, 0_f64, 0_f64));
+    assert_eq!(steering.update(Some(0_f64), 0.5, dt), (0.5, 0.5));
+    // 偏差为负时左轮快, 向右转
+    assert_eq!(steering.update(Some(-0.4), 0.5, dt), (0.7, 0.3));
+    assert!(!steering.lost());
+
+    // 丢线后向最后看到线的一侧旋转
+    let search = 0.5 * config::TRACE_SEARCH_SPEED;
+    assert_eq!(steering.update(None, 0.5, dt), (search, -search));
+    assert!(steering.lost());
+
+    // 超时停车
+    let timeout = Duration::from_millis(config::TRACE_SEARCH_TIMEOUT_MS);
+    assert_eq!(steering.update(None, 0.5, timeout), (0_f64, 0_f64));
+
+    // 重新找到线
+    steering.update(Some(0.2), 0.5, dt);
+    assert!(!steering.lost());
+    assert_eq!(steering.update(None, 0.5, dt), (-search, search));
+  }
+}
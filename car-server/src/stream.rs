@@ -0,0 +1,166 @@
+//! 摄像头推流: 通过 HTTP 以 MJPEG 格式发送最新的画面
+//!
+//! 浏览器或控制器端访问 http://<小车地址>:<STREAM_PORT>/ 即可观看
+
+use std::{
+  io::{self, Write},
+  sync::{Arc, Condvar, Mutex},
+  time::Duration,
+};
+
+#[cfg(feature = "rasp")]
+use log::{debug, info};
+#[cfg(feature = "rasp")]
+use std::{
+  io::Read,
+  net::{TcpListener, TcpStream, ToSocketAddrs},
+  sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+  thread::{self, JoinHandle},
+};
+
+const BOUNDARY: &str = "frame";
+
+/// 最新的一帧 JPEG 画面
+#[derive(Default)]
+pub struct Frames {
+  latest: Mutex<(u64, Option<Arc<Vec<u8>>>)>, // (版本, 画面), 每发布一帧版本加一
+  condvar: Condvar,
+  #[cfg(feature = "rasp")]
+  viewers: AtomicUsize, // 观看的客户端数量
+}
+
+impl Frames {
+  /// 发布一帧
+  pub fn publish(&self, jpeg: Vec<u8>) {
+    let mut latest = self.latest.lock().unwrap();
+    *latest = (latest.0 + 1, Some(Arc::new(jpeg)));
+    self.condvar.notify_all();
+  }
+
+  /// 等待比 version 新的一帧, 超时返回 None
+  pub fn wait_newer(&self, version: u64, timeout: Duration) -> Option<(u64, Arc<Vec<u8>>)> {
+    let latest = self.latest.lock().unwrap();
+    let (latest, _) = self.condvar.wait_timeout_while(latest, timeout, |(v, _)| *v <= version).unwrap();
+    match &*latest {
+      (v, Some(jpeg)) if *v > version => Some((*v, Arc::clone(jpeg))),
+      _ => None,
+    }
+  }
+
+  /// 是否有客户端在观看
+  #[cfg(feature = "rasp")]
+  pub fn has_viewers(&self) -> bool {
+    self.viewers.load(Ordering::SeqCst) > 0
+  }
+
+  /// 客户端开始观看, 返回的 Viewer 释放时观看的客户端数量减一
+  #[cfg(feature = "rasp")]
+  fn viewer_connected(&self) -> Viewer<'_> {
+    self.viewers.fetch_add(1, Ordering::SeqCst);
+    Viewer(self)
+  }
+}
+
+/// 观看的客户端, 见 Frames::viewer_connected
+#[cfg(feature = "rasp")]
+#[must_use]
+struct Viewer<'a>(&'a Frames);
+
+#[cfg(feature = "rasp")]
+impl Drop for Viewer<'_> {
+  fn drop(&mut self) {
+    self.0.viewers.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+/// 启动推流服务, should_shutdown 为 true 时停止接受连接
+#[cfg(feature = "rasp")]
+pub fn serve(
+  addr: impl ToSocketAddrs,
+  frames: Arc<Frames>,
+  should_shutdown: Arc<AtomicBool>,
+) -> io::Result<JoinHandle<()>> {
+  let listener = TcpListener::bind(addr)?;
+  listener.set_nonblocking(true)?;
+  info!("stream on: {:?}", listener.local_addr());
+
+  Ok(thread::spawn(move || {
+    while !should_shutdown.load(Ordering::Acquire) {
+      let stream = match listener.accept() {
+        Ok((stream, _)) => stream,
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+          thread::sleep(Duration::from_millis(100));
+          continue;
+        }
+        Err(err) => {
+          debug!("stream: {}", err);
+          continue;
+        }
+      };
+      let _ = stream.set_nonblocking(false);
+      let frames = Arc::clone(&frames);
+      thread::spawn(move || {
+        let _viewer = frames.viewer_connected();
+        let _ = send_stream(stream, &frames).inspect_err(|err| debug!("stream: {}", err));
+      });
+    }
+  }))
+}
+
+/// 忽略请求内容, 持续发送画面直到连接断开
+#[cfg(feature = "rasp")]
+fn send_stream(mut stream: TcpStream, frames: &Frames) -> io::Result<()> {
+  let mut request = [0; 1024];
+  let _ = stream.read(&mut request)?;
+
+  write!(
+    stream,
+    "HTTP/1.0 200 OK\r\nCache-Control: no-cache\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\n\r\n",
+    BOUNDARY
+  )?;
+
+  let mut version = 0;
+  loop {
+    if let Some((v, jpeg)) = frames.wait_newer(version, Duration::from_secs(1)) {
+      version = v;
+      write_part(&mut stream, &jpeg)?;
+    }
+  }
+}
+
+/// 写入 multipart 的一部分
+fn write_part(writer: &mut impl Write, jpeg: &[u8]) -> io::Result<()> {
+  write!(writer, "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", BOUNDARY, jpeg.len())?;
+  writer.write_all(jpeg)?;
+  writer.write_all(b"\r\n")?;
+  writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+  use std::{sync::Arc, thread, time::Duration};
+
+  use super::{write_part, Frames};
+
+  #[test]
+  fn test_frames() {
+    let frames = Arc::new(Frames::default());
+    assert!(frames.wait_newer(0, Duration::from_millis(10)).is_none());
+
+    let publisher = thread::spawn({
+      let frames = Arc::clone(&frames);
+      move || frames.publish(vec![1, 2, 3])
+    });
+    let (version, jpeg) = frames.wait_newer(0, Duration::from_secs(1)).unwrap();
+    publisher.join().unwrap();
+    assert_eq!((version, jpeg.as_slice()), (1, [1, 2, 3].as_slice()));
+    assert!(frames.wait_newer(version, Duration::from_millis(10)).is_none());
+  }
+
+  #[test]
+  fn test_write_part() {
+    let mut buf = Vec::new();
+    write_part(&mut buf, &[0xff, 0xd8]).unwrap();
+    assert_eq!(buf, b"--frame\r\nContent-Type: image/jpeg\r\nContent-Length: 2\r\n\r\n\xff\xd8\r\n");
+  }
+}
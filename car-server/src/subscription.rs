@@ -0,0 +1,116 @@
+//! 统计数据的订阅: 每个连接按主题分别设置推送间隔, 只推送订阅的主题中的字段
+//!
+//! 主题有变化时最快每个推送间隔推送一次, 没有变化时至少每 STATISTICS_INTERVAL_MS 推送一次以更新读数的年龄
+
+use std::time::{Duration, Instant};
+
+use car_utils::{Subscription, Topic, TOPICS};
+
+use crate::config;
+
+struct Entry {
+  interval: Duration,
+  version: Option<u32>,    // 已推送的版本, None 为需要推送
+  pushed: Option<Instant>, // 上一次推送的时间
+}
+
+pub struct Subscriptions {
+  topics: [Option<Entry>; TOPICS], // 未订阅的主题为 None
+}
+
+/// 默认订阅全部主题
+impl Default for Subscriptions {
+  fn default() -> Self {
+    let mut subscriptions = Self { topics: Default::default() };
+    subscriptions.subscribe(
+      &Topic::ALL.map(|topic| Subscription { topic, interval_ms: config::STATISTICS_MIN_INTERVAL_MS as u16 }),
+    );
+    subscriptions
+  }
+}
+
+impl Subscriptions {
+  /// 替换订阅, 订阅的主题立即推送一次
+  pub fn subscribe(&mut self, subscriptions: &[Subscription]) {
+    self.topics = Default::default();
+    for subscription in subscriptions {
+      let interval = Duration::from_millis(subscription.interval_ms as u64)
+        .max(Duration::from_millis(config::SUBSCRIPTION_MIN_INTERVAL_MS));
+      self.topics[subscription.topic as usize] = Some(Entry { interval, version: None, pushed: None });
+    }
+  }
+
+  /// 主动获取: 订阅的主题在推送间隔到时推送一次
+  pub fn refresh(&mut self) {
+    self.topics.iter_mut().flatten().for_each(|entry| entry.version = None);
+  }
+
+  /// 需要推送的主题
+  pub fn due(&self, versions: [u32; TOPICS], now: Instant) -> Vec<Topic> {
+    let heartbeat = Duration::from_millis(config::STATISTICS_INTERVAL_MS);
+    Topic::ALL
+      .into_iter()
+      .filter(|&topic| {
+        self.topics[topic as usize].as_ref().is_some_and(|entry| {
+          let elapsed = entry.pushed.map_or(Duration::MAX, |pushed| now.saturating_duration_since(pushed));
+          elapsed >= entry.interval
+            && (entry.version != Some(versions[topic as usize]) || elapsed >= entry.interval.max(heartbeat))
+        })
+      })
+      .collect()
+  }
+
+  /// 记录已推送的主题
+  pub fn pushed(&mut self, topics: &[Topic], versions: [u32; TOPICS], now: Instant) {
+    for &topic in topics {
+      if let Some(entry) = self.topics[topic as usize].as_mut() {
+        entry.version = Some(versions[topic as usize]);
+        entry.pushed = Some(now);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::{Duration, Instant};
+
+  use car_utils::{Subscription, Topic};
+
+  use super::Subscriptions;
+
+  fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+  }
+
+  #[test]
+  fn test_due() {
+    let start = Instant::now();
+    let mut subscriptions = Subscriptions::default();
+    assert_eq!(subscriptions.due([0; 4], start), Topic::ALL);
+    subscriptions.pushed(&Topic::ALL, [0; 4], start);
+    assert!(subscriptions.due([0; 4], start + ms(500)).is_empty());
+    assert_eq!(subscriptions.due([0; 4], start + ms(1000)), Topic::ALL);
+
+    // 距离 20 Hz, 温湿度 0.5 Hz, 不订阅其它主题
+    subscriptions.subscribe(&[
+      Subscription { topic: Topic::Distance, interval_ms: 50 },
+      Subscription { topic: Topic::TH, interval_ms: 2000 },
+    ]);
+    assert_eq!(subscriptions.due([0; 4], start), [Topic::Distance, Topic::TH]);
+    subscriptions.pushed(&[Topic::Distance, Topic::TH], [0; 4], start);
+
+    let versions = [1, 1, 1, 1];
+    assert!(subscriptions.due(versions, start + ms(40)).is_empty());
+    assert_eq!(subscriptions.due(versions, start + ms(50)), [Topic::Distance]);
+    subscriptions.pushed(&[Topic::Distance], versions, start + ms(50));
+    // 没有变化时按 STATISTICS_INTERVAL_MS 推送
+    assert!(subscriptions.due(versions, start + ms(1000)).is_empty());
+    assert_eq!(subscriptions.due(versions, start + ms(1050)), [Topic::Distance]);
+    assert_eq!(subscriptions.due(versions, start + ms(2000)), [Topic::Distance, Topic::TH]);
+
+    subscriptions.refresh();
+    subscriptions.pushed(&[Topic::Distance], versions, start + ms(2000));
+    assert_eq!(subscriptions.due(versions, start + ms(2050)), [Topic::TH]);
+  }
+}
@@ -0,0 +1,62 @@
+//! 巡测: 沿赛道寻迹行驶, 按固定间隔记录温湿度与测量时的位置
+
+use std::time::{Duration, Instant};
+
+use car_utils::Sample;
+
+use crate::config;
+
+#[derive(Debug)]
+pub struct Survey {
+  version: u32, // 对应 Statistics::survey_version
+  start: Instant,
+  origin: f64,           // 开始时的里程 cm
+  last: Option<Instant>, // 上一个样本的时间
+}
+
+impl Survey {
+  pub fn new(version: u32, now: Instant, distance: f64) -> Self {
+    Self { version, start: now, origin: distance, last: None }
+  }
+
+  pub fn version(&self) -> u32 {
+    self.version
+  }
+
+  /// 输入一次温湿度读数与当前里程, 距上一个样本超过 SURVEY_INTERVAL_MS 时返回新样本
+  pub fn sample(&mut self, now: Instant, distance: f64, temperature: f32, humidity: f32) -> Option<Sample> {
+    let interval = Duration::from_millis(config::SURVEY_INTERVAL_MS);
+    if self.last.is_some_and(|last| now.duration_since(last) < interval) {
+      return None;
+    }
+    self.last = Some(now);
+
+    Some(Sample {
+      ms: now.duration_since(self.start).as_millis() as u32,
+      distance: (distance - self.origin) as f32,
+      temperature,
+      humidity,
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::{Duration, Instant};
+
+  use super::Survey;
+
+  #[test]
+  fn test_sample() {
+    let now = Instant::now();
+    let mut survey = Survey::new(1, now, 100_f64);
+    let sample = survey.sample(now, 100_f64, 25_f32, 50_f32).unwrap();
+    assert_eq!((sample.ms, sample.distance), (0, 0_f32));
+
+    // 未到采样间隔
+    assert!(survey.sample(now + Duration::from_millis(500), 120_f64, 25_f32, 50_f32).is_none());
+
+    let sample = survey.sample(now + Duration::from_millis(1000), 160_f64, 26_f32, 48_f32).unwrap();
+    assert_eq!((sample.ms, sample.distance, sample.temperature), (1000, 60_f32, 26_f32));
+  }
+}
@@ -1,67 +1,156 @@
-use car_utils::command::Navigate;
-use opencv::core::{self, Mat, MatExprTraitConst, MatTraitConst, Point, Scalar, Vector};
-use opencv::imgproc;
+//! 寻迹: 从摄像头画面中识别线的位置
 
-pub fn follow_line(frame: &mut Mat) -> Navigate {
-  // 将输入帧转换为灰度图像
+mod color_tape;
+mod contour;
+mod marker;
+mod sliding_window;
+
+use car_utils::TraceAlgorithm;
+use opencv::{
+  core::{self, Mat, MatExprTraitConst, MatTraitConst, Point, Scalar, Vector},
+  imgproc,
+};
+
+use crate::settings::Settings;
+
+use color_tape::ColorTape;
+use contour::Contour;
+pub use marker::{draw_marker, MarkerDetector};
+use sliding_window::SlidingWindow;
+
+/// 寻线算法
+pub trait LineFollower: Send {
+  /// 处理一帧, 返回线相对画面中心的横向偏差, -1 (最左) 到 1 (最右); 没有识别到线时为 None
+  /// 可以在 frame 上绘制调试信息
+  fn follow(&mut self, frame: &mut Mat) -> opencv::Result<Option<f64>>;
+
+  /// 最近一帧的二值化图像, 白色为识别为线的区域
+  fn mask(&self) -> &Mat;
+}
+
+/// 创建寻迹算法
+pub fn follower(algorithm: TraceAlgorithm, settings: &Settings) -> Box<dyn LineFollower> {
+  match algorithm {
+    TraceAlgorithm::Contour => Box::<Contour>::default(),
+    TraceAlgorithm::SlidingWindow => Box::<SlidingWindow>::default(),
+    TraceAlgorithm::ColorTape => Box::new(ColorTape::new(settings.trace_tape)),
+  }
+}
+
+/// 横坐标相对画面中心的偏差
+fn lateral_error(x: f64, width: i32) -> f64 {
+  let center = width as f64 / 2_f64;
+  ((x - center) / center).clamp(-1_f64, 1_f64)
+}
+
+/// 灰度化后反向二值化, 深色的线为白色
+fn threshold(frame: &Mat) -> opencv::Result<Mat> {
   let mut gray = Mat::default();
-  imgproc::cvt_color(frame, &mut gray, imgproc::COLOR_BGR2GRAY, 0).unwrap();
+  imgproc::cvt_color(frame, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
 
-  // 对灰度图像进行二值化处理
   let mut thresh = Mat::default();
-  imgproc::threshold(&gray, &mut thresh, 0.0, 255.0, imgproc::THRESH_BINARY_INV + imgproc::THRESH_OTSU).unwrap();
+  imgproc::threshold(&gray, &mut thresh, 0.0, 255.0, imgproc::THRESH_BINARY_INV + imgproc::THRESH_OTSU)?;
+  Ok(thresh)
+}
 
-  // 创建遮掩层
-  let mut mask = Mat::zeros(thresh.rows(), thresh.cols(), thresh.typ()).unwrap().to_mat().unwrap();
-  let (height, width) = (frame.rows(), frame.cols());
-  let roi_vertices = Vector::from_slice(&[
+/// 画面下方梯形的感兴趣区域
+fn roi_vertices(width: i32, height: i32) -> Vector<Point> {
+  Vector::from_slice(&[
     Point::new(0, height),
     Point::new((width as f64 * 0.45) as i32, (height as f64 * 0.6) as i32),
     Point::new((width as f64 * 0.55) as i32, (height as f64 * 0.6) as i32),
     Point::new(width, height),
-  ]);
+  ])
+}
+
+/// 在画面上标出感兴趣区域
+pub fn draw_roi(frame: &mut Mat) -> opencv::Result<()> {
+  let vertices = roi_vertices(frame.cols(), frame.rows());
+  imgproc::polylines(frame, &vertices, true, Scalar::new(255.0, 255.0, 0.0, 0.0), 1, imgproc::LINE_8, 0)
+}
 
-  imgproc::fill_poly(&mut mask, &roi_vertices, Scalar::all(255.0), imgproc::LINE_8, 0, core::Point::new(0, 0)).unwrap();
+/// 只保留感兴趣区域
+fn apply_roi(mask: &Mat) -> opencv::Result<Mat> {
+  let (height, width) = (mask.rows(), mask.cols());
+  let mut roi = Mat::zeros(height, width, mask.typ())?.to_mat()?;
+  imgproc::fill_poly(&mut roi, &roi_vertices(width, height), Scalar::all(255.0), imgproc::LINE_8, 0, Point::new(0, 0))?;
 
-  // 位运算，只保留感兴趣区域
-  let mut masked_image = Mat::default();
-  core::bitwise_and(&thresh, &mask, &mut masked_image, &core::no_array()).unwrap();
+  let mut masked = Mat::default();
+  core::bitwise_and(mask, &roi, &mut masked, &core::no_array())?;
+  Ok(masked)
+}
 
-  // 查找轮廓
+/// 面积最大的轮廓的质心
+fn largest_centroid(mask: &Mat) -> opencv::Result<Option<Point>> {
   let mut contours = Vector::<Mat>::new();
-  imgproc::find_contours(
-    &masked_image,
-    &mut contours,
-    imgproc::RETR_EXTERNAL,
-    imgproc::CHAIN_APPROX_SIMPLE,
-    Point::new(0, 0),
-  )
-  .unwrap();
-
-  if !contours.is_empty() {
-    // 选出面积最大的轮廓
-    let max_contour = contours.iter().max_by_key(|c| imgproc::contour_area(c, false).unwrap() as i32).unwrap();
-    let moments = imgproc::moments(&max_contour, false).unwrap();
-
-    if moments.m00 > 0.0 {
-      let cx = (moments.m10 / moments.m00) as i32;
-      let cy = (moments.m01 / moments.m00) as i32;
-
-      // 去掉显示质心的代码
-      imgproc::circle(frame, Point::new(cx, cy), 5, Scalar::new(0.0, 0.0, 255.0, 0.0), -1, imgproc::LINE_8, 0).unwrap();
-      let center = width as f64 / 2.0;
-      let deviation_threshold = width as f64 * 0.1;
-
-      if (cx as f64 - center).abs() <= deviation_threshold {
-        return Navigate::Forward;
-      } else if cx < center as i32 {
-        return Navigate::Right;
-      } else {
-        return Navigate::Left;
-      }
+  imgproc::find_contours(mask, &mut contours, imgproc::RETR_EXTERNAL, imgproc::CHAIN_APPROX_SIMPLE, Point::new(0, 0))?;
+
+  let mut largest: Option<(f64, Mat)> = None;
+  for contour in contours {
+    let area = imgproc::contour_area(&contour, false)?;
+    if !matches!(largest, Some((max, _)) if max >= area) {
+      largest = Some((area, contour));
     }
   }
 
-  // 没识别到
-  Navigate::BackWard
+  let Some((_, contour)) = largest else {
+    return Ok(None);
+  };
+  let moments = imgproc::moments(&contour, false)?;
+  if moments.m00 > 0.0 {
+    Ok(Some(Point::new((moments.m10 / moments.m00) as i32, (moments.m01 / moments.m00) as i32)))
+  } else {
+    Ok(None)
+  }
+}
+
+/// 在画面上标出质心
+fn draw_centroid(frame: &mut Mat, centroid: Point) -> opencv::Result<()> {
+  imgproc::circle(frame, centroid, 5, Scalar::new(0.0, 0.0, 255.0, 0.0), -1, imgproc::LINE_8, 0)
+}
+
+#[cfg(test)]
+mod test {
+  use car_utils::TraceAlgorithm;
+  use opencv::{core::MatTraitConst, imgcodecs};
+  use serde::Deserialize;
+
+  use super::follower;
+  use crate::settings::Settings;
+
+  const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/trace");
+  const TOLERANCE: f64 = 0.05;
+
+  /// 测试图片与期望的偏差, 见 tests/fixtures/trace/expected.json
+  #[derive(Deserialize)]
+  struct Fixture {
+    image: String,
+    algorithm: TraceAlgorithm,
+    error: Option<f64>,
+  }
+
+  #[test]
+  fn test_fixtures() {
+    let fixtures: Vec<Fixture> =
+      serde_json::from_str(&std::fs::read_to_string(format!("{}/expected.json", FIXTURES)).unwrap()).unwrap();
+    let settings = Settings::default();
+
+    for fixture in fixtures {
+      let mut frame = imgcodecs::imread(&format!("{}/{}", FIXTURES, fixture.image), imgcodecs::IMREAD_COLOR).unwrap();
+      assert!(!frame.empty(), "{}", fixture.image);
+
+      let error = follower(fixture.algorithm, &settings).follow(&mut frame).unwrap();
+      match (error, fixture.error) {
+        (Some(error), Some(expected)) => assert!(
+          (error - expected).abs() < TOLERANCE,
+          "{} {:?}: {} != {}",
+          fixture.image,
+          fixture.algorithm,
+          error,
+          expected
+        ),
+        (error, expected) => assert_eq!(error, expected, "{} {:?}", fixture.image, fixture.algorithm),
+      }
+    }
+  }
 }
@@ -0,0 +1,46 @@
+//! 按 HSV 颜色范围识别胶带, 取感兴趣区域内最大色块的质心
+//! 适合浅色地面上的彩色胶带, 不受光照明暗的影响
+
+use opencv::{
+  core::{self, Mat, MatTraitConst, Scalar},
+  imgproc,
+};
+
+use super::{apply_roi, draw_centroid, largest_centroid, lateral_error, LineFollower};
+use crate::settings::HsvRange;
+
+pub struct ColorTape {
+  range: HsvRange,
+  mask: Mat,
+}
+
+impl ColorTape {
+  pub fn new(range: HsvRange) -> Self {
+    Self { range, mask: Mat::default() }
+  }
+}
+
+impl LineFollower for ColorTape {
+  fn follow(&mut self, frame: &mut Mat) -> opencv::Result<Option<f64>> {
+    let mut hsv = Mat::default();
+    imgproc::cvt_color(frame, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+
+    let scalar = |(h, s, v): (u8, u8, u8)| Scalar::new(h as f64, s as f64, v as f64, 0.0);
+    let mut mask = Mat::default();
+    core::in_range(&hsv, &scalar(self.range.lower), &scalar(self.range.upper), &mut mask)?;
+
+    self.mask = apply_roi(&mask)?;
+
+    match largest_centroid(&self.mask)? {
+      Some(centroid) => {
+        draw_centroid(frame, centroid)?;
+        Ok(Some(lateral_error(centroid.x as f64, frame.cols())))
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn mask(&self) -> &Mat {
+    &self.mask
+  }
+}
@@ -0,0 +1,28 @@
+//! 二值化后取感兴趣区域内最大轮廓的质心
+
+use opencv::core::{Mat, MatTraitConst};
+
+use super::{apply_roi, draw_centroid, largest_centroid, lateral_error, threshold, LineFollower};
+
+#[derive(Default)]
+pub struct Contour {
+  mask: Mat,
+}
+
+impl LineFollower for Contour {
+  fn follow(&mut self, frame: &mut Mat) -> opencv::Result<Option<f64>> {
+    self.mask = apply_roi(&threshold(frame)?)?;
+
+    match largest_centroid(&self.mask)? {
+      Some(centroid) => {
+        draw_centroid(frame, centroid)?;
+        Ok(Some(lateral_error(centroid.x as f64, frame.cols())))
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn mask(&self) -> &Mat {
+    &self.mask
+  }
+}
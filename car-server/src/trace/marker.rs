@@ -0,0 +1,100 @@
+//! 按 HSV 颜色范围识别赛道标志, 取面积最大且超过阈值的颜色
+
+use car_utils::Marker;
+use opencv::{
+  core::{self, Mat, MatTraitConst, Point, Scalar},
+  imgproc,
+};
+
+use crate::{config, settings::MarkerRule};
+
+pub struct MarkerDetector {
+  rules: Vec<MarkerRule>,
+}
+
+impl MarkerDetector {
+  pub fn new(rules: Vec<MarkerRule>) -> Self {
+    Self { rules }
+  }
+
+  /// 识别画面中的标志, 没有标志时为 None
+  pub fn detect(&self, frame: &Mat) -> opencv::Result<Option<Marker>> {
+    if self.rules.is_empty() {
+      return Ok(None);
+    }
+
+    let mut hsv = Mat::default();
+    imgproc::cvt_color(frame, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+
+    let min_area = (frame.total() as f64 * config::MARKER_MIN_AREA) as i32;
+    let scalar = |(h, s, v): (u8, u8, u8)| Scalar::new(h as f64, s as f64, v as f64, 0.0);
+    let mut largest: Option<(i32, Marker)> = None;
+    for rule in &self.rules {
+      let mut area = 0;
+      for range in &rule.ranges {
+        let mut mask = Mat::default();
+        core::in_range(&hsv, &scalar(range.lower), &scalar(range.upper), &mut mask)?;
+        area += core::count_non_zero(&mask)?;
+      }
+
+      if area >= min_area && !matches!(largest, Some((max, _)) if max >= area) {
+        largest = Some((area, rule.marker));
+      }
+    }
+
+    Ok(largest.map(|(_, marker)| marker))
+  }
+}
+
+/// 在画面右上角标出识别到的标志
+pub fn draw_marker(frame: &mut Mat, marker: Marker) -> opencv::Result<()> {
+  let color = match marker {
+    Marker::Stop => Scalar::new(0.0, 0.0, 255.0, 0.0),
+    Marker::Go => Scalar::new(0.0, 255.0, 0.0, 0.0),
+    Marker::Slow => Scalar::new(0.0, 255.0, 255.0, 0.0),
+  };
+  let origin = Point::new(frame.cols() - 50, 15);
+  imgproc::put_text(
+    frame,
+    &format!("{:?}", marker),
+    origin,
+    imgproc::FONT_HERSHEY_SIMPLEX,
+    0.5,
+    color,
+    1,
+    imgproc::LINE_AA,
+    false,
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use car_utils::Marker;
+  use opencv::imgcodecs;
+
+  use super::MarkerDetector;
+  use crate::settings::MarkerRule;
+
+  const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/trace");
+
+  #[test]
+  fn test_detect() {
+    let detector = MarkerDetector::new(MarkerRule::defaults());
+    for (image, expected) in [
+      ("straight.png", None),
+      ("empty.png", None),
+      ("marker_stop.png", Some(Marker::Stop)),
+      ("marker_go.png", Some(Marker::Go)),
+      ("marker_slow.png", Some(Marker::Slow)),
+      // 胶带的颜色不能识别为标志
+      ("tape_left.png", None),
+    ] {
+      let frame = imgcodecs::imread(&format!("{}/{}", FIXTURES, image), imgcodecs::IMREAD_COLOR).unwrap();
+      assert_eq!(detector.detect(&frame).unwrap(), expected, "{}", image);
+    }
+
+    // 没有配置标志时不识别
+    let frame = imgcodecs::imread(&format!("{}/marker_stop.png", FIXTURES), imgcodecs::IMREAD_COLOR).unwrap();
+    assert_eq!(MarkerDetector::new(Vec::new()).detect(&frame).unwrap(), None);
+  }
+}
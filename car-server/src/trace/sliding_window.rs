@@ -0,0 +1,74 @@
+//! 滑动窗口车道线检测
+//!
+//! 以画面下半部分各列的像素数找到线的起点, 再从下往上逐个移动窗口跟随线, 取各窗口中心的平均位置;
+//! 相比只取质心, 弯道处能更早地反映线的走向
+
+use opencv::{
+  core::{Mat, MatTraitConst, Rect, Scalar},
+  imgproc,
+};
+
+use super::{apply_roi, lateral_error, threshold, LineFollower};
+use crate::config;
+
+#[derive(Default)]
+pub struct SlidingWindow {
+  mask: Mat,
+}
+
+impl LineFollower for SlidingWindow {
+  fn follow(&mut self, frame: &mut Mat) -> opencv::Result<Option<f64>> {
+    self.mask = apply_roi(&threshold(frame)?)?;
+    let (width, height) = (self.mask.cols() as usize, self.mask.rows() as usize);
+    let windows = search(self.mask.data_bytes()?, width, height);
+
+    // 标出各窗口
+    let margin = (width as f64 * config::TRACE_WINDOW_MARGIN) as i32;
+    for &(x, y, window_height) in windows.iter() {
+      let rect = Rect::new(x as i32 - margin, y as i32, margin * 2, window_height as i32);
+      imgproc::rectangle(frame, rect, Scalar::new(0.0, 255.0, 0.0, 0.0), 2, imgproc::LINE_8, 0)?;
+    }
+
+    if windows.is_empty() {
+      return Ok(None);
+    }
+    let x = windows.iter().map(|&(x, _, _)| x).sum::<f64>() / windows.len() as f64;
+    Ok(Some(lateral_error(x, width as i32)))
+  }
+
+  fn mask(&self) -> &Mat {
+    &self.mask
+  }
+}
+
+/// 在二值图中搜索线, 返回识别到线的窗口 (中心横坐标, 上边纵坐标, 高度)
+fn search(mask: &[u8], width: usize, height: usize) -> Vec<(f64, usize, usize)> {
+  let pixel = |x: usize, y: usize| mask[y * width + x] != 0;
+
+  // 起点: 下半部分像素最多的列
+  let base = (0..width).max_by_key(|&x| (height / 2..height).filter(|&y| pixel(x, y)).count());
+  let Some(mut current) = base.filter(|&x| (height / 2..height).any(|y| pixel(x, y))) else {
+    return Vec::new();
+  };
+
+  let margin = (width as f64 * config::TRACE_WINDOW_MARGIN) as usize;
+  let window_height = height / config::TRACE_WINDOWS;
+  let mut windows = Vec::new();
+  for i in 0..config::TRACE_WINDOWS {
+    let (top, bottom) = (height - (i + 1) * window_height, height - i * window_height);
+    let (left, right) = (current.saturating_sub(margin), (current + margin).min(width));
+
+    let (count, sum) = (top..bottom)
+      .flat_map(|y| (left..right).map(move |x| (x, y)))
+      .filter(|&(x, y)| pixel(x, y))
+      .fold((0, 0), |(count, sum), (x, _)| (count + 1, sum + x));
+    if count < config::TRACE_WINDOW_MIN_PIXELS {
+      continue;
+    }
+
+    current = sum / count;
+    windows.push((sum as f64 / count as f64, top, window_height));
+  }
+
+  windows
+}
@@ -0,0 +1,49 @@
+"""生成寻迹测试用的合成赛道图片 (320x240, 浅色地面)
+
+    python3 generate.py
+"""
+
+import struct
+import zlib
+
+WIDTH, HEIGHT = 320, 240
+FLOOR = (200, 200, 200)
+
+
+def write_png(path, pixels):
+    raw = b"".join(b"\x00" + bytes(c for pixel in row for c in pixel) for row in pixels)
+
+    def chunk(kind, data):
+        return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", zlib.crc32(kind + data))
+
+    with open(path, "wb") as f:
+        f.write(b"\x89PNG\r\n\x1a\n")
+        f.write(chunk(b"IHDR", struct.pack(">IIBBBBB", WIDTH, HEIGHT, 8, 2, 0, 0, 0)))
+        f.write(chunk(b"IDAT", zlib.compress(raw)))
+        f.write(chunk(b"IEND", b""))
+
+
+def track(path, center, half_width=10, color=(20, 20, 20), marker=None):
+    """center(y): 线在第 y 行的中心横坐标, None 为没有线; marker: 线右侧 40x40 标志的颜色"""
+    pixels = []
+    for y in range(HEIGHT):
+        x0 = center(y)
+        pixels.append([color if x0 is not None and abs(x - x0) <= half_width else FLOOR for x in range(WIDTH)])
+    if marker is not None:
+        for y in range(100, 140):
+            for x in range(220, 260):
+                pixels[y][x] = marker
+    write_png(path, pixels)
+
+
+if __name__ == "__main__":
+    track("straight.png", lambda y: 160)
+    track("left.png", lambda y: 120)
+    track("right.png", lambda y: 200)
+    # 从画面底部中央向右上方弯曲
+    track("curve_right.png", lambda y: 160 + (HEIGHT - y) * 0.6)
+    track("empty.png", lambda y: None)
+    track("tape_left.png", lambda y: 130, color=(40, 70, 200))
+    track("marker_stop.png", lambda y: 160, marker=(220, 30, 30))
+    track("marker_go.png", lambda y: 160, marker=(30, 180, 60))
+    track("marker_slow.png", lambda y: 160, marker=(230, 200, 30))
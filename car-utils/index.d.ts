@@ -1,3 +1,28 @@
 export * from "./bindings/Command";
 export { Response as Statistics } from "./bindings/Response";
 export * from "./bindings/Navigate";
+export * from "./bindings/Scan";
+export * from "./bindings/AutopilotState";
+export * from "./bindings/Sound";
+export * from "./bindings/LedEffect";
+export * from "./bindings/NixieMode";
+export * from "./bindings/TraceAlgorithm";
+export * from "./bindings/Marker";
+export * from "./bindings/StreamOverlay";
+export * from "./bindings/MissionStep";
+export * from "./bindings/MissionAction";
+export * from "./bindings/MissionState";
+export * from "./bindings/MissionProgress";
+export * from "./bindings/Measurement";
+export * from "./bindings/ScriptAction";
+export * from "./bindings/MacroAction";
+export * from "./bindings/MacroState";
+export * from "./bindings/Metric";
+export * from "./bindings/Alarm";
+export * from "./bindings/Sample";
+export * from "./bindings/SensorError";
+export * from "./bindings/SensorStatus";
+export * from "./bindings/Topic";
+export * from "./bindings/Subscription";
+export * from "./bindings/Ack";
+export * from "./bindings/RequestError";
@@ -60,6 +60,15 @@ impl<const N: usize> RingBuffer<N> {
     }
   }
 
+  /// 查看第 index 个元素
+  pub fn peek(&self, index: usize) -> Option<u8> {
+    if index >= self.len() {
+      None
+    } else {
+      Some(self.buf[self.get_index(index)])
+    }
+  }
+
   pub fn dequeue_one(&mut self) -> Option<u8> {
     if self.is_empty() {
       None
@@ -157,6 +166,9 @@ mod test {
 
     assert_eq!(buffer.dequeue_one(), Some(12));
     assert!(buffer.enqueue_one(13).is_ok());
+    assert_eq!(buffer.peek(11), Some(2));
+    assert_eq!(buffer.peek(12), Some(13));
+    assert_eq!(buffer.peek(13), None);
     assert!(buffer.enqueue_one(13).is_err());
 
     buffer.dequeue_with(buffer.len(), |buf| {
@@ -3,7 +3,12 @@ use num_traits::{FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-#[derive(TS, Serialize, Deserialize, Default, Debug)]
+use crate::{
+  mission::{MissionAction, MissionStep},
+  LedEffect, NixieMode, RequestError, Sound, StreamOverlay, Subscription, TraceAlgorithm, REQUEST_HEADER_LEN,
+};
+
+#[derive(TS, Serialize, Deserialize, Clone, Default, Debug)]
 #[serde(tag = "kind", rename_all = "lowercase")]
 #[ts(export)]
 #[rustfmt::skip]
@@ -13,11 +18,30 @@ pub enum Command {
   Statistics, // 获取统计数据
   Navigate { navigate: Navigate, speed: u8, }, // 控制车方向命令，(方向u8, 速度u8)
   TH { enabled: bool, },                       // 是否开启温湿传感器 (enabledu8,)
-  Nixie { enabled: bool, brightness: u8, },    // 是否开启 数码管 (enabled u8, brightness)
+  Nixie { enabled: bool, brightness: u8, mode: NixieMode, }, // 是否开启 数码管 (enabled u8, brightness, 显示模式 u8)
   Servos { angle: u8, },                       // 舵机 (angle u8, )
-  Trace { enabled: bool, },                    // 是否开启寻迹
+  Trace { enabled: bool, algorithm: TraceAlgorithm, }, // 是否开启寻迹 (enabled u8, 寻迹算法 u8)
   Ultrasonic { enabled: bool, },               // 是否开启超声波测距
-  Led { enabled: bool, },                      // 是否开启 led
+  Led { enabled: bool, effect: LedEffect, color: (u8, u8, u8), brightness: u8, }, // 是否开启 led (enabled u8, 效果 u8, r, g, b, 亮度 0-100)
+  Scan { enabled: bool, from: u8, to: u8, step: u8, }, // 雷达扫描 (enabled u8, 起始角度, 结束角度, 步进角度)
+  Autopilot { enabled: bool, },                // 是否开启自动避障漫游
+  Buzzer { sound: Sound, },                    // 蜂鸣器播放声音, Stop 停止播放 (sound u8, )
+  Melody { melody: String, },                  // 蜂鸣器播放自定义乐谱 (乐谱 utf8, )
+  NixieText { text: String, },                 // 数码管滚动显示的文字 (文字 utf8, )
+  NixieSegments { segments: [u8; 4], },        // 数码管自定义段码 (4 位段码, )
+  SyncTime {
+    #[ts(type = "number")]
+    timestamp: u64,
+  }, // 以客户端时间校准小车时钟 (Unix 时间戳 ms u64 大端, )
+  TracePid { kp: f32, ki: f32, kd: f32, },      // 寻迹 PID 参数 (kp f32 大端, ki, kd)
+  Stream { enabled: bool, overlay: StreamOverlay, }, // 摄像头推流 (enabled u8, 感兴趣区域 u8, 二值化图像 u8, 识别结果 u8)
+  Mission { steps: Vec<MissionStep>, },         // 上传并开始执行任务 (依次为各步骤, 见 MissionStep)
+  MissionControl { action: MissionAction, },    // 暂停, 继续或中止任务 (action u8, )
+  ScriptSource { text: String, },               // 追加脚本源码, 源码较长时分多次发送 (源码 utf8, )
+  Script { action: ScriptAction, },             // 启动, 停止脚本或清空源码 (action u8, )
+  Macro { action: MacroAction, scale: u8, name: String, }, // 录制与回放手动驾驶 (action u8, 回放速度百分比 u8, 名称 utf8)
+  Survey { enabled: bool, },                   // 开始或停止巡测: 沿赛道行驶并记录温湿度与位置 (enabled u8, )
+  Subscribe { subscriptions: Vec<Subscription>, }, // 订阅统计数据, 替换之前的订阅 (依次为各主题 u8, 推送间隔 ms u16 大端)
 }
 
 impl Command {
@@ -27,11 +51,27 @@ impl Command {
       Command::Statistics => 1,
       Command::Navigate { .. } => 3,
       Command::TH { .. } => 2,
-      Command::Nixie { .. } => 3,
+      Command::Nixie { .. } => 4,
       Command::Servos { .. } => 2,
-      Command::Trace { .. } => 2,
+      Command::Trace { .. } => 3,
       Command::Ultrasonic { .. } => 2,
-      Command::Led { .. } => 2,
+      Command::Led { .. } => 7,
+      Command::Scan { .. } => 5,
+      Command::Autopilot { .. } => 2,
+      Command::Buzzer { .. } => 2,
+      Command::Melody { ref melody } => 1 + melody.len(),
+      Command::NixieText { ref text } => 1 + text.len(),
+      Command::NixieSegments { .. } => 5,
+      Command::SyncTime { .. } => 9,
+      Command::TracePid { .. } => 13,
+      Command::Stream { .. } => 5,
+      Command::Mission { ref steps } => 1 + steps.iter().map(MissionStep::buf_len).sum::<usize>(),
+      Command::MissionControl { .. } => 2,
+      Command::ScriptSource { ref text } => 1 + text.len(),
+      Command::Script { .. } => 2,
+      Command::Macro { ref name, .. } => 3 + name.len(),
+      Command::Survey { .. } => 2,
+      Command::Subscribe { ref subscriptions } => 1 + 3 * subscriptions.len(),
     }
   }
 
@@ -57,87 +97,264 @@ impl Command {
         buf[0] = 3;
         buf[1] = enabled as u8;
       }
-      Command::Nixie { enabled, brightness } => {
-        debug_assert!(buf.len() == 3);
+      Command::Nixie { enabled, brightness, mode } => {
+        debug_assert!(buf.len() == 4);
         buf[0] = 4;
         buf[1] = enabled as u8;
         buf[2] = brightness;
+        buf[3] = mode.to_u8().unwrap_or_default();
       }
       Command::Servos { angle } => {
         debug_assert!(buf.len() == 2);
         buf[0] = 5;
         buf[1] = angle;
       }
-      Command::Trace { enabled } => {
-        debug_assert!(buf.len() == 2);
+      Command::Trace { enabled, algorithm } => {
+        debug_assert!(buf.len() == 3);
         buf[0] = 6;
         buf[1] = enabled as u8;
+        buf[2] = algorithm.to_u8().unwrap_or_default();
       }
       Command::Ultrasonic { enabled } => {
         debug_assert!(buf.len() == 2);
         buf[0] = 7;
         buf[1] = enabled as u8;
       }
-      Command::Led { enabled } => {
-        debug_assert!(buf.len() == 2);
+      Command::Led { enabled, effect, color: (r, g, b), brightness } => {
+        debug_assert!(buf.len() == 7);
         buf[0] = 8;
         buf[1] = enabled as u8;
+        buf[2] = effect.to_u8().unwrap_or_default();
+        buf[3] = r;
+        buf[4] = g;
+        buf[5] = b;
+        buf[6] = brightness;
+      }
+      Command::Scan { enabled, from, to, step } => {
+        debug_assert!(buf.len() == 5);
+        buf[0] = 9;
+        buf[1] = enabled as u8;
+        buf[2] = from;
+        buf[3] = to;
+        buf[4] = step;
+      }
+      Command::Autopilot { enabled } => {
+        debug_assert!(buf.len() == 2);
+        buf[0] = 10;
+        buf[1] = enabled as u8;
+      }
+      Command::Buzzer { sound } => {
+        debug_assert!(buf.len() == 2);
+        buf[0] = 11;
+        buf[1] = sound.to_u8().unwrap_or_default();
+      }
+      Command::Melody { ref melody } => {
+        debug_assert!(buf.len() == 1 + melody.len());
+        buf[0] = 12;
+        buf[1..].copy_from_slice(melody.as_bytes());
+      }
+      Command::NixieText { ref text } => {
+        debug_assert!(buf.len() == 1 + text.len());
+        buf[0] = 13;
+        buf[1..].copy_from_slice(text.as_bytes());
+      }
+      Command::NixieSegments { segments } => {
+        debug_assert!(buf.len() == 5);
+        buf[0] = 14;
+        buf[1..].copy_from_slice(&segments);
+      }
+      Command::SyncTime { timestamp } => {
+        debug_assert!(buf.len() == 9);
+        buf[0] = 15;
+        buf[1..].copy_from_slice(&timestamp.to_be_bytes());
+      }
+      Command::TracePid { kp, ki, kd } => {
+        debug_assert!(buf.len() == 13);
+        buf[0] = 16;
+        buf[1..5].copy_from_slice(&kp.to_be_bytes());
+        buf[5..9].copy_from_slice(&ki.to_be_bytes());
+        buf[9..13].copy_from_slice(&kd.to_be_bytes());
+      }
+      Command::Stream { enabled, overlay } => {
+        debug_assert!(buf.len() == 5);
+        buf[0] = 17;
+        buf[1] = enabled as u8;
+        buf[2] = overlay.roi as u8;
+        buf[3] = overlay.mask as u8;
+        buf[4] = overlay.centroid as u8;
+      }
+      Command::Mission { ref steps } => {
+        buf[0] = 18;
+        let mut offset = 1;
+        for step in steps {
+          step.write(&mut buf[offset..offset + step.buf_len()]);
+          offset += step.buf_len();
+        }
+      }
+      Command::MissionControl { action } => {
+        debug_assert!(buf.len() == 2);
+        buf[0] = 19;
+        buf[1] = action.to_u8().unwrap_or_default();
+      }
+      Command::ScriptSource { ref text } => {
+        debug_assert!(buf.len() == 1 + text.len());
+        buf[0] = 20;
+        buf[1..].copy_from_slice(text.as_bytes());
+      }
+      Command::Script { action } => {
+        debug_assert!(buf.len() == 2);
+        buf[0] = 21;
+        buf[1] = action.to_u8().unwrap_or_default();
+      }
+      Command::Macro { action, scale, ref name } => {
+        debug_assert!(buf.len() == 3 + name.len());
+        buf[0] = 22;
+        buf[1] = action.to_u8().unwrap_or_default();
+        buf[2] = scale;
+        buf[3..].copy_from_slice(name.as_bytes());
+      }
+      Command::Survey { enabled } => {
+        debug_assert!(buf.len() == 2);
+        buf[0] = 23;
+        buf[1] = enabled as u8;
+      }
+      Command::Subscribe { ref subscriptions } => {
+        debug_assert!(buf.len() == 1 + 3 * subscriptions.len());
+        buf[0] = 24;
+        for (chunk, subscription) in buf[1..].chunks_exact_mut(3).zip(subscriptions) {
+          chunk[0] = subscription.topic.to_u8().unwrap_or_default();
+          chunk[1..].copy_from_slice(&subscription.interval_ms.to_be_bytes());
+        }
       }
     }
   }
 
   pub fn parse(buf: &[u8]) -> Result<Command, CommandError> {
     let buf_len = buf.len();
-    debug_assert!(!buf.is_empty());
+    if buf.is_empty() {
+      return Err(CommandError::ParserError);
+    }
     match buf[0] {
-      0 => {
-        debug_assert!(buf.len() == 1);
-        Ok(Command::NOP)
+      0 | 1 if buf_len != 1 => Err(CommandError::ParserError),
+      0 => Ok(Command::NOP),
+      1 => Ok(Command::Statistics),
+      2 if buf_len != 3 => Err(CommandError::ParserError),
+      2 => Ok(Command::Navigate { navigate: variant(buf[1])?, speed: buf[2] }),
+      3 if buf_len != 2 => Err(CommandError::ParserError),
+      3 => Ok(Command::TH { enabled: buf[1] != 0 }),
+      4 if buf_len != 4 => Err(CommandError::ParserError),
+      4 => Ok(Command::Nixie { enabled: buf[1] != 0, brightness: buf[2], mode: variant(buf[3])? }),
+      5 if buf_len != 2 => Err(CommandError::ParserError),
+      5 => Ok(Command::Servos { angle: buf[1] }),
+      6 if buf_len != 3 => Err(CommandError::ParserError),
+      6 => Ok(Command::Trace { enabled: buf[1] != 0, algorithm: variant(buf[2])? }),
+      7 if buf_len != 2 => Err(CommandError::ParserError),
+      7 => Ok(Command::Ultrasonic { enabled: buf[1] != 0 }),
+      8 if buf_len != 7 => Err(CommandError::ParserError),
+      8 => Ok(Command::Led {
+        enabled: buf[1] != 0,
+        effect: variant(buf[2])?,
+        color: (buf[3], buf[4], buf[5]),
+        brightness: buf[6],
+      }),
+      9 if buf_len != 5 => Err(CommandError::ParserError),
+      9 => Ok(Command::Scan { enabled: buf[1] != 0, from: buf[2], to: buf[3], step: buf[4] }),
+      10 if buf_len != 2 => Err(CommandError::ParserError),
+      10 => Ok(Command::Autopilot { enabled: buf[1] != 0 }),
+      11 if buf_len != 2 => Err(CommandError::ParserError),
+      11 => Ok(Command::Buzzer { sound: variant(buf[1])? }),
+      12 => match std::str::from_utf8(&buf[1..]) {
+        Ok(melody) => Ok(Command::Melody { melody: melody.to_string() }),
+        Err(_) => Err(CommandError::ParserError),
+      },
+      13 => match std::str::from_utf8(&buf[1..]) {
+        Ok(text) => Ok(Command::NixieText { text: text.to_string() }),
+        Err(_) => Err(CommandError::ParserError),
+      },
+      14 if buf_len != 5 => Err(CommandError::ParserError),
+      14 => Ok(Command::NixieSegments { segments: [buf[1], buf[2], buf[3], buf[4]] }),
+      15 if buf_len != 9 => Err(CommandError::ParserError),
+      15 => Ok(Command::SyncTime { timestamp: u64::from_be_bytes(buf[1..9].try_into().unwrap()) }),
+      16 if buf_len != 13 => Err(CommandError::ParserError),
+      16 => {
+        let f32_at = |i: usize| f32::from_be_bytes(buf[i..i + 4].try_into().unwrap());
+        Ok(Command::TracePid { kp: f32_at(1), ki: f32_at(5), kd: f32_at(9) })
       }
-      1 => {
-        debug_assert!(buf.len() == 1);
-        Ok(Command::Statistics)
-      }
-      2 if buf.len() < 3 => Err(CommandError::ParserError),
-      2 => {
-        debug_assert!(buf_len == 3);
-        Ok(Command::Navigate { navigate: Navigate::from_u8(buf[1]).unwrap_or(Navigate::Brake), speed: buf[2] })
-      }
-      3 if buf_len < 2 => Err(CommandError::ParserError),
-      3 => {
-        debug_assert!(buf_len == 2);
-        Ok(Command::TH { enabled: buf[1] != 0 })
-      }
-      4 if buf_len < 3 => Err(CommandError::ParserError),
-      4 => {
-        debug_assert!(buf_len == 3);
-        Ok(Command::Nixie { enabled: buf[1] != 0, brightness: buf[2] })
-      }
-      5 if buf_len < 2 => Err(CommandError::ParserError),
-      5 => {
-        debug_assert!(buf_len == 2);
-        Ok(Command::Servos { angle: buf[1] })
-      }
-      6 if buf_len < 2 => Err(CommandError::ParserError),
-      6 => {
-        debug_assert!(buf_len == 2);
-        Ok(Command::Trace { enabled: buf[1] != 0 })
-      }
-      7 if buf_len < 2 => Err(CommandError::ParserError),
-      7 => {
-        debug_assert!(buf_len == 2);
-        Ok(Command::Ultrasonic { enabled: buf[1] != 0 })
-      }
-      8 if buf_len < 2 => Err(CommandError::ParserError),
-      8 => {
-        debug_assert!(buf_len == 2);
-        Ok(Command::Led { enabled: buf[1] != 0 })
+      17 if buf_len != 5 => Err(CommandError::ParserError),
+      17 => Ok(Command::Stream {
+        enabled: buf[1] != 0,
+        overlay: StreamOverlay { roi: buf[2] != 0, mask: buf[3] != 0, centroid: buf[4] != 0 },
+      }),
+      18 => {
+        let mut steps = Vec::new();
+        let mut offset = 1;
+        while offset < buf_len {
+          let (step, len) = MissionStep::parse(&buf[offset..])?;
+          steps.push(step);
+          offset += len;
+        }
+        Ok(Command::Mission { steps })
+      }
+      19 if buf_len != 2 => Err(CommandError::ParserError),
+      19 => Ok(Command::MissionControl { action: variant(buf[1])? }),
+      20 => match std::str::from_utf8(&buf[1..]) {
+        Ok(text) => Ok(Command::ScriptSource { text: text.to_string() }),
+        Err(_) => Err(CommandError::ParserError),
+      },
+      21 if buf_len != 2 => Err(CommandError::ParserError),
+      21 => Ok(Command::Script { action: variant(buf[1])? }),
+      22 if buf_len < 3 => Err(CommandError::ParserError),
+      22 => match std::str::from_utf8(&buf[3..]) {
+        Ok(name) => Ok(Command::Macro { action: variant(buf[1])?, scale: buf[2], name: name.to_string() }),
+        Err(_) => Err(CommandError::ParserError),
+      },
+      23 if buf_len != 2 => Err(CommandError::ParserError),
+      23 => Ok(Command::Survey { enabled: buf[1] != 0 }),
+      24 if !(buf_len - 1).is_multiple_of(3) => Err(CommandError::ParserError),
+      24 => {
+        let subscriptions = buf[1..]
+          .chunks_exact(3)
+          .map(|chunk| {
+            let topic = variant(chunk[0])?;
+            Ok(Subscription { topic, interval_ms: u16::from_be_bytes([chunk[1], chunk[2]]) })
+          })
+          .collect::<Result<_, _>>()?;
+        Ok(Command::Subscribe { subscriptions })
       }
       _ => Err(CommandError::UnknownCommand),
     }
   }
 }
 
+/// 请求包头: 负载长度 u8, 序号 (u16 大端)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestHeader {
+  pub len: u8,
+  pub seq: u16,
+}
+
+impl RequestHeader {
+  pub fn parse(buf: [u8; REQUEST_HEADER_LEN]) -> Self {
+    Self { len: buf[0], seq: u16::from_be_bytes([buf[1], buf[2]]) }
+  }
+
+  pub fn to_bytes(self) -> [u8; REQUEST_HEADER_LEN] {
+    let [high, low] = self.seq.to_be_bytes();
+    [self.len, high, low]
+  }
+}
+
+impl Command {
+  /// 编码为请求包, 命令长度超过 u8::MAX 时返回 None
+  pub fn to_request(&self, seq: u16) -> Option<Vec<u8>> {
+    let len = u8::try_from(self.buf_len()).ok()?;
+    let mut buf = vec![0; REQUEST_HEADER_LEN + self.buf_len()];
+    buf[..REQUEST_HEADER_LEN].copy_from_slice(&RequestHeader { len, seq }.to_bytes());
+    self.write(&mut buf[REQUEST_HEADER_LEN..]);
+    Some(buf)
+  }
+}
+
 #[derive(TS, Serialize, Default, FromPrimitive, ToPrimitive, Debug, Deserialize, Clone, Copy)]
 pub enum Angle {
   #[default]
@@ -159,8 +376,106 @@ pub enum Navigate {
   BackWard = 0x04, // 后退
 }
 
-#[derive(Debug)]
+/// 脚本操作
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum ScriptAction {
+  #[default]
+  Stop, // 停止运行
+  Start, // 以已上传的源码启动
+  Clear, // 清空已上传的源码, 准备上传新脚本
+}
+
+/// 录制与回放操作
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum MacroAction {
+  #[default]
+  Stop, // 结束录制并保存, 或停止回放
+  Record, // 开始录制
+  Play,   // 回放
+  Delete, // 删除录制
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum CommandError {
-  ParserError,
-  UnknownCommand,
+  ParserError,     // 长度或格式错误
+  UnknownCommand,  // 未知的命令
+  InvalidArgument, // 枚举参数超出范围
+}
+
+/// 解析枚举参数
+pub(crate) fn variant<T: FromPrimitive>(byte: u8) -> Result<T, CommandError> {
+  T::from_u8(byte).ok_or(CommandError::InvalidArgument)
+}
+
+impl From<CommandError> for RequestError {
+  fn from(err: CommandError) -> Self {
+    match err {
+      CommandError::ParserError => RequestError::BadLength,
+      CommandError::UnknownCommand => RequestError::UnknownCommand,
+      CommandError::InvalidArgument => RequestError::InvalidArgument,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Command, CommandError, Navigate, RequestHeader};
+  use crate::{RequestError, Sound, Subscription, Topic, REQUEST_HEADER_LEN};
+
+  fn round_trip(command: &Command) -> Result<Command, CommandError> {
+    let mut buf = vec![0; command.buf_len()];
+    command.write(&mut buf);
+    Command::parse(&buf)
+  }
+
+  #[test]
+  fn test_parse() {
+    let command = round_trip(&Command::Navigate { navigate: Navigate::Left, speed: 30 }).unwrap();
+    assert!(matches!(command, Command::Navigate { navigate: Navigate::Left, speed: 30 }));
+    let subscriptions = vec![Subscription { topic: Topic::TH, interval_ms: 2000 }];
+    let command = round_trip(&Command::Subscribe { subscriptions: subscriptions.clone() }).unwrap();
+    assert!(matches!(command, Command::Subscribe { subscriptions: ref s } if *s == subscriptions));
+
+    // 长度不符
+    assert_eq!(Command::parse(&[]).unwrap_err(), CommandError::ParserError);
+    assert_eq!(Command::parse(&[1, 0]).unwrap_err(), CommandError::ParserError);
+    assert_eq!(Command::parse(&[5]).unwrap_err(), CommandError::ParserError);
+    assert_eq!(Command::parse(&[5, 90, 0]).unwrap_err(), CommandError::ParserError);
+    assert_eq!(Command::parse(&[24, 0, 0]).unwrap_err(), CommandError::ParserError);
+
+    // 枚举超出范围
+    assert_eq!(Command::parse(&[2, 9, 50]).unwrap_err(), CommandError::InvalidArgument);
+    assert_eq!(Command::parse(&[11, 0xff]).unwrap_err(), CommandError::InvalidArgument);
+    assert_eq!(Command::parse(&[24, 9, 0, 50]).unwrap_err(), CommandError::InvalidArgument);
+    assert!(matches!(Command::parse(&[11, 0]), Ok(Command::Buzzer { sound: Sound::Stop })));
+    // 已有声音的编号不能改变
+    assert!(matches!(Command::parse(&[11, 5]), Ok(Command::Buzzer { sound: Sound::Custom })));
+    assert!(matches!(Command::parse(&[11, 6]), Ok(Command::Buzzer { sound: Sound::Alarm })));
+
+    assert_eq!(Command::parse(&[0xff]).unwrap_err(), CommandError::UnknownCommand);
+  }
+
+  #[test]
+  fn test_request() {
+    let command = Command::Servos { angle: 45 };
+    let request = command.to_request(0x1234).unwrap();
+    let header = RequestHeader::parse(request[..REQUEST_HEADER_LEN].try_into().unwrap());
+    assert_eq!(header, RequestHeader { len: 2, seq: 0x1234 });
+    assert_eq!(request, [2, 0x12, 0x34, 5, 45]);
+    assert!(matches!(Command::parse(&request[REQUEST_HEADER_LEN..]), Ok(Command::Servos { angle: 45 })));
+
+    // 超过 u8::MAX 的命令无法发送
+    assert!(Command::ScriptSource { text: "x".repeat(255) }.to_request(0).is_none());
+  }
+
+  #[test]
+  fn test_request_error() {
+    assert_eq!(RequestError::from(CommandError::ParserError), RequestError::BadLength);
+    assert_eq!(RequestError::from(CommandError::UnknownCommand), RequestError::UnknownCommand);
+    assert_eq!(RequestError::from(CommandError::InvalidArgument), RequestError::InvalidArgument);
+  }
 }
@@ -1,29 +1,87 @@
 pub mod buffer;
 pub mod command;
+pub mod mission;
 
-use std::sync::atomic::{AtomicBool, AtomicI16, AtomicU16, AtomicU8, Ordering};
+use std::{
+  cell::RefCell,
+  collections::VecDeque,
+  sync::{
+    atomic::{AtomicBool, AtomicI16, AtomicI64, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering},
+    Arc, Mutex, MutexGuard, OnceLock, PoisonError, TryLockError,
+  },
+  time::{Duration, Instant},
+};
 
+use arc_swap::ArcSwapOption;
 use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use mission::{Measurement, MissionProgress, MissionState, MissionStep};
+
 #[repr(u8)]
 #[derive(TS, FromPrimitive, ToPrimitive, Debug, Deserialize, Clone, Copy)]
 #[ts(export)]
 pub enum CommandType {
   NOP,
-  Statistics, // 获取统计数据
-  Navigate,   // 控制车方向命令，(方向u8, 速度u8)
-  TH,         // 是否开启温湿传感器 (enabledu8,)
-  Nixie,      // 是否开启 数码管 (enabled u8, brightness)
-  Servos,     // 舵机 (angle u8, )
-  Trace,      // 是否开启寻迹
-  Ultrasonic, // 是否开启超声波测距
+  Statistics,     // 获取统计数据
+  Navigate,       // 控制车方向命令，(方向u8, 速度u8)
+  TH,             // 是否开启温湿传感器 (enabledu8,)
+  Nixie,          // 是否开启 数码管 (enabled u8, brightness)
+  Servos,         // 舵机 (angle u8, )
+  Trace,          // 是否开启寻迹
+  Ultrasonic,     // 是否开启超声波测距
+  Led,            // 是否开启 led
+  Scan,           // 雷达扫描
+  Autopilot,      // 自动避障漫游
+  Buzzer,         // 蜂鸣器播放声音
+  Melody,         // 蜂鸣器播放自定义乐谱
+  NixieText,      // 数码管滚动显示的文字
+  NixieSegments,  // 数码管自定义段码
+  SyncTime,       // 校准时钟
+  TracePid,       // 寻迹 PID 参数
+  Stream,         // 摄像头推流
+  Mission,        // 上传并开始执行任务
+  MissionControl, // 暂停, 继续或中止任务
+  ScriptSource,   // 追加脚本源码
+  Script,         // 启动, 停止脚本或清空源码
+  Macro,          // 录制与回放手动驾驶
+  Survey,         // 巡测
+  Subscribe,      // 订阅统计数据
 }
 
-#[derive(FromPrimitive, ToPrimitive, Debug)]
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy)]
 pub enum ResponseType {
   Statistics,
+  Scan,        // 雷达扫描结果
+  Measurement, // 任务的测量结果
+  Log,         // 脚本输出的日志
+  Macros,      // 已保存的录制列表
+  Alarm,       // 环境报警事件
+  Sample,      // 巡测的温湿度样本
+  Ack,         // 请求的处理结果
+}
+
+/// 请求的处理结果, 与请求的序号对应
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct Ack {
+  pub seq: u16,
+  pub error: Option<RequestError>, // 成功为 None
+}
+
+/// 请求失败的原因
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum RequestError {
+  UnknownCommand,  // 未知的命令
+  BadLength,       // 命令长度或格式错误
+  InvalidAngle,    // 舵机角度超出 0-180
+  InvalidArgument, // 其它参数无效, 如录制的名称
+  Busy,            // 自动避障, 回放等功能占用时忽略
+  HardwareFailure, // 硬件或文件读写失败
+  NoReply,         // 客户端超时没有收到处理结果, 小车不会回复
 }
 
 /// 统计信息
@@ -33,6 +91,7 @@ pub struct Response {
   // 数码管
   // pub now: (u8, u8, u8),           // 时间 （hms）
   pub time_brightness: Option<u8>, // 时间显示亮度
+  pub nixie_mode: NixieMode,       // 数码管显示模式
 
   // 蜂鸣器
 
@@ -40,16 +99,255 @@ pub struct Response {
   pub speed_percent: u8, // 速度百分比
 
   // 超声波测距 舵机
-  pub distance: Option<f32>, // 障碍物距离
-  pub servos: u8,            // 舵机角度
+  pub distance: Option<f32>,         // 障碍物距离
+  pub distance_status: SensorStatus, // 超声波读数的状态
+  pub servos: u8,                    // 舵机角度
 
   // LED 灯
-  pub led: bool, // 是否开启LED 等
+  pub led: Option<LedEffect>,  // LED 灯效果, 未开启为 None
+  pub led_color: (u8, u8, u8), // LED 灯颜色
+  pub led_brightness: u8,      // LED 灯亮度 0-100
 
   // 温湿传感器
-  pub th: Option<(f32, f32)>, // 温度, 湿度
+  pub th: Option<(f32, f32)>,  // 温度, 湿度
+  pub th_status: SensorStatus, // 温湿度读数的状态
   // 寻迹
-  pub trace: bool, // 是否开启寻迹
+  pub trace: Option<TraceAlgorithm>, // 寻迹算法, 未开启寻迹时为 None
+  pub trace_pid: (f32, f32, f32),    // 寻迹 PID 参数 kp, ki, kd
+  pub marker: Option<Marker>,        // 寻迹时生效的赛道标志
+  pub stream: Option<StreamOverlay>, // 推流的调试叠加层, 未开启推流时为 None
+  pub camera: bool,                  // 摄像头是否可用, 不可用时无法寻迹和推流
+
+  // 雷达扫描
+  pub scan: bool, // 是否开启雷达扫描
+
+  // 自动避障
+  pub autopilot: Option<AutopilotState>, // 自动避障状态, 未开启为 None
+
+  // 任务
+  pub mission: Option<MissionProgress>, // 任务进度, 没有任务时为 None
+
+  // 脚本
+  pub script: bool, // 脚本是否正在运行
+
+  // 录制与回放
+  pub macro_state: Option<MacroState>, // 正在录制或回放, 空闲时为 None
+  pub alarm: bool,                     // 是否有正在报警的环境规则
+  pub survey: bool,                    // 是否正在巡测
+
+  // 蜂鸣器
+  pub sound: Option<Sound>, // 正在播放的声音
+}
+
+/// 数码管显示模式
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum NixieMode {
+  #[default]
+  Clock, // 时钟
+  Temperature, // 温度
+  Humidity,    // 湿度
+  Distance,    // 障碍物距离
+  Speed,       // 速度百分比
+  Text,        // 滚动文字
+  Segments,    // 自定义段码
+  Cycle,       // 在时钟, 温度, 湿度, 距离之间轮换
+}
+
+/// 寻迹算法
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum TraceAlgorithm {
+  #[default]
+  Contour, // 二值化后取最大轮廓的质心
+  SlidingWindow, // 滑动窗口车道线检测
+  ColorTape,     // 按颜色识别胶带
+}
+
+/// 赛道上的颜色标志
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum Marker {
+  Stop = 1, // 红色: 停车
+  Go,       // 绿色: 通行, 解除停车与减速
+  Slow,     // 黄色: 减速
+}
+
+/// 录制与回放的状态
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum MacroState {
+  Recording = 1, // 录制中
+  Playing,       // 回放中
+}
+
+/// 推流画面上的调试叠加层
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct StreamOverlay {
+  pub roi: bool,      // 标出感兴趣区域
+  pub mask: bool,     // 显示二值化图像而非原始画面
+  pub centroid: bool, // 标出识别到的线
+}
+
+/// LED 灯效果
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum LedEffect {
+  #[default]
+  Solid, // 常亮
+  Blink,   // 闪烁
+  Breathe, // 呼吸
+  Rainbow, // 彩虹渐变, 忽略颜色
+  Status,  // 状态指示, 忽略颜色
+}
+
+/// 蜂鸣器的声音
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum Sound {
+  #[default]
+  Stop, // 停止播放
+  Beep,            // 鸣笛
+  Obstacle,        // 障碍物刹车
+  LostLine,        // 寻迹丢线
+  OverTemperature, // 温度过高
+  Custom,          // 自定义乐谱
+  Alarm,           // 环境报警, 放在最后以免改变已有声音的编号
+}
+
+/// 环境监测的指标
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum Metric {
+  Temperature, // 温度 °C
+  Humidity,    // 湿度 %
+}
+
+/// 报警事件: 规则开始或解除报警
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[ts(export)]
+pub struct Alarm {
+  #[ts(type = "number")]
+  pub time: u64, // 校准后的 Unix 时间戳 ms
+  pub name: String, // 规则名称
+  pub metric: Metric,
+  pub value: f32,   // 触发时的读数, 变化率规则为每分钟的变化量
+  pub active: bool, // true 为开始报警, false 为解除
+}
+
+/// 按周期调度读取的传感器
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sensor {
+  TH,         // 温湿传感器
+  Ultrasonic, // 超声波
+}
+
+impl Sensor {
+  /// 读数所属的主题
+  pub fn topic(self) -> Topic {
+    match self {
+      Sensor::TH => Topic::TH,
+      Sensor::Ultrasonic => Topic::Distance,
+    }
+  }
+}
+
+/// 统计数据的主题: 客户端按主题订阅 Response 中的字段
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum Topic {
+  Distance, // distance, distance_status
+  TH,       // th, th_status
+  Motion,   // speed_percent, servos, marker, autopilot
+  Status,   // 其它字段
+}
+
+impl Topic {
+  pub const ALL: [Topic; TOPICS] = [Topic::Distance, Topic::TH, Topic::Motion, Topic::Status];
+
+  /// Response 的字段所属的主题
+  pub fn of(field: &str) -> Topic {
+    match field {
+      "distance" | "distance_status" => Topic::Distance,
+      "th" | "th_status" => Topic::TH,
+      "speed_percent" | "servos" | "marker" | "autopilot" => Topic::Motion,
+      _ => Topic::Status,
+    }
+  }
+}
+
+/// 订阅一个主题: 有变化时最快每 interval_ms 推送一次
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct Subscription {
+  pub topic: Topic,
+  pub interval_ms: u16,
+}
+
+/// 传感器读取失败的原因
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum SensorError {
+  NoResponse = 1, // 传感器没有响应
+  Timeout,        // 等待数据或回波超时
+  Checksum,       // 校验和错误
+  OutOfRange,     // 读数超出量程
+}
+
+/// 传感器读数的状态
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct SensorStatus {
+  pub age: Option<u32>,           // 距上一次成功读取的时间 ms, 从未读取时为 None
+  pub stale: bool,                // 是否过期
+  pub error: Option<SensorError>, // 最近一次读取失败的原因, 读取成功后清除
+  pub samples: u32,               // 成功读取的次数
+  pub failures: u32,              // 读取失败的次数
+}
+
+/// 巡测的样本: 温湿度与测量时的位置
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[ts(export)]
+pub struct Sample {
+  pub ms: u32,          // 距巡测开始的时间
+  pub distance: f32,    // 距巡测起点的行驶距离 cm, 由电机速度估算, 后退时减少
+  pub temperature: f32, // °C
+  pub humidity: f32,    // %
+}
+
+/// 自动避障漫游的状态
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum AutopilotState {
+  #[default]
+  Forward, // 前进
+  Stop,      // 前方有障碍物, 停车
+  ScanLeft,  // 测量左侧距离
+  ScanRight, // 测量右侧距离
+  TurnLeft,  // 左转
+  TurnRight, // 右转
+  BackWard,  // 两侧都有障碍物, 后退
+}
+
+/// 雷达扫描结果: 极坐标 (角度 -> 距离)
+/// 第 i 个距离对应的角度为 from + i * step
+#[derive(TS, Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct Scan {
+  pub from: u8,                    // 起始角度
+  pub step: u8,                    // 步进角度
+  pub distances: Vec<Option<u16>>, // 障碍物距离 cm, 测量失败为 None
 }
 
 /// 统计信息
@@ -58,6 +356,9 @@ pub struct Statistics {
   // 数码管
   nixie: AtomicBool,
   nixie_brightness: AtomicU8,
+  nixie_mode: AtomicU8,
+  nixie_text: Mutex<String>,
+  nixie_segments: AtomicU32,
 
   // 电动机
   speed: AtomicU8,
@@ -69,6 +370,9 @@ pub struct Statistics {
 
   // LED 灯
   led: AtomicBool,
+  led_effect: AtomicU8,
+  led_color: AtomicU32, // 0xRRGGBB
+  led_brightness: AtomicU8,
 
   // 温湿传感器
   th: AtomicBool,
@@ -77,16 +381,137 @@ pub struct Statistics {
 
   // 寻迹
   trace: AtomicBool,
+  trace_algorithm: AtomicU8,
+  trace_pid: [AtomicU32; 3], // f32 的位
+  marker: AtomicU8,          // 0 为没有标志
+
+  camera: AtomicBool, // 摄像头是否可用
+
+  // 推流
+  stream: AtomicBool,
+  stream_overlay: AtomicU8, // 第 0-2 位: roi, mask, centroid
+
+  // 雷达扫描
+  scan: AtomicBool,
+  scan_from: AtomicU8,
+  scan_to: AtomicU8,
+  scan_step: AtomicU8,
+  last_scan: Mutex<Option<Scan>>, // 最近一次完整的扫描结果
+  scan_version: AtomicU32,        // 每完成一次扫描加一
+
+  // 自动避障
+  autopilot: AtomicBool,
+  autopilot_state: AtomicU8,
+
+  // 任务
+  mission_steps: Mutex<Vec<MissionStep>>,
+  mission_state: AtomicU8,
+  mission_step: AtomicU8,                       // 正在执行的步骤序号
+  mission_version: AtomicU32,                   // 每上传一次任务加一
+  last_measurement: Mutex<Option<Measurement>>, // 最近一次测量结果
+  measurement_version: AtomicU32,               // 每测量一次加一
+
+  // 脚本
+  script: AtomicBool,
+  script_source: Mutex<String>,               // 已上传的脚本源码
+  script_version: AtomicU32,                  // 每启动一次脚本加一
+  script_log: Mutex<VecDeque<(u32, String)>>, // 最近的脚本日志与序号
+  script_log_seq: AtomicU32,                  // 最新的日志序号
+
+  // 录制与回放
+  macro_state: AtomicU8, // 0 为空闲
+
+  // 环境报警
+  alarm: AtomicBool,                        // 是否有正在报警的规则
+  alarm_log: Mutex<VecDeque<(u32, Alarm)>>, // 最近的报警事件与序号
+  alarm_seq: AtomicU32,                     // 最新的报警事件序号
+
+  // 巡测
+  survey: AtomicBool,
+  survey_version: AtomicU32,                      // 每开始一次巡测加一
+  survey_samples: Mutex<VecDeque<(u32, Sample)>>, // 本次巡测的样本与序号
+  survey_seq: AtomicU32,                          // 最新的样本序号
+
+  // 蜂鸣器
+  sound: AtomicU32,      // 低 8 位为正在播放的声音, 其余为请求的序号, 序号变化时重新播放
+  melody: Mutex<String>, // 自定义乐谱
+
+  // 传感器调度
+  sensor_updated: [AtomicU64; 2], // 上一次成功读取的时间 (monotonic_ms), 0 为从未读取
+  sensor_period: [AtomicU32; 2],  // 读取周期 ms
+  sensor_error: [AtomicU8; 2],    // 最近一次读取失败的原因, 0 为没有错误
+  sensor_samples: [AtomicU32; 2],
+  sensor_failures: [AtomicU32; 2],
+
+  // 连接
+  clients: AtomicU16, // 已连接的客户端数量
+
+  clock_offset: AtomicI64, // 客户端校时得到的时钟偏差 ms
+
+  // 快照
+  writer: Mutex<()>,                 // 写入批次互斥, 同一时间只有一个线程写入
+  versions: [AtomicU32; TOPICS],     // 各主题的字段每变化一次加一
+  snapshot: ArcSwapOption<Snapshot>, // 最近一次发布的快照, 各连接共享
+}
+
+thread_local! {
+  // 本线程正在持有写入批次的 Statistics 的地址, 嵌套的批次重复记录
+  static BATCHES: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 写入批次, 见 Statistics::batch
+pub struct Batch<'a> {
+  statistics: &'a Statistics,
+  writer: Option<MutexGuard<'a, ()>>, // 最外层的批次持有写入锁
+}
+
+impl Drop for Batch<'_> {
+  fn drop(&mut self) {
+    let address = self.statistics.address();
+    BATCHES.with_borrow_mut(|batches| {
+      if let Some(i) = batches.iter().rposition(|batch| *batch == address) {
+        batches.swap_remove(i);
+      }
+    });
+    // 最外层的批次结束时发布快照, 之后才释放写入锁
+    if self.writer.is_some() && !std::thread::panicking() {
+      self.statistics.publish();
+    }
+  }
+}
+
+/// 已连接的客户端, 见 Statistics::client_connected
+#[must_use]
+pub struct Client<'a>(&'a Statistics);
+
+impl Drop for Client<'_> {
+  fn drop(&mut self) {
+    self.0.clients.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+/// 统计信息的快照: 在写入批次之间生成, 各字段来自同一时刻
+#[derive(Debug)]
+pub struct Snapshot {
+  pub versions: [u32; TOPICS], // 生成时各主题的版本
+  pub taken: u64,              // 生成的时间 (monotonic_ms)
+  pub response: Response,
 }
 
 macro_rules! getter_setter {
   ($field:ident, $setter:ident, $type:ty) => {
+    getter_setter!($field, $setter, $type, Status);
+  };
+  ($field:ident, $setter:ident, $type:ty, $topic:ident) => {
     pub fn $field(&self) -> $type {
       self.$field.load(Ordering::SeqCst)
     }
 
     pub fn $setter(&self, value: $type) {
-      self.$field.store(value, Ordering::SeqCst);
+      let _batch = self.batch();
+      if self.$field.swap(value, Ordering::SeqCst) != value {
+        self.changed(Topic::$topic);
+      }
     }
   };
 }
@@ -94,39 +519,696 @@ macro_rules! getter_setter {
 impl Statistics {
   getter_setter!(nixie, set_nixie, bool);
   getter_setter!(nixie_brightness, set_nixie_brightness, u8);
-  getter_setter!(speed, set_speed, u8);
-  getter_setter!(ultrasonic, set_ultrasonic, bool);
-  getter_setter!(distance, set_distance, u16);
-  getter_setter!(servos, set_servos, u8);
+  getter_setter!(speed, set_speed, u8, Motion);
+  getter_setter!(ultrasonic, set_ultrasonic, bool, Distance);
+  getter_setter!(distance, set_distance, u16, Distance);
+  getter_setter!(servos, set_servos, u8, Motion);
   getter_setter!(led, set_led, bool);
-  getter_setter!(th, set_th, bool);
+  getter_setter!(led_brightness, set_led_brightness, u8);
+  getter_setter!(th, set_th, bool, TH);
   getter_setter!(trace, set_trace, bool);
+  getter_setter!(scan, set_scan, bool);
+  getter_setter!(scan_from, set_scan_from, u8);
+  getter_setter!(scan_to, set_scan_to, u8);
+  getter_setter!(scan_step, set_scan_step, u8);
+  getter_setter!(autopilot, set_autopilot, bool, Motion);
+  getter_setter!(mission_step, set_mission_step, u8);
+  getter_setter!(script, set_script, bool);
+  getter_setter!(alarm, set_alarm, bool);
+  getter_setter!(survey, set_survey, bool);
+  getter_setter!(clock_offset, set_clock_offset, i64);
+  getter_setter!(camera, set_camera, bool);
+  getter_setter!(stream, set_stream, bool);
 
   pub fn temperature(&self) -> f32 {
     self.temperature.load(Ordering::SeqCst) as f32 / 100_f32
   }
   pub fn set_temperature(&self, value: f32) {
-    self.temperature.store((value * 100_f32) as i16, Ordering::SeqCst)
+    let _batch = self.batch();
+    let value = (value * 100_f32) as i16;
+    if self.temperature.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::TH);
+    }
   }
   pub fn humidity(&self) -> f32 {
     self.humidity.load(Ordering::SeqCst) as f32 / 100_f32
   }
   pub fn set_humidity(&self, value: f32) {
-    self.humidity.store((value * 100_f32) as u16, Ordering::SeqCst)
+    let _batch = self.batch();
+    let value = (value * 100_f32) as u16;
+    if self.humidity.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::TH);
+    }
+  }
+
+  pub fn autopilot_state(&self) -> AutopilotState {
+    AutopilotState::from_u8(self.autopilot_state.load(Ordering::SeqCst)).unwrap_or_default()
+  }
+  pub fn set_autopilot_state(&self, value: AutopilotState) {
+    let _batch = self.batch();
+    let value = value.to_u8().unwrap_or_default();
+    if self.autopilot_state.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Motion);
+    }
+  }
+
+  pub fn clients(&self) -> u16 {
+    self.clients.load(Ordering::SeqCst)
+  }
+  /// 客户端连接, 返回的 Client 释放时 (包括连接线程 panic) 减少连接数
+  pub fn client_connected(&self) -> Client<'_> {
+    self.clients.fetch_add(1, Ordering::SeqCst);
+    Client(self)
+  }
+
+  pub fn nixie_mode(&self) -> NixieMode {
+    NixieMode::from_u8(self.nixie_mode.load(Ordering::SeqCst)).unwrap_or_default()
+  }
+  pub fn set_nixie_mode(&self, value: NixieMode) {
+    let _batch = self.batch();
+    let value = value.to_u8().unwrap_or_default();
+    if self.nixie_mode.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Status);
+    }
+  }
+  pub fn nixie_text(&self) -> String {
+    self.nixie_text.lock().unwrap().clone()
+  }
+  pub fn set_nixie_text(&self, value: String) {
+    *self.nixie_text.lock().unwrap() = value;
+  }
+  pub fn nixie_segments(&self) -> [u8; 4] {
+    self.nixie_segments.load(Ordering::SeqCst).to_be_bytes()
+  }
+  pub fn set_nixie_segments(&self, value: [u8; 4]) {
+    self.nixie_segments.store(u32::from_be_bytes(value), Ordering::SeqCst)
+  }
+
+  pub fn trace_algorithm(&self) -> TraceAlgorithm {
+    TraceAlgorithm::from_u8(self.trace_algorithm.load(Ordering::SeqCst)).unwrap_or_default()
+  }
+  pub fn set_trace_algorithm(&self, value: TraceAlgorithm) {
+    let _batch = self.batch();
+    let value = value.to_u8().unwrap_or_default();
+    if self.trace_algorithm.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Status);
+    }
+  }
+
+  pub fn trace_pid(&self) -> (f32, f32, f32) {
+    let [kp, ki, kd] = self.trace_pid.each_ref().map(|gain| f32::from_bits(gain.load(Ordering::SeqCst)));
+    (kp, ki, kd)
+  }
+  pub fn set_trace_pid(&self, (kp, ki, kd): (f32, f32, f32)) {
+    let _batch = self.batch();
+    let mut changed = false;
+    for (gain, value) in self.trace_pid.iter().zip([kp, ki, kd]) {
+      changed |= gain.swap(value.to_bits(), Ordering::SeqCst) != value.to_bits();
+    }
+    if changed {
+      self.changed(Topic::Status);
+    }
+  }
+
+  pub fn marker(&self) -> Option<Marker> {
+    Marker::from_u8(self.marker.load(Ordering::SeqCst))
+  }
+  pub fn set_marker(&self, value: Option<Marker>) {
+    let _batch = self.batch();
+    let value = value.and_then(|marker| marker.to_u8()).unwrap_or_default();
+    if self.marker.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Motion);
+    }
+  }
+
+  pub fn macro_state(&self) -> Option<MacroState> {
+    MacroState::from_u8(self.macro_state.load(Ordering::SeqCst))
+  }
+  pub fn set_macro_state(&self, value: Option<MacroState>) {
+    let _batch = self.batch();
+    let value = value.and_then(|state| state.to_u8()).unwrap_or_default();
+    if self.macro_state.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Status);
+    }
+  }
+
+  pub fn stream_overlay(&self) -> StreamOverlay {
+    let bits = self.stream_overlay.load(Ordering::SeqCst);
+    StreamOverlay { roi: bits & 0b001 != 0, mask: bits & 0b010 != 0, centroid: bits & 0b100 != 0 }
+  }
+  pub fn set_stream_overlay(&self, value: StreamOverlay) {
+    let _batch = self.batch();
+    let bits = value.roi as u8 | (value.mask as u8) << 1 | (value.centroid as u8) << 2;
+    if self.stream_overlay.swap(bits, Ordering::SeqCst) != bits {
+      self.changed(Topic::Status);
+    }
+  }
+
+  pub fn led_effect(&self) -> LedEffect {
+    LedEffect::from_u8(self.led_effect.load(Ordering::SeqCst)).unwrap_or_default()
+  }
+  pub fn set_led_effect(&self, value: LedEffect) {
+    let _batch = self.batch();
+    let value = value.to_u8().unwrap_or_default();
+    if self.led_effect.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Status);
+    }
+  }
+  pub fn led_color(&self) -> (u8, u8, u8) {
+    let [_, r, g, b] = self.led_color.load(Ordering::SeqCst).to_be_bytes();
+    (r, g, b)
+  }
+  pub fn set_led_color(&self, (r, g, b): (u8, u8, u8)) {
+    let _batch = self.batch();
+    let value = u32::from_be_bytes([0, r, g, b]);
+    if self.led_color.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Status);
+    }
+  }
+
+  pub fn sound(&self) -> Sound {
+    self.sound_request().0
+  }
+  /// 正在播放的声音与请求的序号
+  pub fn sound_request(&self) -> (Sound, u32) {
+    let value = self.sound.load(Ordering::SeqCst);
+    (Sound::from_u32(value & 0xff).unwrap_or_default(), value >> 8)
+  }
+  /// 请求播放声音, 与正在播放的声音相同时重新播放
+  pub fn set_sound(&self, value: Sound) {
+    let _batch = self.batch();
+    let value = value.to_u32().unwrap_or_default();
+    let old = self
+      .sound
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| Some((current >> 8).wrapping_add(1) << 8 | value))
+      .unwrap();
+    if old & 0xff != value {
+      self.changed(Topic::Status);
+    }
+  }
+  /// 声音播放结束: 若期间没有新的请求则停止
+  pub fn finish_sound(&self, request: u32) {
+    let _batch = self.batch();
+    let stop = Sound::Stop.to_u32().unwrap_or_default();
+    let finished = self.sound.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+      (current >> 8 == request).then_some(current & !0xff | stop)
+    });
+    if finished.is_ok_and(|old| old & 0xff != stop) {
+      self.changed(Topic::Status);
+    }
+  }
+  pub fn melody(&self) -> String {
+    self.melody.lock().unwrap().clone()
+  }
+  pub fn set_melody(&self, value: String) {
+    *self.melody.lock().unwrap() = value;
+  }
+
+  pub fn mission_state(&self) -> MissionState {
+    MissionState::from_u8(self.mission_state.load(Ordering::SeqCst)).unwrap_or_default()
+  }
+  pub fn set_mission_state(&self, value: MissionState) {
+    let _batch = self.batch();
+    let value = value.to_u8().unwrap_or_default();
+    if self.mission_state.swap(value, Ordering::SeqCst) != value {
+      self.changed(Topic::Status);
+    }
+  }
+
+  pub fn mission_steps(&self) -> Vec<MissionStep> {
+    self.mission_steps.lock().unwrap().clone()
+  }
+  pub fn mission_version(&self) -> u32 {
+    self.mission_version.load(Ordering::SeqCst)
+  }
+
+  /// 载入新任务并从第一步开始执行, 替换正在执行的任务
+  pub fn load_mission(&self, steps: Vec<MissionStep>) {
+    let _batch = self.batch();
+    let mut mission_steps = self.mission_steps.lock().unwrap();
+    *mission_steps = steps;
+    self.set_mission_step(0);
+    self.set_mission_state(MissionState::Running);
+    self.mission_version.fetch_add(1, Ordering::SeqCst);
+    self.changed(Topic::Status); // 步骤总数可能变化
+  }
+
+  /// 发布一次测量结果
+  pub fn publish_measurement(&self, measurement: Measurement) {
+    let mut last_measurement = self.last_measurement.lock().unwrap();
+    *last_measurement = Some(measurement);
+    self.measurement_version.fetch_add(1, Ordering::SeqCst);
+  }
+
+  /// 获取比 version 更新的测量结果
+  pub fn measurement_since(&self, version: u32) -> Option<(u32, Measurement)> {
+    let last_measurement = self.last_measurement.lock().unwrap();
+    let current = self.measurement_version.load(Ordering::SeqCst);
+    if current == version {
+      None
+    } else {
+      last_measurement.map(|measurement| (current, measurement))
+    }
+  }
+
+  /// 追加脚本源码, 超过 SCRIPT_MAX_LEN 时返回 false
+  pub fn append_script_source(&self, text: &str) -> bool {
+    let mut source = self.script_source.lock().unwrap();
+    if source.len() + text.len() > SCRIPT_MAX_LEN {
+      return false;
+    }
+    source.push_str(text);
+    true
+  }
+  pub fn clear_script_source(&self) {
+    self.script_source.lock().unwrap().clear();
+  }
+  pub fn script_source(&self) -> String {
+    self.script_source.lock().unwrap().clone()
+  }
+
+  /// 以已上传的源码启动脚本, 替换正在运行的脚本
+  pub fn start_script(&self) {
+    self.script_version.fetch_add(1, Ordering::SeqCst);
+    self.set_script(true);
+  }
+  pub fn script_version(&self) -> u32 {
+    self.script_version.load(Ordering::SeqCst)
+  }
+
+  /// 记录一条脚本日志, 只保留最近的 SCRIPT_LOG_LEN 条
+  pub fn log(&self, message: String) {
+    let mut script_log = self.script_log.lock().unwrap();
+    let seq = self.script_log_seq.fetch_add(1, Ordering::SeqCst) + 1;
+    if script_log.len() >= SCRIPT_LOG_LEN {
+      script_log.pop_front();
+    }
+    script_log.push_back((seq, message));
+  }
+
+  /// 获取序号比 seq 新的脚本日志
+  pub fn logs_since(&self, seq: u32) -> Option<(u32, Vec<(u32, String)>)> {
+    let script_log = self.script_log.lock().unwrap();
+    let current = self.script_log_seq.load(Ordering::SeqCst);
+    if current == seq {
+      None
+    } else {
+      let logs = script_log.iter().filter(|(s, _)| *s > seq).cloned().collect();
+      Some((current, logs))
+    }
+  }
+
+  /// 记录一条报警事件, 只保留最近的 ALARM_LOG_LEN 条
+  pub fn publish_alarm(&self, alarm: Alarm) {
+    let mut alarm_log = self.alarm_log.lock().unwrap();
+    let seq = self.alarm_seq.fetch_add(1, Ordering::SeqCst) + 1;
+    if alarm_log.len() >= ALARM_LOG_LEN {
+      alarm_log.pop_front();
+    }
+    alarm_log.push_back((seq, alarm));
+  }
+
+  /// 获取序号比 seq 新的报警事件
+  pub fn alarms_since(&self, seq: u32) -> Option<(u32, Vec<(u32, Alarm)>)> {
+    let alarm_log = self.alarm_log.lock().unwrap();
+    let current = self.alarm_seq.load(Ordering::SeqCst);
+    if current == seq {
+      None
+    } else {
+      let alarms = alarm_log.iter().filter(|(s, _)| *s > seq).cloned().collect();
+      Some((current, alarms))
+    }
+  }
+
+  /// 开始巡测, 清空上次巡测的样本
+  pub fn start_survey(&self) {
+    self.survey_samples.lock().unwrap().clear();
+    self.survey_version.fetch_add(1, Ordering::SeqCst);
+    self.set_survey(true);
+  }
+  pub fn survey_version(&self) -> u32 {
+    self.survey_version.load(Ordering::SeqCst)
+  }
+
+  /// 记录一个巡测样本, 只保留最近的 SURVEY_MAX_SAMPLES 个
+  pub fn publish_sample(&self, sample: Sample) {
+    let mut survey_samples = self.survey_samples.lock().unwrap();
+    let seq = self.survey_seq.fetch_add(1, Ordering::SeqCst) + 1;
+    if survey_samples.len() >= SURVEY_MAX_SAMPLES {
+      survey_samples.pop_front();
+    }
+    survey_samples.push_back((seq, sample));
+  }
+
+  /// 获取序号比 seq 新的巡测样本
+  pub fn samples_since(&self, seq: u32) -> Option<(u32, Vec<(u32, Sample)>)> {
+    let survey_samples = self.survey_samples.lock().unwrap();
+    let current = self.survey_seq.load(Ordering::SeqCst);
+    if current == seq {
+      None
+    } else {
+      let samples = survey_samples.iter().filter(|(s, _)| *s > seq).copied().collect();
+      Some((current, samples))
+    }
+  }
+
+  /// 设置传感器的读取周期
+  pub fn set_sensor_period(&self, sensor: Sensor, period: Duration) {
+    self.sensor_period[sensor as usize].store(period.as_millis() as u32, Ordering::SeqCst);
+    monotonic_ms(); // 从第一个传感器开始调度时计时
+  }
+
+  /// 记录传感器一次成功的读取
+  pub fn sensor_updated(&self, sensor: Sensor) {
+    let _batch = self.batch();
+    let i = sensor as usize;
+    self.sensor_updated[i].store(monotonic_ms() + 1, Ordering::SeqCst);
+    self.sensor_samples[i].fetch_add(1, Ordering::SeqCst);
+    if self.sensor_error[i].swap(0, Ordering::SeqCst) != 0 {
+      self.changed(sensor.topic());
+    }
+  }
+
+  /// 记录传感器一次失败的读取
+  pub fn sensor_failed(&self, sensor: Sensor, error: SensorError) {
+    let _batch = self.batch();
+    let i = sensor as usize;
+    self.sensor_failures[i].fetch_add(1, Ordering::SeqCst);
+    let error = error.to_u8().unwrap_or_default();
+    if self.sensor_error[i].swap(error, Ordering::SeqCst) != error {
+      self.changed(sensor.topic());
+    }
+  }
+
+  /// 传感器读数的状态, 超过 SENSOR_STALE_PERIODS 个周期没有成功读取时过期
+  pub fn sensor_status(&self, sensor: Sensor) -> SensorStatus {
+    let i = sensor as usize;
+    let now = monotonic_ms();
+    let updated = self.sensor_updated[i].load(Ordering::SeqCst);
+    let period = self.sensor_period[i].load(Ordering::SeqCst) as u64;
+    let age = (updated > 0).then(|| now.saturating_sub(updated - 1));
+    SensorStatus {
+      age: age.map(|age| age.min(u32::MAX as u64) as u32),
+      stale: period > 0 && age.unwrap_or(now) > period * SENSOR_STALE_PERIODS,
+      error: SensorError::from_u8(self.sensor_error[i].load(Ordering::SeqCst)),
+      samples: self.sensor_samples[i].load(Ordering::SeqCst),
+      failures: self.sensor_failures[i].load(Ordering::SeqCst),
+    }
+  }
+
+  /// 发布一次扫描结果
+  pub fn publish_scan(&self, scan: Scan) {
+    let mut last_scan = self.last_scan.lock().unwrap();
+    *last_scan = Some(scan);
+    self.scan_version.fetch_add(1, Ordering::SeqCst);
+  }
+
+  /// 获取比 version 更新的扫描结果
+  pub fn scan_since(&self, version: u32) -> Option<(u32, Scan)> {
+    let last_scan = self.last_scan.lock().unwrap();
+    let current = self.scan_version.load(Ordering::SeqCst);
+    if current == version {
+      None
+    } else {
+      last_scan.clone().map(|scan| (current, scan))
+    }
+  }
+
+  /// 主题中的字段有变化
+  /// 读取次数与读数的年龄不算作变化, 随快照过期重新生成
+  fn changed(&self, topic: Topic) {
+    self.versions[topic as usize].fetch_add(1, Ordering::SeqCst);
+  }
+  pub fn versions(&self) -> [u32; TOPICS] {
+    self.versions.each_ref().map(|version| version.load(Ordering::SeqCst))
+  }
+
+  /// 开始一个写入批次, 返回的 Batch 释放时结束; 批次内的修改一起出现在快照中
+  /// 批次可以嵌套, Response 中字段的 setter 都在批次中修改; 不同线程的批次依次进行,
+  /// 批次中不要等待硬件或文件, 最外层的批次结束时发布快照
+  pub fn batch(&self) -> Batch<'_> {
+    let address = self.address();
+    let nested = BATCHES.with_borrow(|batches| batches.contains(&address));
+    let writer = (!nested).then(|| self.writer.lock().unwrap_or_else(PoisonError::into_inner));
+    BATCHES.with_borrow_mut(|batches| batches.push(address));
+    Batch { statistics: self, writer }
+  }
+  fn address(&self) -> usize {
+    self as *const Self as usize
+  }
+
+  /// 生成并发布快照, 调用时持有写入锁
+  fn publish(&self) -> Arc<Snapshot> {
+    let snapshot =
+      Arc::new(Snapshot { versions: self.versions(), taken: monotonic_ms(), response: self.to_response() });
+    self.snapshot.store(Some(Arc::clone(&snapshot)));
+    snapshot
+  }
+
+  /// 获取快照: 读取最近一次发布的快照, 不等待正在进行的写入批次
+  /// 快照超过 SNAPSHOT_MAX_AGE_MS 时若没有写入则重新生成以更新读数的年龄, 有写入时沿用旧的快照
+  pub fn snapshot(&self) -> Arc<Snapshot> {
+    loop {
+      let snapshot = self.snapshot.load_full();
+      if let Some(snapshot) = snapshot.as_ref().filter(|s| monotonic_ms() <= s.taken + SNAPSHOT_MAX_AGE_MS) {
+        return Arc::clone(snapshot);
+      }
+      match self.writer.try_lock() {
+        Ok(_writer) => return self.publish(),
+        Err(TryLockError::Poisoned(writer)) => {
+          let _writer = writer.into_inner();
+          return self.publish();
+        }
+        Err(TryLockError::WouldBlock) => {
+          if let Some(snapshot) = snapshot {
+            return snapshot;
+          }
+          // 还没有快照: 本线程持有批次时直接生成, 否则等待当前的批次结束后发布
+          if BATCHES.with_borrow(|batches| batches.contains(&self.address())) {
+            return Arc::new(Snapshot {
+              versions: self.versions(),
+              taken: monotonic_ms(),
+              response: self.to_response(),
+            });
+          }
+          std::thread::yield_now();
+        }
+      }
+    }
   }
 
   pub fn to_response(&self) -> Response {
     Response {
       time_brightness: self.nixie().then(|| self.nixie_brightness()),
+      nixie_mode: self.nixie_mode(),
       speed_percent: self.speed(),
       distance: self.ultrasonic().then(|| self.distance() as f32 / 100_f32),
+      distance_status: self.sensor_status(Sensor::Ultrasonic),
       servos: self.servos(),
-      led: self.led(),
+      led: self.led().then(|| self.led_effect()),
+      led_color: self.led_color(),
+      led_brightness: self.led_brightness(),
       th: self.th().then(|| (self.temperature(), self.humidity())),
-      trace: self.trace(),
+      th_status: self.sensor_status(Sensor::TH),
+      trace: self.trace().then(|| self.trace_algorithm()),
+      trace_pid: self.trace_pid(),
+      marker: self.marker(),
+      stream: self.stream().then(|| self.stream_overlay()),
+      camera: self.camera(),
+      scan: self.scan(),
+      autopilot: self.autopilot().then(|| self.autopilot_state()),
+      mission: (self.mission_state() != MissionState::Idle).then(|| MissionProgress {
+        state: self.mission_state(),
+        step: self.mission_step(),
+        total: self.mission_steps.lock().unwrap().len() as u8,
+      }),
+      script: self.script(),
+      macro_state: self.macro_state(),
+      alarm: self.alarm(),
+      survey: self.survey(),
+      sound: Some(self.sound()).filter(|sound| *sound != Sound::Stop),
     }
   }
 }
 
-pub const RESPONSE_HEADER_LEN: usize = 2; // 回复包头长度
-pub const REQUEST_HEADER_LEN: usize = 1; // 请求包头长度
+pub const SCRIPT_MAX_LEN: usize = 16 * 1024; // 脚本源码的最大长度
+pub const SCRIPT_LOG_LEN: usize = 32; // 保留的脚本日志条数
+pub const ALARM_LOG_LEN: usize = 32; // 保留的报警事件条数
+pub const SURVEY_MAX_SAMPLES: usize = 4096; // 一次巡测保留的样本数
+pub const SENSOR_STALE_PERIODS: u64 = 3; // 超过几个读取周期没有成功读取时视为过期
+pub const TOPICS: usize = 4; // 主题的数量
+pub const SNAPSHOT_MAX_AGE_MS: u64 = 50; // 快照的最长复用时间, 读数的年龄精确到此
+
+/// 单调时钟 ms, 从第一次调用开始计时
+fn monotonic_ms() -> u64 {
+  static START: OnceLock<Instant> = OnceLock::new();
+  START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+pub const RESPONSE_HEADER_LEN: usize = 3; // 回复包头长度: 负载长度 (u16 大端), 回复类型
+pub const REQUEST_HEADER_LEN: usize = 3; // 请求包头长度: 负载长度 u8, 序号 (u16 大端)
+
+#[cfg(test)]
+mod test {
+  use std::{sync::Arc, time::Duration};
+
+  use super::{Ack, RequestError, Sensor, SensorError, Sound, Statistics, Topic};
+
+  #[test]
+  fn test_snapshot() {
+    let statistics = Statistics::default();
+    let snapshot = statistics.snapshot();
+    assert!(Arc::ptr_eq(&snapshot, &statistics.snapshot()));
+
+    // 值没有变化时版本不变
+    statistics.set_speed(0);
+    statistics.set_led_color((0, 0, 0));
+    assert_eq!(statistics.versions(), snapshot.versions);
+
+    statistics.set_speed(50);
+    let updated = statistics.snapshot();
+    assert_eq!(updated.response.speed_percent, 50);
+    assert_eq!(updated.versions, [0, 0, 1, 0]);
+    assert!(Arc::ptr_eq(&updated, &statistics.snapshot()));
+
+    // 读取次数不算作变化, 错误的出现与消失算作变化
+    statistics.sensor_updated(Sensor::TH);
+    assert_eq!(statistics.versions()[Topic::TH as usize], 0);
+    statistics.sensor_failed(Sensor::TH, SensorError::Timeout);
+    statistics.sensor_failed(Sensor::TH, SensorError::Timeout);
+    assert_eq!(statistics.versions()[Topic::TH as usize], 1);
+    statistics.sensor_updated(Sensor::TH);
+    assert_eq!(statistics.versions(), [0, 2, 1, 0]);
+  }
+
+  #[test]
+  fn test_sound() {
+    let statistics = Statistics::default();
+    statistics.set_sound(Sound::Beep);
+    let (sound, request) = statistics.sound_request();
+    assert_eq!(sound, Sound::Beep);
+
+    // 播放中再次请求相同的声音时重新播放, 之前的播放结束不会停止它
+    statistics.set_sound(Sound::Beep);
+    let (sound, restarted) = statistics.sound_request();
+    assert_eq!(sound, Sound::Beep);
+    assert_ne!(restarted, request);
+    statistics.finish_sound(request);
+    assert_eq!(statistics.sound(), Sound::Beep);
+    statistics.finish_sound(restarted);
+    assert_eq!(statistics.sound(), Sound::Stop);
+  }
+
+  #[test]
+  fn test_clients() {
+    let statistics = Arc::new(Statistics::default());
+    let connection = std::thread::spawn({
+      let statistics = Arc::clone(&statistics);
+      move || {
+        let _client = statistics.client_connected();
+        assert_eq!(statistics.clients(), 1);
+        panic!("连接线程 panic");
+      }
+    });
+    assert!(connection.join().is_err());
+    assert_eq!(statistics.clients(), 0);
+  }
+
+  #[test]
+  fn test_sensor_status() {
+    let statistics = Statistics::default();
+    statistics.set_sensor_period(Sensor::Ultrasonic, Duration::from_secs(60));
+    let status = statistics.sensor_status(Sensor::Ultrasonic);
+    assert_eq!((status.age, status.stale, status.error), (None, false, None));
+
+    statistics.sensor_updated(Sensor::Ultrasonic);
+    let age = statistics.sensor_status(Sensor::Ultrasonic).age.unwrap();
+
+    // 失败保留上一次成功读取的年龄, 只记录错误
+    statistics.sensor_failed(Sensor::Ultrasonic, SensorError::NoResponse);
+    statistics.sensor_failed(Sensor::Ultrasonic, SensorError::Timeout);
+    let status = statistics.sensor_status(Sensor::Ultrasonic);
+    assert!(status.age.is_some_and(|failed| failed >= age));
+    assert_eq!(status.error, Some(SensorError::Timeout));
+    assert_eq!((status.samples, status.failures), (1, 2));
+
+    // 成功清除错误, 不清除失败次数
+    statistics.sensor_updated(Sensor::Ultrasonic);
+    let status = statistics.sensor_status(Sensor::Ultrasonic);
+    assert_eq!((status.error, status.stale), (None, false));
+    assert_eq!((status.samples, status.failures), (2, 2));
+    // 其它传感器不受影响
+    assert_eq!(statistics.sensor_status(Sensor::TH).samples, 0);
+  }
+
+  #[test]
+  fn test_batch() {
+    let statistics = Arc::new(Statistics::default());
+    statistics.set_th(true);
+    let writer = std::thread::spawn({
+      let statistics = Arc::clone(&statistics);
+      move || {
+        for i in 0..300 {
+          let _batch = statistics.batch();
+          statistics.set_temperature(i as f32);
+          statistics.set_humidity(i as f32);
+        }
+      }
+    });
+
+    // 快照中的温度与湿度总是来自同一个批次
+    while !writer.is_finished() {
+      let (temperature, humidity) = statistics.snapshot().response.th.unwrap();
+      assert_eq!(temperature, humidity);
+    }
+    writer.join().unwrap();
+    assert_eq!(statistics.snapshot().response.th, Some((299_f32, 299_f32)));
+  }
+
+  #[test]
+  fn test_snapshot_during_batch() {
+    let statistics = Arc::new(Statistics::default());
+    statistics.set_speed(10);
+    let before = statistics.snapshot();
+    let (opened, open) = std::sync::mpsc::channel();
+    let (close, closed) = std::sync::mpsc::channel::<()>();
+    let writer = std::thread::spawn({
+      let statistics = Arc::clone(&statistics);
+      move || {
+        let _batch = statistics.batch();
+        statistics.set_speed(20);
+        opened.send(()).unwrap();
+        closed.recv().unwrap();
+      }
+    });
+    open.recv().unwrap();
+
+    // 批次未结束时读取不等待, 过期后仍沿用旧的快照
+    std::thread::sleep(Duration::from_millis(super::SNAPSHOT_MAX_AGE_MS + 10));
+    let snapshot = statistics.snapshot();
+    assert!(Arc::ptr_eq(&snapshot, &before));
+    assert_eq!(snapshot.response.speed_percent, 10);
+
+    close.send(()).unwrap();
+    writer.join().unwrap();
+    assert_eq!(statistics.snapshot().response.speed_percent, 20);
+  }
+
+  #[test]
+  fn test_ack() {
+    let ack = Ack { seq: 7, error: Some(RequestError::InvalidAngle) };
+    let json = serde_json::to_string(&ack).unwrap();
+    assert_eq!(json, r#"{"seq":7,"error":"InvalidAngle"}"#);
+    assert_eq!(serde_json::from_str::<Ack>(&json).unwrap(), ack);
+    let ack = Ack { seq: 8, error: None };
+    assert_eq!(serde_json::from_str::<Ack>(&serde_json::to_string(&ack).unwrap()).unwrap(), ack);
+  }
+
+  #[test]
+  fn test_topic() {
+    let fields = serde_json::to_value(Statistics::default().to_response()).unwrap();
+    for topic in Topic::ALL {
+      assert!(fields.as_object().unwrap().keys().any(|field| Topic::of(field) == topic));
+    }
+  }
+}
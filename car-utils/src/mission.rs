@@ -0,0 +1,175 @@
+//! 任务: 客户端上传的一系列步骤, 由小车依次执行
+
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::command::{variant, CommandError, Navigate};
+
+/// 任务步骤
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "step", rename_all = "lowercase")]
+#[ts(export)]
+#[rustfmt::skip]
+pub enum MissionStep {
+  Move { forward: bool, speed: u8, cm: u16, },            // 前进或后退一段距离, 按速度标定换算为时间 (forward u8, speed u8, cm u16 大端)
+  Drive { navigate: Navigate, speed: u8, ms: u16, },      // 按方向行驶一段时间 (方向 u8, speed u8, ms u16 大端)
+  Turn { degrees: i16, speed: u8, },                      // 原地转向, 正数右转 (degrees i16 大端, speed u8)
+  Servo { angle: u8, },                                   // 转动舵机 (angle u8, )
+  WaitDistance { cm: u16, },                              // 等待前方障碍物距离不大于 cm (cm u16 大端, )
+  Wait { ms: u16, },                                      // 等待 (ms u16 大端, )
+  Beep,                                                   // 鸣笛
+  Measure,                                                // 测量距离与温湿度, 推送测量结果
+}
+
+impl MissionStep {
+  pub fn buf_len(&self) -> usize {
+    match self {
+      MissionStep::Move { .. } => 5,
+      MissionStep::Drive { .. } => 5,
+      MissionStep::Turn { .. } => 4,
+      MissionStep::Servo { .. } => 2,
+      MissionStep::WaitDistance { .. } => 3,
+      MissionStep::Wait { .. } => 3,
+      MissionStep::Beep => 1,
+      MissionStep::Measure => 1,
+    }
+  }
+
+  pub fn write(&self, buf: &mut [u8]) {
+    debug_assert!(buf.len() == self.buf_len());
+
+    match *self {
+      MissionStep::Move { forward, speed, cm } => {
+        buf[0] = 0;
+        buf[1] = forward as u8;
+        buf[2] = speed;
+        buf[3..5].copy_from_slice(&cm.to_be_bytes());
+      }
+      MissionStep::Drive { navigate, speed, ms } => {
+        buf[0] = 1;
+        buf[1] = navigate.to_u8().unwrap_or_default();
+        buf[2] = speed;
+        buf[3..5].copy_from_slice(&ms.to_be_bytes());
+      }
+      MissionStep::Turn { degrees, speed } => {
+        buf[0] = 2;
+        buf[1..3].copy_from_slice(&degrees.to_be_bytes());
+        buf[3] = speed;
+      }
+      MissionStep::Servo { angle } => {
+        buf[0] = 3;
+        buf[1] = angle;
+      }
+      MissionStep::WaitDistance { cm } => {
+        buf[0] = 4;
+        buf[1..3].copy_from_slice(&cm.to_be_bytes());
+      }
+      MissionStep::Wait { ms } => {
+        buf[0] = 5;
+        buf[1..3].copy_from_slice(&ms.to_be_bytes());
+      }
+      MissionStep::Beep => {
+        buf[0] = 6;
+      }
+      MissionStep::Measure => {
+        buf[0] = 7;
+      }
+    }
+  }
+
+  /// 解析 buf 开头的一个步骤, 返回步骤与其长度
+  pub fn parse(buf: &[u8]) -> Result<(MissionStep, usize), CommandError> {
+    let u16_at = |i: usize| u16::from_be_bytes([buf[i], buf[i + 1]]);
+    let step = match buf.first() {
+      Some(0) if buf.len() >= 5 => MissionStep::Move { forward: buf[1] != 0, speed: buf[2], cm: u16_at(3) },
+      Some(1) if buf.len() >= 5 => MissionStep::Drive { navigate: variant(buf[1])?, speed: buf[2], ms: u16_at(3) },
+      Some(2) if buf.len() >= 4 => MissionStep::Turn { degrees: u16_at(1) as i16, speed: buf[3] },
+      Some(3) if buf.len() >= 2 => MissionStep::Servo { angle: buf[1] },
+      Some(4) if buf.len() >= 3 => MissionStep::WaitDistance { cm: u16_at(1) },
+      Some(5) if buf.len() >= 3 => MissionStep::Wait { ms: u16_at(1) },
+      Some(6) => MissionStep::Beep,
+      Some(7) => MissionStep::Measure,
+      Some(0..=7) | None => return Err(CommandError::ParserError),
+      Some(_) => return Err(CommandError::UnknownCommand),
+    };
+    Ok((step, step.buf_len()))
+  }
+}
+
+/// 控制正在执行的任务
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum MissionAction {
+  #[default]
+  Pause, // 暂停, 电机刹车
+  Resume, // 继续
+  Abort,  // 中止
+}
+
+/// 任务状态
+#[repr(u8)]
+#[derive(TS, FromPrimitive, ToPrimitive, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum MissionState {
+  #[default]
+  Idle, // 没有任务
+  Running, // 执行中
+  Paused,  // 已暂停
+  Done,    // 已完成
+  Aborted, // 已中止
+}
+
+impl MissionState {
+  /// 任务是否还未结束
+  pub fn is_active(self) -> bool {
+    matches!(self, MissionState::Running | MissionState::Paused)
+  }
+}
+
+/// 任务进度
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct MissionProgress {
+  pub state: MissionState,
+  pub step: u8,  // 正在执行的步骤序号, 完成后等于 total
+  pub total: u8, // 步骤总数
+}
+
+/// 任务中 Measure 步骤的测量结果
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[ts(export)]
+pub struct Measurement {
+  pub step: u8,               // 步骤序号
+  pub distance: Option<f32>,  // 障碍物距离 m, 测量失败为 None
+  pub th: Option<(f32, f32)>, // 温度, 湿度, 未开启温湿传感器为 None
+}
+
+#[cfg(test)]
+mod test {
+  use super::MissionStep;
+  use crate::command::Navigate;
+
+  #[test]
+  fn test_encode() {
+    let steps = [
+      MissionStep::Move { forward: true, speed: 60, cm: 120 },
+      MissionStep::Drive { navigate: Navigate::Left, speed: 40, ms: 1500 },
+      MissionStep::Turn { degrees: -90, speed: 50 },
+      MissionStep::Servo { angle: 45 },
+      MissionStep::WaitDistance { cm: 300 },
+      MissionStep::Wait { ms: 2000 },
+      MissionStep::Beep,
+      MissionStep::Measure,
+    ];
+    for step in steps {
+      let mut buf = vec![0; step.buf_len()];
+      step.write(&mut buf);
+      assert_eq!(MissionStep::parse(&buf).unwrap(), (step, buf.len()));
+      // 长度不足
+      assert!(MissionStep::parse(&buf[..buf.len() - 1]).is_err());
+    }
+  }
+}